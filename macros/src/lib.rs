@@ -0,0 +1,157 @@
+//! Procedural macros for archetype. These are re-exported by archetype
+//! itself and shouldn't need to be depended on directly.
+
+use proc_macro::{TokenStream, TokenTree};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bake every snapshot under a directory into the binary as a
+/// `BundledStorage`.
+///
+/// The directory is relative to the manifest of the crate using the
+/// macro. Each file ending in the snapshot extension becomes the snapshot
+/// under its path relative to the directory, with the extension removed,
+/// so `snapshots/api/users.snap` is the snapshot under `api/users`. The
+/// extension is `snap` unless `archetype.toml`, or the
+/// `[package.metadata.archetype]` table of `Cargo.toml`, sets another.
+/// Anything else in the directory, such as pending `.snap.new` snapshots,
+/// patches, overrides, or the `objects` of content-addressed snapshots, is
+/// left out.
+#[proc_macro]
+pub fn embed_snapshots(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(expanded) => expanded.parse().expect("expansion should be valid Rust"),
+        Err(message) => format!("compile_error!({:?})", message)
+            .parse()
+            .expect("compile_error! should be valid Rust"),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let dir = directory(input)?;
+    let manifest = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| String::from("embed_snapshots! must be used within a cargo build"))?;
+    let extension = extension(Path::new(&manifest))?;
+    let root = Path::new(&manifest).join(dir);
+    let mut files = Vec::new();
+    walk(&root, &root.join("objects"), &mut files)
+        .map_err(|err| format!("unable to read snapshots in {}: {}", root.display(), err))?;
+    files.sort();
+
+    let mut expanded = String::from("::archetype::store::BundledStorage::new()");
+    for file in files {
+        let relative = file.strip_prefix(&root).expect("walked within root");
+        let Some(key) = key(relative, &extension) else {
+            continue;
+        };
+        let path = file
+            .to_str()
+            .ok_or_else(|| format!("{} is not valid UTF-8", file.display()))?;
+        expanded.push_str(&format!(".with({:?}, include_str!({:?}))", key, path));
+    }
+    Ok(expanded)
+}
+
+/// The single string literal naming the directory.
+fn directory(input: TokenStream) -> Result<String, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let literal = match tokens.as_slice() {
+        [TokenTree::Literal(literal)] => literal.to_string(),
+        _ => {
+            return Err(String::from(
+                "expected a directory, e.g. embed_snapshots!(\"snapshots\")",
+            ))
+        }
+    };
+    literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .filter(|literal| !literal.contains('\\'))
+        .map(String::from)
+        .ok_or_else(|| String::from("expected a plain string literal naming a directory"))
+}
+
+/// The snapshot extension configured for the crate whose manifest is in
+/// `dir`, as archetype itself finds it.
+fn extension(dir: &Path) -> Result<String, String> {
+    let parse = |path: &Path| -> Result<Option<toml::Table>, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map(Some)
+                .map_err(|err| format!("invalid {}: {}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("unable to read {}: {}", path.display(), err)),
+        }
+    };
+    let table = match parse(&dir.join("archetype.toml"))? {
+        Some(table) => Some(table),
+        None => parse(&dir.join("Cargo.toml"))?.and_then(|manifest| {
+            manifest
+                .get("package")?
+                .get("metadata")?
+                .get("archetype")?
+                .as_table()
+                .cloned()
+        }),
+    };
+    let extension = table.as_ref().and_then(|table| table.get("extension"));
+    match extension {
+        None => Ok(String::from("snap")),
+        Some(toml::Value::String(extension)) => Ok(extension.clone()),
+        Some(other) => Err(format!(
+            "expected the extension to be a string, not {}",
+            other
+        )),
+    }
+}
+
+/// Collect every file under `dir`, other than those under `skip`.
+fn walk(dir: &Path, skip: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path != skip {
+                walk(&path, skip, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The snapshot key for a file at `relative` within the snapshot
+/// directory, or `None` if it isn't a snapshot ending in `.{extension}`.
+fn key(relative: &Path, extension: &str) -> Option<String> {
+    let parts: Vec<&str> = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<_>>()?;
+    let key = parts.join("/");
+    key.strip_suffix(&format!(".{}", extension))
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key;
+    use std::path::Path;
+
+    #[test]
+    fn only_snapshots_have_keys() {
+        let key = |relative: &str| key(Path::new(relative), "snap");
+        assert_eq!(key("api/users.snap").as_deref(), Some("api/users"));
+        assert_eq!(
+            key("multi_search.json.snap").as_deref(),
+            Some("multi_search.json")
+        );
+        for other in [
+            "search.snap.new",
+            "search.snap.patch",
+            "archetype.overrides.toml",
+            "search",
+        ] {
+            assert_eq!(key(other), None);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Snapshots of audio, as fingerprints compared with tolerance.
+//!
+//! DSP code rarely produces the same samples twice across platforms,
+//! compilers, or harmless refactors, so the samples themselves make for
+//! brittle snapshots. A fingerprint of how loud each short window of the
+//! audio is, and roughly at what pitch, is stored instead, and a new one
+//! matches so long as each window is within tolerance of the stored one.
+
+use std::time::Duration;
+
+/// The quietest level stored, in dBFS, which silence is clamped to.
+const FLOOR: f32 = -120.0;
+
+/// A snapshot of a buffer of mono samples, between `-1.0` and `1.0`,
+/// stored as a fingerprint of each window of it: its start, its RMS level
+/// in dBFS, and its pitch as estimated from how often it crosses zero.
+///
+/// ```text
+/// 8000 Hz, windows of 50ms
+/// 0.000s -3.0 dB 440 Hz
+/// 0.050s -9.1 dB 440 Hz
+/// ```
+///
+/// The snapshot matches if every window's level and pitch are within
+/// tolerance of the stored ones, and otherwise fails as `snap` would,
+/// naming the window furthest out of tolerance.
+///
+/// ```
+/// let rate = 8000;
+/// let tone: Vec<f32> = (0..rate / 5)
+///     .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / rate as f32).sin() * 0.5)
+///     .collect();
+/// archetype::Audio::new("audio-example", &tone, rate)
+///     .level_tolerance(0.5)
+///     .snap();
+/// ```
+#[must_use = "the snapshot is only asserted by `snap`"]
+#[derive(Clone, Debug)]
+pub struct Audio<'a> {
+    key: String,
+    samples: &'a [f32],
+    sample_rate: u32,
+    window: Duration,
+    level_tolerance: f32,
+    pitch_tolerance: f32,
+}
+
+impl<'a> Audio<'a> {
+    /// Start a snapshot under `key` of `samples`, sampled at
+    /// `sample_rate`. Mix multichannel audio down to mono first.
+    pub fn new(key: impl Into<String>, samples: &'a [f32], sample_rate: u32) -> Audio<'a> {
+        Audio {
+            key: key.into(),
+            samples,
+            sample_rate,
+            window: Duration::from_millis(50),
+            level_tolerance: 1.0,
+            pitch_tolerance: 0.05,
+        }
+    }
+
+    /// Fingerprint windows of `window`. Defaults to 50ms.
+    pub fn window(mut self, window: Duration) -> Audio<'a> {
+        self.window = window;
+        self
+    }
+
+    /// Tolerate levels up to `decibels` louder or quieter than the stored
+    /// ones. Defaults to 1dB.
+    pub fn level_tolerance(mut self, decibels: f32) -> Audio<'a> {
+        self.level_tolerance = decibels;
+        self
+    }
+
+    /// Tolerate pitches up to `ratio` higher or lower than the stored
+    /// ones, as a fraction of the higher. Defaults to `0.05`.
+    pub fn pitch_tolerance(mut self, ratio: f32) -> Audio<'a> {
+        self.pitch_tolerance = ratio;
+        self
+    }
+
+    /// Assert the fingerprint against the one stored under the key,
+    /// writing it if it is missing.
+    pub fn snap(self) {
+        let subject = self.fingerprint();
+        let Some(stored) = crate::stored(&self.key) else {
+            return crate::snap(&self.key, subject);
+        };
+        match self.outside(&stored, &subject) {
+            // Asserting what's stored counts the snapshot as matched,
+            // and leaves it as it is.
+            None => crate::snap(&self.key, stored),
+            Some(worst) => {
+                if let Err(failure) = crate::check(&self.key, subject, false) {
+                    panic!("{}\n{}", failure, worst);
+                }
+            }
+        }
+    }
+
+    fn fingerprint(&self) -> String {
+        let rate = self.sample_rate as f64;
+        let length = ((rate * self.window.as_secs_f64()).round() as usize).max(1);
+        let mut out = format!("{} Hz, windows of {:?}\n", self.sample_rate, self.window);
+        for (i, window) in self.samples.chunks(length).enumerate() {
+            let squares: f64 = window.iter().map(|&x| f64::from(x) * f64::from(x)).sum();
+            let rms = (squares / window.len() as f64).sqrt();
+            let level = (20.0 * rms.log10()).max(f64::from(FLOOR));
+            // Half a cycle passes between each crossing and the next.
+            let crossings: Vec<usize> = (1..window.len())
+                .filter(|&i| (window[i - 1] < 0.0) != (window[i] < 0.0))
+                .collect();
+            let pitch = match (crossings.first(), crossings.last()) {
+                (Some(first), Some(last)) if last > first => {
+                    (crossings.len() - 1) as f64 * rate / (2.0 * (last - first) as f64)
+                }
+                _ => 0.0,
+            };
+            out.push_str(&format!(
+                "{:.3}s {:.1} dB {:.0} Hz\n",
+                (i * length) as f64 / rate,
+                level,
+                pitch
+            ));
+        }
+        out
+    }
+
+    /// How the window of `subject` furthest out of tolerance of `stored`
+    /// differs, unless every window is within tolerance.
+    fn outside(&self, stored: &str, subject: &str) -> Option<String> {
+        let (stored_lines, subject_lines) = (stored.lines(), subject.lines());
+        if stored_lines.clone().count() != subject_lines.clone().count()
+            || stored.lines().next() != subject.lines().next()
+        {
+            return Some(String::from(
+                "the fingerprints differ in sample rate, window, or length",
+            ));
+        }
+        let mut worst: Option<(f32, String)> = None;
+        for (stored, subject) in stored_lines.zip(subject_lines).skip(1) {
+            let (Some(old), Some(new)) = (window(stored), window(subject)) else {
+                return Some(format!("unable to read fingerprint {:?}", stored));
+            };
+            let level = (old.1 - new.1).abs() / self.level_tolerance;
+            let higher = old.2.max(new.2);
+            let pitch = match higher > 0.0 {
+                true => (old.2 - new.2).abs() / higher / self.pitch_tolerance,
+                false => 0.0,
+            };
+            let (score, difference) = match level >= pitch {
+                true => (
+                    level,
+                    format!(
+                        "level {} dB -> {} dB, beyond {} dB",
+                        old.1, new.1, self.level_tolerance
+                    ),
+                ),
+                false => (
+                    pitch,
+                    format!(
+                        "pitch {} Hz -> {} Hz, beyond {}",
+                        old.2, new.2, self.pitch_tolerance
+                    ),
+                ),
+            };
+            if score > 1.0 && worst.as_ref().is_none_or(|(worst, _)| score > *worst) {
+                let at = format!("furthest out of tolerance at {}: {}", old.0, difference);
+                worst = Some((score, at));
+            }
+        }
+        worst.map(|(_, at)| at)
+    }
+}
+
+/// Take a snapshot of `samples`, sampled at `sample_rate`, as `Audio`
+/// would with its default window and tolerances.
+///
+/// ```
+/// let silence = vec![0.0; 4000];
+/// archetype::snap_audio("audio-silence-example", &silence, 8000);
+/// ```
+pub fn snap_audio(key: &str, samples: &[f32], sample_rate: u32) {
+    Audio::new(key, samples, sample_rate).snap();
+}
+
+/// The start, level, and pitch of a line of a fingerprint.
+fn window(line: &str) -> Option<(&str, f32, f32)> {
+    let mut fields = line.split_whitespace();
+    let start = fields.next()?;
+    let level = fields.next()?.parse().ok()?;
+    let pitch = fields.nth(1)?.parse().ok()?;
+    Some((start, level, pitch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Audio;
+
+    fn tone(frequency: f32, gain: f32) -> Vec<f32> {
+        (0..1600)
+            .map(|i| (i as f32 * frequency * std::f32::consts::TAU / 8000.0).sin() * gain)
+            .collect()
+    }
+
+    #[test]
+    fn fingerprints_match_within_tolerance() {
+        let stored = tone(440.0, 0.5);
+        let audio = Audio::new("audio_tone", &stored, 8000);
+        let fingerprint = audio.fingerprint();
+        assert_eq!(
+            fingerprint.lines().take(2).collect::<Vec<_>>(),
+            ["8000 Hz, windows of 50ms", "0.000s -9.0 dB 441 Hz"]
+        );
+        audio.snap();
+
+        let quieter = tone(440.0, 0.48);
+        let subject = Audio::new("audio_tone", &quieter, 8000).fingerprint();
+        assert_eq!(audio_outside(&fingerprint, &subject), None);
+
+        let flat = tone(400.0, 0.5);
+        let subject = Audio::new("audio_tone", &flat, 8000).fingerprint();
+        assert_eq!(
+            audio_outside(&fingerprint, &subject).as_deref(),
+            Some("furthest out of tolerance at 0.100s: pitch 441 Hz -> 399 Hz, beyond 0.05")
+        );
+
+        let shorter = &stored[..800];
+        let subject = Audio::new("audio_tone", shorter, 8000).fingerprint();
+        assert!(audio_outside(&fingerprint, &subject).is_some());
+    }
+
+    fn audio_outside(stored: &str, subject: &str) -> Option<String> {
+        Audio::new("audio_tone", &[], 8000).outside(stored, subject)
+    }
+}
@@ -0,0 +1,155 @@
+//! Checks that stored snapshots are fit to commit.
+//!
+//! These are meant for pre-commit and pre-push hooks, by way of `cargo
+//! archetype check`, to catch snapshots that were left for review, that no
+//! test asserts against anymore, or that link to snapshots that are gone.
+
+use crate::store;
+use crate::GlobalConfig;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+
+/// Something wrong with the stored snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A pending copy of a snapshot, such as `search.snap.new`, that was
+    /// left for review and never accepted or discarded.
+    Pending {
+        /// Where the pending copy is.
+        location: String,
+    },
+    /// A snapshot that no test referenced.
+    Orphaned {
+        /// The key of the snapshot.
+        key: String,
+    },
+    /// A snapshot that links to one that doesn't exist.
+    DanglingLink {
+        /// The key of the link.
+        key: String,
+        /// The key it links to.
+        target: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Pending { location } => write!(f, "pending snapshot at {}", location),
+            Problem::Orphaned { key } => write!(f, "no test references the snapshot {}", key),
+            Problem::DanglingLink { key, target } => {
+                write!(f, "{} links to {}, which doesn't exist", key, target)
+            }
+        }
+    }
+}
+
+/// Find the problems with the snapshots stored as `config` describes. A
+/// snapshot is orphaned if it isn't among the `referenced` keys, such as
+/// those in a coverage file, which are only checked when given.
+pub fn problems(config: &GlobalConfig, referenced: Option<&[&str]>) -> io::Result<Vec<Problem>> {
+    let storage = config.snapshot_storage();
+    let mut problems: Vec<Problem> = pending(config)?
+        .into_iter()
+        .map(|location| Problem::Pending { location })
+        .collect();
+    let entries = store::list_in(&*storage)?;
+    for entry in &entries {
+        let Some(target) = &entry.link else {
+            continue;
+        };
+        if storage.read(target)?.is_none() {
+            problems.push(Problem::DanglingLink {
+                key: entry.key.clone(),
+                target: target.clone(),
+            });
+        }
+    }
+    if let Some(referenced) = referenced {
+        let referenced: BTreeSet<&str> = referenced.iter().copied().collect();
+        problems.extend(
+            entries
+                .into_iter()
+                .filter(|entry| !referenced.contains(entry.key.as_str()))
+                .map(|entry| Problem::Orphaned { key: entry.key }),
+        );
+    }
+    Ok(problems)
+}
+
+/// Where the pending snapshots under each snapshot directory are.
+#[cfg(feature = "fs")]
+fn pending(config: &GlobalConfig) -> io::Result<Vec<String>> {
+    use crate::store::Storage;
+
+    // Snapshots kept elsewhere than in files can't be left pending.
+    if config.storage.is_some() {
+        return Ok(Vec::new());
+    }
+    let extension = format!("{}.new", config.extension);
+    let mut locations = BTreeSet::new();
+    for dir in config.snapshot_dirs() {
+        let pending = store::FsStorage::new(dir, &extension);
+        for key in pending.keys()? {
+            locations.insert(pending.location(&key));
+        }
+    }
+    Ok(locations.into_iter().collect())
+}
+
+#[cfg(not(feature = "fs"))]
+fn pending(_: &GlobalConfig) -> io::Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{problems, Problem};
+    use crate::store::BundledStorage;
+    use crate::GlobalConfig;
+
+    #[test]
+    fn finds_orphans_and_dangling_links() {
+        let config = GlobalConfig::new().storage(
+            BundledStorage::new()
+                .with("search", "by-term\n")
+                .with("links/search", "archetype-link: search\n")
+                .with("links/gone", "archetype-link: gone\n"),
+        );
+        assert_eq!(
+            problems(&config, None).unwrap(),
+            vec![Problem::DanglingLink {
+                key: String::from("links/gone"),
+                target: String::from("gone"),
+            }]
+        );
+        let problems = problems(&config, Some(&["search", "links/search"])).unwrap();
+        assert_eq!(
+            problems[1..],
+            [Problem::Orphaned {
+                key: String::from("links/gone")
+            }]
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn finds_pending_snapshots() {
+        let dir = std::env::temp_dir().join(format!("archetype-audit-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("api")).unwrap();
+        std::fs::write(dir.join("search.snap"), "by-term\n").unwrap();
+        std::fs::write(dir.join("api/users.snap.new"), "[]\n").unwrap();
+        let config = GlobalConfig::new().root(&dir);
+        assert_eq!(
+            problems(&config, None).unwrap(),
+            vec![Problem::Pending {
+                location: dir
+                    .join("api/users.snap.new")
+                    .to_string_lossy()
+                    .into_owned()
+            }]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Keys derived from where a snapshot is taken, for `snapshot!`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+thread_local! {
+    /// How many snapshots each function has taken on this thread.
+    static TAKEN: RefCell<HashMap<String, usize>> = RefCell::new(HashMap::new());
+}
+
+/// The key of the next snapshot taken in the function enclosing `item`, a
+/// function defined within it: its module path, starting with the
+/// crate's name, and its name, with `::` as `/`, and how many snapshots
+/// it has taken so far on this thread, such as
+/// `app/search/tests/by_term-2`. Each test runs on its own thread, so
+/// counting restarts with every run.
+///
+/// When the function is run by a case of a parameterized test, as
+/// generated by `rstest` or `test-case`, the name of the case follows the
+/// function's, such as `app/search/tests/by_term/case_1_empty-2`.
+#[doc(hidden)]
+pub fn key<F>(_item: F) -> String {
+    let mut function = path(std::any::type_name::<F>());
+    if let Some(case) = std::thread::current()
+        .name()
+        .and_then(|test| case(&function, test))
+    {
+        function = format!("{}/{}", function, case);
+    }
+    let taken = TAKEN.with(|taken| {
+        let mut taken = taken.borrow_mut();
+        let count = taken.entry(function.clone()).or_default();
+        *count += 1;
+        *count
+    });
+    format!("{}-{}", function, taken)
+}
+
+/// The path of the function enclosing the item whose type name is `name`,
+/// as a key, leaving out the item itself and any closures it's within.
+fn path(name: &str) -> String {
+    let mut segments: Vec<&str> = name.split("::").collect();
+    segments.pop();
+    while segments.last() == Some(&"{{closure}}") {
+        segments.pop();
+    }
+    segments.join("/")
+}
+
+/// The case that the test named `test` runs the function at `function`
+/// for, if it's a case of a parameterized test of the function.
+///
+/// Each test runs on a thread named after the test's path, without the
+/// crate's name, and `rstest` and `test-case` generate a test for each
+/// case in a module named after the function, so the test is named after
+/// the function followed by the case.
+fn case<'a>(function: &str, test: &'a str) -> Option<&'a str> {
+    let (parent, case) = test.rsplit_once("::")?;
+    let parent = parent.replace("::", "/");
+    let parameterized = function
+        .strip_suffix(&parent)
+        .is_some_and(|krate| krate.ends_with('/'));
+    parameterized.then_some(case)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{case, key, path};
+
+    #[test]
+    fn keys_count_snapshots_per_function() {
+        fn item() {}
+        assert_eq!(
+            key(item),
+            "archetype/auto/tests/keys_count_snapshots_per_function-1"
+        );
+        assert_eq!(
+            key(item),
+            "archetype/auto/tests/keys_count_snapshots_per_function-2"
+        );
+        assert_eq!(
+            path("app::search::tests::by_term::{{closure}}::item"),
+            "app/search/tests/by_term"
+        );
+    }
+
+    #[test]
+    fn parameterized_cases_are_keyed_apart() {
+        let function = "app/search/tests/by_term";
+        assert_eq!(
+            case(function, "search::tests::by_term::case_1_empty"),
+            Some("case_1_empty")
+        );
+        assert_eq!(case(function, "search::tests::by_term"), None);
+        assert_eq!(case(function, "search::tests::other::case_1"), None);
+        assert_eq!(case(function, "arch::tests::by_term::case_1"), None);
+
+        let key = std::thread::Builder::new()
+            .name(String::from("auto::tests::by_term::case_2_unicode"))
+            .spawn(by_term)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(key, "archetype/auto/tests/by_term/case_2_unicode-1");
+    }
+
+    fn by_term() -> String {
+        fn item() {}
+        key(item)
+    }
+}
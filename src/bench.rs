@@ -0,0 +1,201 @@
+//! Snapshots of benchmark and profiling summaries, with the measurements
+//! rounded so that run-to-run noise doesn't fail them.
+
+use crate::{Rounding, Settings};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// The units a whole number is rounded with in a text summary.
+const UNITS: &[&str] = &[
+    "ps", "ns", "us", "µs", "μs", "ms", "s", "%", "B", "KB", "MB", "GB", "KiB", "MiB", "GiB",
+    "B/s", "KB/s", "MB/s", "GB/s", "KiB/s", "MiB/s", "GiB/s", "elem/s", "Kelem/s", "Melem/s",
+    "Gelem/s",
+];
+
+/// Take a snapshot of a benchmark or profiling summary, such as what
+/// `criterion` prints, under a file with the name `key`, with each
+/// measurement rounded as set with `Settings::rounding`.
+///
+/// A measurement is a decimal number, or a whole number followed by a
+/// unit of time, size, throughput, or a percentage. Other whole numbers
+/// are left alone, since they're as likely to be part of a benchmark's
+/// name as a count of samples, as are numbers within words, such as
+/// `x86_64`.
+///
+/// ```
+/// archetype::snap_bench(
+///     "bench-example",
+///     "search/by-term 20     time:   [26.029 us 26.251 us 26.505 us]\n\
+///      change: [-1.2043% +0.4837% +2.1102%] (p = 0.45 > 0.05)\n",
+/// );
+/// ```
+pub fn snap_bench(key: &str, summary: &str) {
+    crate::snap(key, round_text(Settings::current().rounding, summary));
+}
+
+/// Take a snapshot of a benchmark or profiling summary as JSON, such as
+/// the estimates `criterion` saves, under a file with the name `key`,
+/// with every number in it rounded as set with `Settings::rounding`.
+///
+/// ```
+/// use archetype::{Rounding, Settings};
+///
+/// Settings::new().rounding(Rounding::Powers(2.0)).bind(|| {
+///     archetype::snap_json_bench(
+///         "json-bench-example",
+///         &serde_json::json!({
+///             "mean": { "point_estimate": 26251.3, "standard_error": 61.9 },
+///             "samples": 100,
+///         }),
+///     )
+/// });
+/// ```
+pub fn snap_json_bench<A: Serialize>(key: &str, summary: &A) {
+    let mut summary = serde_json::to_value(summary).expect("should serialize");
+    round_json(Settings::current().rounding, &mut summary);
+    crate::snap_json(key, &summary);
+}
+
+fn round_text(rounding: Rounding, summary: &str) -> String {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let number = NUMBER.get_or_init(|| {
+        Regex::new(r"([+-]?)(\d+(?:\.\d+)?)(\s?)([A-Za-zµμ%/]*)").expect("valid number pattern")
+    });
+    number
+        .replace_all(summary, |captures: &Captures| {
+            let whole = &captures[0];
+            let start = captures.get(0).map_or(0, |m| m.start());
+            let within_word = summary[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
+            let (digits, unit) = (&captures[2], &captures[4]);
+            let measured = digits.contains('.') || UNITS.contains(&unit);
+            if within_word || !measured {
+                return whole.to_string();
+            }
+            let value: f64 = digits.parse().expect("matched a number");
+            format!(
+                "{}{}{}{}",
+                &captures[1],
+                round(rounding, value),
+                &captures[3],
+                unit
+            )
+        })
+        .into_owned()
+}
+
+fn round_json(rounding: Rounding, value: &mut Value) {
+    match value {
+        Value::Number(number) => {
+            if let Some(rounded) = number
+                .as_f64()
+                .and_then(|n| serde_json::from_str(&round(rounding, n)).ok())
+            {
+                *number = rounded;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| round_json(rounding, item)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| round_json(rounding, field)),
+        Value::Null | Value::Bool(_) | Value::String(_) => {}
+    }
+}
+
+/// `value` rounded as `rounding` does, written without any more decimal
+/// places than the rounding keeps.
+fn round(rounding: Rounding, value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+    match rounding {
+        Rounding::Significant(figures) => {
+            let magnitude = value.abs().log10().floor() as i32;
+            let mut places = figures.max(1) as i32 - 1 - magnitude;
+            let scale = 10f64.powi(places);
+            let rounded = (value * scale).round() / scale;
+            // Rounding up to the next power of ten, as 0.996 does to 1.0,
+            // leaves a figure fewer after the point.
+            if rounded.abs() >= 10f64.powi(magnitude + 1) {
+                places -= 1;
+            }
+            format!("{:.*}", places.max(0) as usize, rounded)
+        }
+        Rounding::Multiple(multiple) => {
+            let places = multiple
+                .to_string()
+                .split_once('.')
+                .map_or(0, |(_, fraction)| fraction.len());
+            format!("{:.*}", places, (value / multiple).round() * multiple)
+        }
+        Rounding::Powers(base) => {
+            let power = base.powf((value.abs().ln() / base.ln()).round());
+            // Keep enough figures to write any power exactly, without the
+            // error `powf` leaves behind.
+            let rounded = round(Rounding::Significant(12), power.copysign(value));
+            match rounded.contains('.') {
+                true => rounded.trim_end_matches('0').trim_end_matches('.').into(),
+                false => rounded,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{round, round_json, round_text};
+    use crate::Rounding;
+
+    #[test]
+    fn rounds_each_way() {
+        let significant = Rounding::Significant(2);
+        assert_eq!(round(significant, 26.251), "26");
+        assert_eq!(round(significant, 0.012345), "0.012");
+        assert_eq!(round(significant, 12345.0), "12000");
+        assert_eq!(round(significant, -1.2043), "-1.2");
+        assert_eq!(round(significant, 0.996), "1.0");
+        assert_eq!(round(Rounding::Multiple(5.0), 26.251), "25");
+        assert_eq!(round(Rounding::Multiple(0.25), 26.4), "26.50");
+        assert_eq!(round(Rounding::Powers(2.0), 26.251), "32");
+        assert_eq!(round(Rounding::Powers(10.0), 0.0123), "0.01");
+        assert_eq!(round(Rounding::Powers(2.0), -0.3), "-0.25");
+        assert_eq!(round(significant, 0.0), "0");
+    }
+
+    #[test]
+    fn rounds_measurements_in_text() {
+        assert_eq!(
+            round_text(
+                Rounding::default(),
+                "x86_64 fib 20  time: [26.029 us 26.251 us 26.505 us]\n\
+                 thrpt: 381 Melem/s, change: +0.4837% (p = 0.45)\n\
+                 Found 3 outliers among 100 measurements (3.00%)\n"
+            ),
+            "x86_64 fib 20  time: [26 us 26 us 27 us]\n\
+             thrpt: 380 Melem/s, change: +0.48% (p = 0.45)\n\
+             Found 3 outliers among 100 measurements (3.0%)\n"
+        );
+    }
+
+    #[test]
+    fn rounds_every_number_in_json() {
+        let mut summary = serde_json::json!({
+            "mean": { "point_estimate": 26251.3, "unit": "ns 12.5" },
+            "samples": [1204, 0.987],
+            "ok": true,
+        });
+        round_json(Rounding::default(), &mut summary);
+        assert_eq!(
+            summary,
+            serde_json::json!({
+                "mean": { "point_estimate": 26000, "unit": "ns 12.5" },
+                "samples": [1200, 0.99],
+                "ok": true,
+            })
+        );
+    }
+}
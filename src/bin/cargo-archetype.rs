@@ -0,0 +1,311 @@
+//! `cargo archetype`, tooling for working with snapshot files.
+//!
+//! # merge-driver
+//!
+//! A git merge driver that merges JSON snapshots structurally, so that
+//! concurrent updates to different fields of a snapshot don't conflict.
+//! Register it once per clone:
+//!
+//! ```text
+//! git config merge.archetype.name "archetype snapshot merge"
+//! git config merge.archetype.driver "cargo archetype merge-driver %O %A %B"
+//! ```
+//!
+//! and route snapshots to it in `.gitattributes`:
+//!
+//! ```text
+//! *.snap merge=archetype
+//! ```
+//!
+//! Merged snapshots keep the key order they were written with, so they
+//! still match the output they were taken from. Any metadata headers above
+//! the snapshots are merged field by field, apart from the snapshots
+//! themselves. Snapshots that aren't JSON, or
+//! whose changes overlap, fall back to `git merge-file` and are left with
+//! the usual conflict markers.
+//!
+//! # rename
+//!
+//! Rename the snapshot under one key to another, along with any links to
+//! it, in the crate in the current directory:
+//!
+//! ```text
+//! cargo archetype rename search_by_term search/by-term
+//! ```
+//!
+//! # renames
+//!
+//! Suggest renames for snapshots that a coverage file (see `coverage`)
+//! shows no test referenced, pairing each with the most similar snapshot
+//! that was, as happens when a test is renamed and its snapshot recorded
+//! again. With `--apply`, replace each new snapshot with the old one,
+//! keeping its metadata and links:
+//!
+//! ```text
+//! cargo archetype renames target/coverage.keys
+//! cargo archetype renames target/coverage.keys --apply
+//! ```
+//!
+//! # check
+//!
+//! Fail if any snapshot is pending review, as `search.snap.new`, or links
+//! to a snapshot that doesn't exist, and, given a coverage file (see
+//! `coverage`), if any snapshot isn't referenced by a test. This is meant
+//! for pre-commit and pre-push hooks:
+//!
+//! ```text
+//! cargo archetype check
+//! cargo archetype check target/coverage.keys
+//! ```
+//!
+//! # lock
+//!
+//! Lock a snapshot so that updates never rewrite it unless forced with
+//! `ARCHETYPE_FORCE=1`, or unlock it again:
+//!
+//! ```text
+//! cargo archetype lock api/search-contract
+//! cargo archetype unlock api/search-contract
+//! ```
+//!
+//! # compare
+//!
+//! Report the snapshots added, removed, and changed between two snapshot
+//! directories, such as the same suite checked out from two branches side
+//! by side, failing if there are any. Both are read with the crate's
+//! extension and layout:
+//!
+//! ```text
+//! cargo archetype compare snapshots-baseline snapshots
+//! ```
+//!
+//! # coverage
+//!
+//! Report how many of the crate's snapshots were referenced by the keys
+//! recorded in a coverage file, by directory, and list those that weren't:
+//!
+//! ```text
+//! rm -f target/coverage.keys
+//! ARCHETYPE_COVERAGE=$PWD/target/coverage.keys cargo test
+//! cargo archetype coverage target/coverage.keys
+//! ```
+
+use archetype::GlobalConfig;
+use std::fs;
+use std::process::{Command, ExitCode};
+
+const USAGE: &str = "usage: cargo archetype <command>
+
+commands:
+  merge-driver <base> <current> <other>   merge snapshots for git
+  rename <old> <new>                      rename a snapshot
+  renames <file> [--apply]                suggest renames for orphaned snapshots
+  check [<file>]                          check snapshots are fit to commit
+  lock <key>                              protect a snapshot from updates
+  unlock <key>                            allow a snapshot to be updated
+  compare <before> <after>                report changes between snapshot dirs
+  coverage <file>                         report snapshots a run referenced";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // Cargo passes the subcommand name along when run as `cargo archetype`.
+    if args.first().map(String::as_str) == Some("archetype") {
+        args.remove(0);
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["merge-driver", base, current, other] => merge_driver(base, current, other),
+        ["rename", old, new] => rename(old, new),
+        ["renames", file] => renames(file, false),
+        ["renames", file, "--apply"] => renames(file, true),
+        ["check"] => check(None),
+        ["check", file] => check(Some(file)),
+        ["lock", key] => lock(key, true),
+        ["unlock", key] => lock(key, false),
+        ["compare", before, after] => compare(before, after),
+        ["coverage", file] => coverage(file),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// The configuration of the crate containing the current directory.
+fn config() -> GlobalConfig {
+    let mut dir = std::env::current_dir().unwrap_or_else(|err| {
+        eprintln!("unable to find the current directory: {}", err);
+        std::process::exit(2)
+    });
+    loop {
+        if dir.join("Cargo.toml").exists() {
+            return GlobalConfig::load_from(dir);
+        }
+        if !dir.pop() {
+            eprintln!("unable to find Cargo.toml in this directory or any parent");
+            std::process::exit(2)
+        }
+    }
+}
+
+fn rename(old: &str, new: &str) -> ExitCode {
+    let storage = config().snapshot_storage();
+    match archetype::store::rename(&*storage, old, new) {
+        Ok(()) => {
+            println!(
+                "renamed {} to {}",
+                storage.location(old),
+                storage.location(new)
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("unable to rename {} to {}: {}", old, new, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn renames(file: &str, apply: bool) -> ExitCode {
+    let referenced = fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("unable to read {}: {}", file, err);
+        std::process::exit(2)
+    });
+    let storage = config().snapshot_storage();
+    let renames = match archetype::store::renames(&*storage, referenced.lines()) {
+        Ok(renames) => renames,
+        Err(err) => {
+            eprintln!("unable to list snapshots: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    if renames.is_empty() {
+        println!("no likely renames");
+    }
+    for rename in renames {
+        println!(
+            "{} -> {} ({:.0}% similar)",
+            rename.old,
+            rename.new,
+            rename.similarity * 100.0
+        );
+        if apply {
+            if let Err(err) = rename.apply(&*storage) {
+                eprintln!("unable to rename {} to {}: {}", rename.old, rename.new, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn check(file: Option<&str>) -> ExitCode {
+    let referenced = file.map(|file| {
+        fs::read_to_string(file).unwrap_or_else(|err| {
+            eprintln!("unable to read {}: {}", file, err);
+            std::process::exit(2)
+        })
+    });
+    let referenced: Option<Vec<&str>> = referenced.as_deref().map(|keys| keys.lines().collect());
+    match archetype::audit::problems(&config(), referenced.as_deref()) {
+        Ok(problems) if problems.is_empty() => ExitCode::SUCCESS,
+        Ok(problems) => {
+            for problem in problems {
+                eprintln!("{}", problem);
+            }
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("unable to check snapshots: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn lock(key: &str, locked: bool) -> ExitCode {
+    let storage = config().snapshot_storage();
+    let verb = if locked { "lock" } else { "unlock" };
+    match archetype::store::lock(&*storage, key, locked) {
+        Ok(()) => {
+            println!("{}ed {}", verb, storage.location(key));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("unable to {} {}: {}", verb, key, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn compare(before: &str, after: &str) -> ExitCode {
+    let config = config();
+    let (before, after) = (config.storage_in(before), config.storage_in(after));
+    match archetype::store::changes(&*before, &*after) {
+        Ok(changes) => {
+            println!("{}", changes);
+            if changes.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            eprintln!("unable to compare snapshots: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn coverage(file: &str) -> ExitCode {
+    let referenced = fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("unable to read {}: {}", file, err);
+        std::process::exit(2)
+    });
+    let storage = config().snapshot_storage();
+    match archetype::Coverage::of(&*storage, referenced.lines()) {
+        Ok(coverage) => {
+            println!("{}", coverage);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("unable to list snapshots: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Merge `other` into `current` against `base`, writing the result to
+/// `current` as git expects.
+fn merge_driver(base: &str, current: &str, other: &str) -> ExitCode {
+    let read = |path: &str| {
+        fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("unable to read {}: {}", path, err);
+            std::process::exit(2)
+        })
+    };
+    let (base_text, current_text, other_text) = (read(base), read(current), read(other));
+    match archetype::merge::files(&base_text, &other_text, &current_text) {
+        Ok(merged) => match fs::write(current, merged) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("unable to write {}: {}", current, err);
+                ExitCode::from(2)
+            }
+        },
+        Err(err) => {
+            eprintln!("archetype: {}; falling back to a textual merge", err);
+            let status = Command::new("git")
+                .args(["merge-file", "-L", "mine", "-L", "base", "-L", "theirs"])
+                .args([current, base, other])
+                .status();
+            match status {
+                Ok(status) if status.success() => ExitCode::SUCCESS,
+                Ok(_) => ExitCode::FAILURE,
+                Err(err) => {
+                    eprintln!("unable to run git merge-file: {}", err);
+                    ExitCode::from(2)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Snapshots of what CLI-style code prints.
+//!
+//! Output is captured by pointing the process's stdout and stderr file
+//! descriptors at temporary files while the code runs, so only Unix-like
+//! platforms are supported.
+
+use crate::Sections;
+use std::ffi::c_int;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+extern "C" {
+    fn dup(fd: c_int) -> c_int;
+    fn dup2(from: c_int, to: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+const STDOUT: c_int = 1;
+const STDERR: c_int = 2;
+
+/// Held while stdout and stderr are redirected, so captures don't take
+/// each other's output.
+static REDIRECTED: Mutex<()> = Mutex::new(());
+
+/// How many redirections there have been, so each gets its own file.
+static REDIRECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Printed with `print!` before the code runs, to tell whether what it
+/// prints reaches stdout at all, rather than the test harness.
+const MARKER: &str = "\u{1}archetype-output\u{1}";
+
+/// Run `run`, capturing what it prints to stdout and stderr, and take a
+/// snapshot of that under a file with the name `key`, as a section for
+/// each stream. What `run` returns is passed back, such as an exit code
+/// to check alongside.
+///
+/// Everything written to the process's stdout and stderr while `run`
+/// runs is captured, including by other threads and child processes.
+/// Output that isn't UTF-8 is stored lossily.
+///
+/// The test harness captures what each test prints with `print!` and
+/// `eprint!` itself, before it reaches stdout, so there's no output to
+/// capture. Rather than snapshot nothing, this panics when that's the
+/// case: run the tests with `--nocapture`, or put them in a test target
+/// with `harness = false`. Doctests and binaries aren't affected.
+///
+/// ```
+/// fn greet(name: &str) -> i32 {
+///     if name.is_empty() {
+///         eprintln!("no name given");
+///         return 1;
+///     }
+///     println!("hello, {}", name);
+///     0
+/// }
+///
+/// let code = archetype::snap_output("output-example", || greet("ada"));
+/// assert_eq!(code, 0);
+/// ```
+pub fn snap_output<R>(key: &str, run: impl FnOnce() -> R) -> R {
+    let (result, stdout, stderr) =
+        capture(run).unwrap_or_else(|err| panic!("unable to capture output for {}: {}", key, err));
+    let Some(stdout) = stdout.strip_prefix(MARKER) else {
+        panic!(
+            "unable to capture output for {}, as the test harness captures what tests print; \
+             run with --nocapture, or in a test target with harness = false",
+            key
+        );
+    };
+    Sections::new(key)
+        .section("stdout", stdout)
+        .section("stderr", stderr)
+        .snap();
+    result
+}
+
+/// Run `run` with stdout and stderr redirected, returning what it
+/// returns, and what was printed to each, after the marker.
+fn capture<R>(run: impl FnOnce() -> R) -> io::Result<(R, String, String)> {
+    let _lock = REDIRECTED.lock().unwrap_or_else(|err| err.into_inner());
+    let stdout = Redirect::new(STDOUT)?;
+    let stderr = Redirect::new(STDERR)?;
+    print!("{}", MARKER);
+    let result = run();
+    Ok((result, stdout.finish()?, stderr.finish()?))
+}
+
+/// A file descriptor pointed at a temporary file, until it's finished
+/// with or dropped, including by a panic.
+struct Redirect {
+    fd: c_int,
+    saved: c_int,
+    file: File,
+}
+
+impl Redirect {
+    fn new(fd: c_int) -> io::Result<Redirect> {
+        flush();
+        let path = std::env::temp_dir().join(format!(
+            "archetype-output-{}-{}",
+            std::process::id(),
+            REDIRECTIONS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // The open file outlives its name.
+        std::fs::remove_file(&path)?;
+        // SAFETY: `dup` and `dup2` only act on the descriptor table, and
+        // `fd` and the file's descriptor are both open.
+        let saved = unsafe { dup(fd) };
+        if saved < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { dup2(file.as_raw_fd(), fd) } < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { close(saved) };
+            return Err(err);
+        }
+        Ok(Redirect { fd, saved, file })
+    }
+
+    /// Point the descriptor back where it was, once.
+    fn restore(&mut self) {
+        if self.saved < 0 {
+            return;
+        }
+        flush();
+        // SAFETY: `saved` is the descriptor `dup` returned, which nothing
+        // else closes.
+        unsafe {
+            dup2(self.saved, self.fd);
+            close(self.saved);
+        }
+        self.saved = -1;
+    }
+
+    /// Restore the descriptor and read back what was written to it.
+    fn finish(mut self) -> io::Result<String> {
+        self.restore();
+        let mut bytes = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut bytes)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Write out what's buffered for stdout and stderr, so it lands on
+/// whichever file they point to now.
+fn flush() {
+    io::stdout().flush().ok();
+    io::stderr().flush().ok();
+}
@@ -0,0 +1,91 @@
+//! A clock that tests can freeze, so output that includes the time it was
+//! produced can be snapshotted.
+
+use crate::Settings;
+use regex::Regex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a frozen timestamp is replaced with in snapshots.
+const NOW: &str = "[now]";
+
+/// The current time, or the time frozen with `Settings::freeze_time` if
+/// the settings bound to this thread have one.
+///
+/// Code that reads the time through `clock` rather than
+/// `SystemTime::now` can be frozen by a test, and any timestamp of the
+/// frozen time it writes is redacted from the snapshot as `[now]`.
+///
+/// ```
+/// use archetype::Settings;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// fn receipt() -> String {
+///     let issued = archetype::clock().duration_since(UNIX_EPOCH).unwrap();
+///     format!("issued at {}\n", issued.as_secs())
+/// }
+///
+/// Settings::new()
+///     .freeze_time(UNIX_EPOCH + Duration::from_secs(1_709_285_400))
+///     .bind(|| archetype::snap("clock-example", receipt()));
+/// ```
+pub fn clock() -> SystemTime {
+    Settings::current().frozen.unwrap_or_else(SystemTime::now)
+}
+
+/// Replace each timestamp of the time frozen in `settings` in `subject`,
+/// whether RFC 3339 or seconds, milliseconds, microseconds, or
+/// nanoseconds since the Unix epoch, with `[now]`.
+pub(crate) fn redact(settings: &Settings, subject: String) -> String {
+    let Some(pattern) = settings.frozen.and_then(pattern) else {
+        return subject;
+    };
+    pattern.replace_all(&subject, NOW).into_owned()
+}
+
+/// A pattern matching the timestamps of `at`, if it isn't before the Unix
+/// epoch.
+fn pattern(at: SystemTime) -> Option<Regex> {
+    let since = at.duration_since(UNIX_EPOCH).ok()?;
+    let secs = since.as_secs();
+    let timestamp = crate::meta::timestamp(secs);
+    let (date, time) = timestamp.trim_end_matches('Z').split_once('T')?;
+    let rfc3339 = format!(
+        r"{}[T ]{}(?:\.\d+)?(?:Z|[+-]00:?00)?",
+        regex::escape(date),
+        regex::escape(time)
+    );
+    let epoch = format!(
+        r"\b(?:{}|{}|{}|{}(?:\.\d+)?)\b",
+        since.as_nanos(),
+        since.as_micros(),
+        since.as_millis(),
+        secs
+    );
+    Regex::new(&format!("{}|{}", rfc3339, epoch)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clock, redact};
+    use crate::Settings;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn frozen_timestamps_are_redacted() {
+        let at = UNIX_EPOCH + Duration::from_millis(1_709_285_400_250);
+        let settings = Settings::new().freeze_time(at);
+        assert_eq!(settings.bind(clock), at);
+        let subject = "created 2024-03-01T09:30:00.250Z\n\
+                       updated 2024-03-01 09:30:00+00:00\n\
+                       millis 1709285400250, seconds 1709285400.25\n\
+                       later 2024-03-01T09:30:01Z, 17092854002500\n";
+        assert_eq!(
+            redact(&settings, subject.to_string()),
+            "created [now]\n\
+             updated [now]\n\
+             millis [now], seconds [now]\n\
+             later 2024-03-01T09:30:01Z, 17092854002500\n"
+        );
+        assert_eq!(redact(&Settings::new(), subject.to_string()), subject);
+    }
+}
@@ -0,0 +1,167 @@
+//! Snapshots of generated source code.
+
+use crate::Settings;
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+type Format = dyn Fn(&str) -> Result<String, String> + Send + Sync;
+
+/// Formats source code before it is snapshotted by `snap_code`. Register
+/// one per language with `Settings::formatter`.
+#[derive(Clone)]
+pub struct Formatter {
+    name: String,
+    format: Arc<Format>,
+}
+
+impl Formatter {
+    /// Format with `format`, which returns the formatted source or why it
+    /// couldn't be formatted.
+    pub fn new(
+        format: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Formatter {
+        Formatter {
+            name: String::from("closure"),
+            format: Arc::new(format),
+        }
+    }
+
+    /// Format by piping the source through `program` run with `args`,
+    /// which writes the formatted source to stdout.
+    pub fn command<S: Into<String>>(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = S>,
+    ) -> Formatter {
+        let program = program.into();
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        Formatter {
+            name: program.clone(),
+            format: Arc::new(move |source| pipe(&program, &args, source)),
+        }
+    }
+
+    /// Format Rust with `rustfmt`.
+    pub fn rustfmt() -> Formatter {
+        Formatter::command("rustfmt", ["--edition", "2021", "--emit", "stdout"])
+    }
+
+    /// Format with Prettier, using the file name `path` to choose the
+    /// parser, such as `generated.ts`.
+    pub fn prettier(path: impl Into<String>) -> Formatter {
+        Formatter::command("prettier", ["--stdin-filepath".to_string(), path.into()])
+    }
+
+    fn format(&self, source: &str) -> Result<String, String> {
+        (self.format)(source)
+    }
+}
+
+impl fmt::Debug for Formatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Formatter").field(&self.name).finish()
+    }
+}
+
+/// Run `program` with `args`, writing `input` to its stdin and returning
+/// its stdout.
+fn pipe(program: &str, args: &[String], input: &str) -> Result<String, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("unable to run {}: {}", program, err))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    // Writing from another thread keeps a formatter that writes as it
+    // reads from filling its stdout pipe and blocking on us.
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("unable to run {}: {}", program, err))?;
+    match writer.join().expect("writer shouldn't panic") {
+        // A formatter may exit without reading everything, which its exit
+        // status speaks to.
+        Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => {
+            return Err(format!("unable to write to {}: {}", program, err))
+        }
+        _ => {}
+    }
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|err| format!("{} wrote invalid UTF-8: {}", program, err))
+}
+
+/// Take a snapshot of source code in the language `lang` under a file
+/// with the name `key`, formatted with the formatter registered for
+/// `lang` with `Settings::formatter`, if any.
+///
+/// Formatting generated code before it is stored means a golden doesn't
+/// churn when the generator changes only how its output is laid out.
+///
+/// ```
+/// use archetype::{Formatter, Settings};
+///
+/// Settings::new()
+///     .formatter("sql", Formatter::new(|source| Ok(source.to_uppercase())))
+///     .bind(|| archetype::snap_code("code-example", "sql", "select id from users\n"));
+/// ```
+///
+/// Panics if the formatter fails, rather than storing unformatted code.
+pub fn snap_code(key: &str, lang: &str, source: &str) {
+    let settings = Settings::current();
+    let formatter = settings
+        .formatters
+        .iter()
+        .rev()
+        .find(|(name, _)| name == lang)
+        .map(|(_, formatter)| formatter);
+    let subject = match formatter {
+        Some(formatter) => formatter
+            .format(source)
+            .unwrap_or_else(|err| panic!("unable to format {} for {}: {}", lang, key, err)),
+        None => source.to_string(),
+    };
+    crate::snap(key, subject);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snap_code, Formatter};
+    use crate::Settings;
+
+    #[test]
+    fn commands_format_through_stdin() {
+        let formatter = Formatter::command("tr", ["a-z", "A-Z"]);
+        assert_eq!(formatter.format("select id\n").unwrap(), "SELECT ID\n");
+        let missing = Formatter::command("archetype-no-such-formatter", Vec::<String>::new());
+        assert!(missing
+            .format("")
+            .unwrap_err()
+            .starts_with("unable to run archetype-no-such-formatter"));
+        assert!(Formatter::command("false", Vec::<String>::new())
+            .format("")
+            .is_err());
+    }
+
+    #[test]
+    fn snapshot_formatted_code() {
+        let collapse = Formatter::new(|source| {
+            let words: Vec<&str> = source.split_whitespace().collect();
+            Ok(format!("{}\n", words.join(" ")))
+        });
+        Settings::new()
+            .formatter("sql", collapse)
+            .bind(|| snap_code("code_query", "sql", "select id,\n       name\n  from users"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Comparison of a new snapshot against a stored one.
+//!
+//! Nothing in here touches the filesystem, so the same comparison runs
+//! regardless of where snapshots are stored.
+
+use crate::{diff, json, template, Comparison, Settings};
+use similar::{ChangeTag, DiffTag, TextDiff};
+use std::cell::OnceCell;
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::time::Instant;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use web_time::Instant;
+
+/// How a new snapshot differs from the stored one.
+///
+/// The line diff between them is only built when something asks for it,
+/// as a mismatch that's quietly updated never needs one.
+pub(crate) struct Mismatch<'a> {
+    settings: &'a Settings,
+    stored: &'a str,
+    subject: &'a str,
+    started: Instant,
+    diff: OnceCell<(TextDiff<'a, 'a, 'a, str>, bool)>,
+}
+
+impl<'a> Mismatch<'a> {
+    /// The line diff, built the first time it's needed, along with
+    /// whether building it passed the deadline in the settings.
+    fn built(&self) -> &(TextDiff<'a, 'a, 'a, str>, bool) {
+        self.diff.get_or_init(|| {
+            let diff = diff::lines(self.settings, self.stored, self.subject);
+            let timed_out = self
+                .settings
+                .deadline
+                .is_some_and(|deadline| self.started.elapsed() >= deadline);
+            (diff, timed_out)
+        })
+    }
+
+    /// The line diff, unless building it timed out.
+    fn diff(&self) -> Option<&TextDiff<'a, 'a, 'a, str>> {
+        let (diff, timed_out) = self.built();
+        (!timed_out).then_some(diff)
+    }
+
+    /// The similarity ratio of the diff, unless it timed out.
+    pub(crate) fn ratio(&self) -> Option<f32> {
+        self.diff().map(TextDiff::ratio)
+    }
+
+    /// The first few differing lines, located in the stored snapshot. A
+    /// timed out diff has none.
+    pub(crate) fn annotations(&self) -> Vec<diff::Annotation> {
+        self.diff().map(diff::annotations).unwrap_or_default()
+    }
+
+    /// A one line summary of the diff, such as `12 insertions, 3
+    /// deletions, similarity 0.94, first difference at line 87`, for
+    /// failure messages, which are all some CI systems show. A timed out
+    /// diff has none.
+    pub(crate) fn stats(&self) -> Option<String> {
+        let diff = self.diff()?;
+        let (mut insertions, mut deletions) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => insertions += 1,
+                ChangeTag::Delete => deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        let first = diff
+            .ops()
+            .iter()
+            .find(|op| op.tag() != DiffTag::Equal)
+            .map_or(1, |op| op.old_range().start + 1);
+        Some(format!(
+            "{} insertion{}, {} deletion{}, similarity {:.2}, first difference at line {}",
+            insertions,
+            if insertions == 1 { "" } else { "s" },
+            deletions,
+            if deletions == 1 { "" } else { "s" },
+            diff.ratio(),
+            first
+        ))
+    }
+
+    /// Render the mismatch, framed by `key`, in the style from the
+    /// settings.
+    pub(crate) fn render(&self, key: &str) -> String {
+        match self.diff() {
+            Some(diff) => diff::render(self.settings, key, diff),
+            None => diff::timed_out(self.settings.frame, key, self.stored, self.subject),
+        }
+    }
+}
+
+/// Compare `subject` against `stored` according to `settings`, returning
+/// how they differ if they don't match.
+pub(crate) fn compare<'a>(
+    settings: &'a Settings,
+    stored: &'a str,
+    subject: &'a str,
+) -> Option<Mismatch<'a>> {
+    if stored == subject {
+        return None;
+    }
+    // Snapshots compared byte for byte only match if they're identical,
+    // so neither equivalent JSON nor a template stands in for them.
+    let exact = settings.comparison == Comparison::Bytes;
+    if !exact
+        && json::equivalent(
+            stored,
+            subject,
+            &settings.ignore_paths,
+            &settings.unordered_paths,
+        )
+    {
+        return None;
+    }
+    if !exact && template::matches(stored, subject) {
+        return None;
+    }
+    let mismatch = Mismatch {
+        settings,
+        stored,
+        subject,
+        started: Instant::now(),
+        diff: OnceCell::new(),
+    };
+    let accepted = settings
+        .comparison
+        .accepts(stored, subject, || mismatch.built().0.ratio());
+    (!accepted).then_some(mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare;
+    use crate::{Comparison, Settings};
+    use std::time::Duration;
+
+    #[test]
+    fn compare_snapshots() {
+        let settings = Settings::new();
+        assert!(compare(&settings, "a\nb\n", "a\nb\n").is_none());
+        let mismatch = compare(&settings, "a\nb\n", "a\nc\n").unwrap();
+        // Nothing has needed the diff yet.
+        assert!(mismatch.diff.get().is_none());
+        assert_eq!(mismatch.ratio(), Some(0.5));
+
+        let settings = Settings::new().comparison(Comparison::Similarity(0.5));
+        assert!(compare(&settings, "a\nb\n", "a\nc\n").is_none());
+        assert!(compare(&settings, "a\nb\n", "c\nd\n").is_some());
+    }
+
+    #[test]
+    fn mismatch_stats() {
+        let settings = Settings::new();
+        let stored = "a\nb\nc\nd\n";
+        let mismatch = compare(&settings, stored, "a\nb\nC\nD\ne\n").unwrap();
+        assert_eq!(
+            mismatch.stats().unwrap(),
+            "3 insertions, 2 deletions, similarity 0.44, first difference at line 3"
+        );
+        let mismatch = compare(&settings, stored, "a\nb\nc\n").unwrap();
+        assert_eq!(
+            mismatch.stats().unwrap(),
+            "0 insertions, 1 deletion, similarity 0.86, first difference at line 4"
+        );
+    }
+
+    #[test]
+    fn compare_against_template() {
+        let settings = Settings::new();
+        assert!(compare(&settings, "took {{number}}ms\n", "took 12ms\n").is_none());
+        assert!(compare(&settings, "took {{number}}ms\n", "took ms\n").is_some());
+    }
+
+    #[test]
+    fn bytes_are_compared_exactly() {
+        let settings = Settings::new().comparison(Comparison::Bytes);
+        assert!(compare(&settings, "took {{number}}ms\n", "took 12ms\n").is_some());
+        assert!(compare(&settings, "{\"a\": 1}", "{ \"a\": 1 }").is_some());
+        assert!(compare(&settings, "a\r\n", "a\r\n").is_none());
+    }
+
+    #[test]
+    fn compare_past_deadline() {
+        let settings = Settings::new().deadline(Duration::ZERO);
+        assert!(compare(&settings, "a\nb\n", "a\nb\n").is_none());
+        assert_eq!(
+            compare(&settings, "a\nb\n", "a\nc\n").unwrap().ratio(),
+            None
+        );
+    }
+}
@@ -0,0 +1,986 @@
+//! Process-wide configuration.
+//!
+//! Where `Settings` are scoped to a thread and tweak individual
+//! assertions, a `GlobalConfig` sets policy for the whole test binary:
+//! where snapshots live, whether we're running in CI, whether snapshots
+//! may be updated, and whether output is colored. It is set at most once,
+//! before the first assertion, and is fixed for the life of the process.
+//!
+//! Teams can share configuration without code changes by placing an
+//! `archetype.toml` next to the crate's `Cargo.toml`, or with a
+//! `[package.metadata.archetype]` table in `Cargo.toml` itself:
+//!
+//! ```toml
+//! root = "tests/goldens"
+//! update = "never"
+//! extension = "golden"
+//! allow_missing = ["legacy/*"]
+//! allow_secrets = ["fixtures/dummy-credentials"]
+//! read_only = false
+//! layout = "content-addressed"
+//! dry_run = false
+//! provenance = true
+//! filters = [
+//!     { pattern = "\\d{4}-\\d{2}-\\d{2}", replacement = "[date]" },
+//! ]
+//! scrub = ["email", { detector = "phone", replacement = "[tel]" }]
+//! routes = [
+//!     { prefix = "api/", root = "tests/goldens/api" },
+//! ]
+//! ```
+//!
+//! If both exist, `archetype.toml` is used.
+
+use crate::pii::Pii;
+use crate::store::Storage;
+#[cfg(feature = "fs")]
+use crate::store::{ContentAddressedStorage, FsStorage, RoutedStorage};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+static GLOBAL: OnceLock<GlobalConfig> = OnceLock::new();
+
+/// When snapshots may be written to disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Update {
+    /// Write snapshots that are missing, but fail on any mismatch.
+    #[default]
+    New,
+    /// Write snapshots that are missing and overwrite any that mismatch.
+    Always,
+    /// Never write snapshots. Missing snapshots fail.
+    Never,
+}
+
+/// Whether diffs are colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    /// Always color.
+    Always,
+    /// Never color.
+    Never,
+}
+
+impl Color {
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            Color::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+/// The colors diffs are drawn in, when they're colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Red for deletions, green for insertions, and cyan for moves.
+    #[default]
+    Default,
+    /// Orange for deletions, blue for insertions, and magenta for moves,
+    /// which stay apart for the common kinds of color blindness, unlike
+    /// red and green.
+    Colorblind,
+    /// Darker shades of the default colors, which stay legible on a
+    /// light background.
+    Light,
+}
+
+impl Palette {
+    /// The escape codes for deleted, inserted, and moved lines.
+    pub(crate) fn styles(self) -> [&'static str; 3] {
+        match self {
+            Palette::Default => ["\x1b[31m", "\x1b[32m", "\x1b[36m"],
+            Palette::Colorblind => ["\x1b[38;5;208m", "\x1b[38;5;33m", "\x1b[35m"],
+            Palette::Light => ["\x1b[38;5;124m", "\x1b[38;5;28m", "\x1b[38;5;25m"],
+        }
+    }
+}
+
+/// A regular expression whose matches are replaced before a snapshot is
+/// compared or stored.
+#[derive(Clone, Debug)]
+pub(crate) struct Filter {
+    pattern: Regex,
+    replacement: String,
+}
+
+/// How a relative snapshot root is resolved to a directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// Relative to the crate's manifest directory, `CARGO_MANIFEST_DIR`.
+    #[default]
+    Manifest,
+    /// Relative to the Bazel runfiles of the test's workspace, i.e.
+    /// `$RUNFILES_DIR/$TEST_WORKSPACE` (or `$TEST_SRCDIR/$TEST_WORKSPACE`).
+    /// The root should then be the snapshot directory's path within the
+    /// workspace, and the snapshots must be declared as `data` of the
+    /// test so that they are staged into the sandbox.
+    Runfiles,
+}
+
+/// How snapshots are laid out in the snapshot directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// One file per snapshot, holding the snapshot. See `FsStorage`.
+    #[default]
+    Files,
+    /// One small pointer file per snapshot, naming an object that holds
+    /// the snapshot by its content. Identical snapshots are only stored
+    /// once. See `ContentAddressedStorage`.
+    ContentAddressed,
+}
+
+/// Policy shared by every snapshot assertion in the process.
+///
+/// ```
+/// use archetype::{GlobalConfig, Update};
+///
+/// archetype::configure(GlobalConfig::new().root("snapshots").update(Update::New)).ok();
+/// ```
+#[derive(Clone, Debug)]
+pub struct GlobalConfig {
+    pub(crate) root: PathBuf,
+    pub(crate) ci: bool,
+    pub(crate) update: Update,
+    pub(crate) color: Color,
+    pub(crate) palette: Palette,
+    pub(crate) extension: String,
+    pub(crate) filters: Vec<Filter>,
+    pub(crate) allow_missing: Vec<String>,
+    pub(crate) allow_secrets: Vec<String>,
+    pub(crate) storage: Option<Arc<dyn Storage>>,
+    pub(crate) resolution: Resolution,
+    pub(crate) read_only: bool,
+    pub(crate) layout: Layout,
+    pub(crate) manifest_dir: Option<PathBuf>,
+    pub(crate) dry_run: bool,
+    pub(crate) github_actions: bool,
+    pub(crate) coverage: Option<PathBuf>,
+    pub(crate) provenance: bool,
+    pub(crate) force: bool,
+    pub(crate) routes: Vec<(String, PathBuf)>,
+    pub(crate) patches: bool,
+    pub(crate) diff_tool: Option<String>,
+    pub(crate) out: Option<PathBuf>,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) socket: Option<String>,
+}
+
+impl Default for GlobalConfig {
+    fn default() -> GlobalConfig {
+        GlobalConfig {
+            root: PathBuf::from("snapshots"),
+            ci: option_env!("CI").map(|v| v == "true").unwrap_or(false),
+            update: Update::default(),
+            color: Color::default(),
+            palette: Palette::default(),
+            extension: String::from("snap"),
+            filters: Vec::new(),
+            allow_missing: Vec::new(),
+            allow_secrets: Vec::new(),
+            storage: None,
+            resolution: Resolution::default(),
+            read_only: false,
+            layout: Layout::default(),
+            manifest_dir: None,
+            dry_run: false,
+            github_actions: crate::github::detected(),
+            coverage: None,
+            provenance: false,
+            force: false,
+            routes: Vec::new(),
+            patches: false,
+            diff_tool: None,
+            out: None,
+            lossy_utf8: false,
+            socket: None,
+        }
+    }
+}
+
+impl GlobalConfig {
+    /// Create a config with every option at its default.
+    pub fn new() -> GlobalConfig {
+        GlobalConfig::default()
+    }
+
+    /// Create a config from the defaults overridden by `archetype.toml`
+    /// or `[package.metadata.archetype]`, if either is present, and then
+    /// by the `ARCHETYPE_ROOT` environment variable, which can point the
+    /// snapshot root at another crate's goldens, `ARCHETYPE_UPDATE`, which
+    /// is `1` or `always` to overwrite mismatched snapshots, `new`, or
+    /// `never`, `ARCHETYPE_PALETTE`, which is `default`, `colorblind`, or
+    /// `light`, `ARCHETYPE_FORCE=1`, `ARCHETYPE_DRY_RUN=1`,
+    /// `ARCHETYPE_PATCHES=1`, `ARCHETYPE_DIFF_TOOL`, `ARCHETYPE_OUT`, a
+    /// directory to write snapshots to when the root isn't writable,
+    /// `ARCHETYPE_SOCKET`, a socket to stream mismatches to, and
+    /// `ARCHETYPE_COVERAGE`, a file to record coverage in. This is what is
+    /// used when `configure` is never called.
+    ///
+    /// Panics if the configuration file, `ARCHETYPE_UPDATE`, or
+    /// `ARCHETYPE_PALETTE` is malformed.
+    pub fn load() -> GlobalConfig {
+        if cfg!(not(feature = "fs")) {
+            return GlobalConfig::default();
+        }
+        // Hermetic builds may not expose the manifest directory at runtime,
+        // in which case there's no configuration file to be found either.
+        match std::env::var_os("CARGO_MANIFEST_DIR") {
+            Some(dir) => GlobalConfig::load_from(dir),
+            None => GlobalConfig::default(),
+        }
+    }
+
+    /// Like `load`, but for the crate whose manifest is in `manifest_dir`
+    /// rather than the one under test. This is for tooling that runs
+    /// outside of `cargo test`.
+    pub fn load_from(manifest_dir: impl Into<PathBuf>) -> GlobalConfig {
+        let manifest_dir = manifest_dir.into();
+        let mut config = match File::find(&manifest_dir) {
+            Some(file) => file.apply(GlobalConfig::default()),
+            None => GlobalConfig::default(),
+        };
+        config.manifest_dir = Some(manifest_dir);
+        if let Ok(update) = std::env::var("ARCHETYPE_UPDATE") {
+            config = config.update(match update.as_str() {
+                "1" | "always" => Update::Always,
+                "new" => Update::New,
+                "never" => Update::Never,
+                other => panic!(
+                    "ARCHETYPE_UPDATE should be 1, always, new, or never, not {:?}",
+                    other
+                ),
+            });
+        }
+        if let Ok(palette) = std::env::var("ARCHETYPE_PALETTE") {
+            config = config.palette(match palette.as_str() {
+                "default" => Palette::Default,
+                "colorblind" => Palette::Colorblind,
+                "light" => Palette::Light,
+                other => panic!(
+                    "ARCHETYPE_PALETTE should be default, colorblind, or light, not {:?}",
+                    other
+                ),
+            });
+        }
+        if let Ok(force) = std::env::var("ARCHETYPE_FORCE") {
+            config = config.force(matches!(force.as_str(), "1" | "true"));
+        }
+        if let Ok(dry_run) = std::env::var("ARCHETYPE_DRY_RUN") {
+            config = config.dry_run(matches!(dry_run.as_str(), "1" | "true"));
+        }
+        if let Ok(patches) = std::env::var("ARCHETYPE_PATCHES") {
+            config = config.patches(matches!(patches.as_str(), "1" | "true"));
+        }
+        if let Ok(tool) = std::env::var("ARCHETYPE_DIFF_TOOL") {
+            config = config.diff_tool(tool);
+        }
+        if let Some(out) = std::env::var_os("ARCHETYPE_OUT") {
+            config = config.out(out);
+        }
+        if let Ok(socket) = std::env::var("ARCHETYPE_SOCKET") {
+            config = config.socket(socket);
+        }
+        if let Some(coverage) = std::env::var_os("ARCHETYPE_COVERAGE") {
+            config = config.coverage(coverage);
+        }
+        match std::env::var_os("ARCHETYPE_ROOT") {
+            Some(root) => config.root(root),
+            None => config,
+        }
+    }
+
+    /// Store snapshots under `root`. Relative paths are resolved according
+    /// to the `resolution`, by default against the crate's manifest
+    /// directory. Defaults to `snapshots`.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> GlobalConfig {
+        self.root = root.into();
+        self
+    }
+
+    /// Whether we're running in CI, where missing snapshots fail rather
+    /// than being written. Defaults to whether `CI=true` at build time.
+    pub fn ci(mut self, ci: bool) -> GlobalConfig {
+        self.ci = ci;
+        self
+    }
+
+    /// When snapshots may be written to disk.
+    pub fn update(mut self, update: Update) -> GlobalConfig {
+        self.update = update;
+        self
+    }
+
+    /// Whether diffs are colored.
+    pub fn color(mut self, color: Color) -> GlobalConfig {
+        self.color = color;
+        self
+    }
+
+    /// The colors diffs are drawn in, when they're colored. Defaults to
+    /// `Palette::Default`.
+    pub fn palette(mut self, palette: Palette) -> GlobalConfig {
+        self.palette = palette;
+        self
+    }
+
+    /// How a relative `root` is resolved. Defaults to
+    /// `Resolution::Manifest`.
+    pub fn resolution(mut self, resolution: Resolution) -> GlobalConfig {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Never create directories or write snapshots, whatever the update
+    /// policy. Missing and mismatched snapshots always fail. This suits
+    /// hermetic build systems, where the source tree is read-only. To rule
+    /// out writes when building instead, enable the `verify-only` feature,
+    /// under which any attempt to write a snapshot is an error.
+    pub fn read_only(mut self, read_only: bool) -> GlobalConfig {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Report which snapshots would be created or updated without writing
+    /// anything, to preview the effect of a run before letting it write.
+    /// Assertions pass or fail exactly as they would otherwise.
+    pub fn dry_run(mut self, dry_run: bool) -> GlobalConfig {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Emit GitHub Actions `::error` workflow commands for missing and
+    /// mismatched snapshots, so failures are annotated inline on pull
+    /// requests. Defaults to whether `GITHUB_ACTIONS=true`.
+    pub fn github_actions(mut self, github_actions: bool) -> GlobalConfig {
+        self.github_actions = github_actions;
+        self
+    }
+
+    /// Append the key of every snapshot asserted against to the file at
+    /// `path` when the test binary exits, to find snapshots that no test
+    /// references with `Coverage` or `cargo archetype coverage`. Relative
+    /// paths are relative to the working directory, which under `cargo
+    /// test` is the crate's manifest directory.
+    pub fn coverage(mut self, path: impl Into<PathBuf>) -> GlobalConfig {
+        self.coverage = Some(path.into());
+        self
+    }
+
+    /// Record who wrote each snapshot and when in a header above it, along
+    /// with why from `ARCHETYPE_REASON` if it is set, so the history of a
+    /// snapshot is kept alongside it and not only in version control. The
+    /// header is never compared.
+    pub fn provenance(mut self, provenance: bool) -> GlobalConfig {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Update locked snapshots along with every other, rather than failing
+    /// when they mismatch. See `store::lock`.
+    pub fn force(mut self, force: bool) -> GlobalConfig {
+        self.force = force;
+        self
+    }
+
+    /// Write a unified diff next to each mismatched snapshot, such as
+    /// `search.snap.patch`, that `git apply` can apply or any diff tool
+    /// can show. The patch is removed once the snapshot matches again.
+    /// Only snapshots laid out as `Layout::Files` get patches.
+    pub fn patches(mut self, patches: bool) -> GlobalConfig {
+        self.patches = patches;
+        self
+    }
+
+    /// Show mismatches with an external diff tool rather than the built-in
+    /// rendering when stdout is a terminal outside of CI, such as
+    /// `difft {old} {new}` or `delta`. `{old}` and `{new}` are replaced
+    /// with temporary copies of each side, which are otherwise passed
+    /// last. The command is split on whitespace and run without a shell.
+    pub fn diff_tool(mut self, command: impl Into<String>) -> GlobalConfig {
+        self.diff_tool = Some(command.into());
+        self
+    }
+
+    /// Write snapshots that can't be written under the root, such as in a
+    /// sandbox where the source tree is read-only, to the same place under
+    /// `dir` instead, printing where each one went, rather than failing.
+    /// Relative paths are relative to the working directory.
+    pub fn out(mut self, dir: impl Into<PathBuf>) -> GlobalConfig {
+        self.out = Some(dir.into());
+        self
+    }
+
+    /// Read stored snapshots that aren't valid UTF-8, such as one saved in
+    /// another encoding by an editor, with each invalid sequence replaced
+    /// by U+FFFD, warning that it was. The snapshot then mismatches, and
+    /// can be reviewed and rewritten as UTF-8. Otherwise reading it fails
+    /// with the offset of the first invalid byte. Defaults to `false`.
+    pub fn lossy_utf8(mut self, lossy: bool) -> GlobalConfig {
+        self.lossy_utf8 = lossy;
+        self
+    }
+
+    /// Stream each mismatch, with its key, path, and diff, as a line of
+    /// JSON to the socket at `address` as it happens, so a review tool or
+    /// editor plugin listening there can present failures live. The
+    /// address is `tcp://` followed by a host and port, such as
+    /// `tcp://127.0.0.1:7878`, or else the path of a Unix domain socket,
+    /// or of a named pipe on Windows, such as `\\.\pipe\archetype`.
+    pub fn socket(mut self, address: impl Into<String>) -> GlobalConfig {
+        self.socket = Some(address.into());
+        self
+    }
+
+    /// Whether mismatches are shown with the diff tool, if one is set.
+    pub(crate) fn interactive(&self) -> bool {
+        !self.ci && std::io::stdout().is_terminal()
+    }
+
+    /// How snapshots are laid out under `root`. Defaults to
+    /// `Layout::Files`.
+    pub fn layout(mut self, layout: Layout) -> GlobalConfig {
+        self.layout = layout;
+        self
+    }
+
+    /// Store snapshots in files ending in `.{extension}`. Defaults to
+    /// `snap`.
+    pub fn extension(mut self, extension: impl Into<String>) -> GlobalConfig {
+        self.extension = extension.into();
+        self
+    }
+
+    /// Replace every match of the regular expression `pattern` with
+    /// `replacement` before snapshots are compared or stored. The
+    /// replacement may refer to capture groups, e.g. `$1`.
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn filter(mut self, pattern: &str, replacement: impl Into<String>) -> GlobalConfig {
+        let pattern = Regex::new(pattern)
+            .unwrap_or_else(|err| panic!("invalid filter pattern {:?}: {}", pattern, err));
+        self.filters.push(Filter {
+            pattern,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// Replace personally identifiable information of the kind `pii` with
+    /// its default replacement, such as `[email]`, before snapshots are
+    /// compared or stored, so production-shaped fixtures can be snapshotted
+    /// without leaking sensitive data into the repository. Like filters,
+    /// detectors apply in the order they were added; see `Pii::ALL`.
+    pub fn scrub(self, pii: Pii) -> GlobalConfig {
+        self.scrub_with(pii, pii.replacement())
+    }
+
+    /// Like `scrub`, but replace what is found with `replacement`.
+    pub fn scrub_with(self, pii: Pii, replacement: impl Into<String>) -> GlobalConfig {
+        self.filter(pii.pattern(), replacement)
+    }
+
+    /// Allow snapshots whose key matches the glob `pattern` to be missing
+    /// in CI or under `Update::Never`, for snapshots that are known not to
+    /// be recorded yet. Any other missing snapshot still fails. `*`
+    /// matches any run of characters and `?` matches any one character.
+    pub fn allow_missing(mut self, pattern: impl Into<String>) -> GlobalConfig {
+        self.allow_missing.push(pattern.into());
+        self
+    }
+
+    /// Whether the snapshot under `key` is allowed to be missing.
+    pub(crate) fn allows_missing(&self, key: &str) -> bool {
+        self.allow_missing
+            .iter()
+            .any(|pattern| crate::glob::matches(pattern, key))
+    }
+
+    /// Allow snapshots whose key matches the glob `pattern` to be written
+    /// even though they look like they contain a secret, such as an AWS
+    /// key or a private key. Snapshots that look like they contain secrets
+    /// otherwise fail rather than being written, as committing a real
+    /// credential is far worse than a failing test. Use this for fixtures
+    /// with deliberately fake credentials.
+    pub fn allow_secrets(mut self, pattern: impl Into<String>) -> GlobalConfig {
+        self.allow_secrets.push(pattern.into());
+        self
+    }
+
+    /// Whether the snapshot under `key` may be written with secrets.
+    pub(crate) fn allows_secrets(&self, key: &str) -> bool {
+        self.allow_secrets
+            .iter()
+            .any(|pattern| crate::glob::matches(pattern, key))
+    }
+
+    /// Keep snapshots under keys starting with `prefix` in files under
+    /// `root` rather than the main root, with the prefix removed, so
+    /// golden data can live alongside each subsystem. `root` is resolved
+    /// just as the main root is, and the longest matching prefix wins:
+    ///
+    /// ```
+    /// use archetype::GlobalConfig;
+    ///
+    /// // api/users is stored in tests/goldens/api/users.snap.
+    /// let config = GlobalConfig::new().route("api/", "tests/goldens/api");
+    /// ```
+    ///
+    /// Routes don't apply to snapshots kept in a configured `storage`; use
+    /// `RoutedStorage` there instead.
+    pub fn route(mut self, prefix: impl Into<String>, root: impl Into<PathBuf>) -> GlobalConfig {
+        self.routes.push((prefix.into(), root.into()));
+        self
+    }
+
+    /// Keep snapshots in `storage` rather than in files under `root`.
+    pub fn storage(mut self, storage: impl Storage + 'static) -> GlobalConfig {
+        self.storage = Some(Arc::new(storage));
+        self
+    }
+
+    /// Where snapshots are kept under this config.
+    pub fn snapshot_storage(&self) -> Arc<dyn Storage> {
+        match &self.storage {
+            Some(storage) => storage.clone(),
+            #[cfg(feature = "fs")]
+            None if self.routes.is_empty() => self.files(self.snapshot_dir()),
+            #[cfg(feature = "fs")]
+            None => {
+                let routed = self.routes.iter().fold(
+                    RoutedStorage::from_arc(self.files(self.snapshot_dir())),
+                    |routed, (prefix, root)| {
+                        let dir = self.resolve(root, |name| std::env::var(name).ok());
+                        routed.route_arc(prefix.clone(), self.files(dir))
+                    },
+                );
+                Arc::new(routed)
+            }
+            #[cfg(not(feature = "fs"))]
+            None => panic!("no snapshot storage configured; enable the fs feature or use GlobalConfig::storage"),
+        }
+    }
+
+    /// Where snapshots would be kept under this config if they were
+    /// stored in `dir` rather than under the root and any routes, for
+    /// tooling that inspects other copies of the snapshot directory.
+    #[cfg(feature = "fs")]
+    pub fn storage_in(&self, dir: impl Into<PathBuf>) -> Arc<dyn Storage> {
+        self.files(dir.into())
+    }
+
+    /// Snapshots in files under `dir`, laid out as configured.
+    #[cfg(feature = "fs")]
+    fn files(&self, dir: PathBuf) -> Arc<dyn Storage> {
+        match self.layout {
+            Layout::Files => Arc::new(FsStorage::new(dir, &self.extension).lossy(self.lossy_utf8)),
+            Layout::ContentAddressed => {
+                Arc::new(ContentAddressedStorage::new(dir, &self.extension).lossy(self.lossy_utf8))
+            }
+        }
+    }
+
+    /// Whether snapshots may be written to `storage` at all.
+    pub(crate) fn writable(&self, storage: &dyn Storage) -> bool {
+        !self.read_only && self.update != Update::Never && storage.writable()
+    }
+
+    /// Apply every filter to `subject` in the order they were added.
+    pub(crate) fn apply_filters(&self, subject: String) -> String {
+        self.filters.iter().fold(subject, |subject, filter| {
+            filter
+                .pattern
+                .replace_all(&subject, filter.replacement.as_str())
+                .into_owned()
+        })
+    }
+
+    /// The directory snapshots are stored in.
+    #[cfg(feature = "fs")]
+    pub(crate) fn snapshot_dir(&self) -> PathBuf {
+        self.resolve(&self.root, |name| std::env::var(name).ok())
+    }
+
+    /// Every directory snapshots are stored in: the main one, then each
+    /// route's.
+    #[cfg(feature = "fs")]
+    pub(crate) fn snapshot_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.snapshot_dir()];
+        dirs.extend(
+            self.routes
+                .iter()
+                .map(|(_, root)| self.resolve(root, |name| std::env::var(name).ok())),
+        );
+        dirs
+    }
+
+    /// Resolve `root` with environment variables looked up by `var`.
+    ///
+    /// Panics if the variables it's resolved against aren't set.
+    #[cfg(feature = "fs")]
+    fn resolve(&self, root: &Path, var: impl Fn(&str) -> Option<String>) -> PathBuf {
+        self.try_resolve(root, var)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Like `resolve`, but fails with what's missing if the variables
+    /// it's resolved against aren't set.
+    #[cfg(feature = "fs")]
+    pub(crate) fn try_resolve(
+        &self,
+        root: &Path,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<PathBuf, &'static str> {
+        let mut dir = self.base(var)?;
+        dir.push(root);
+        Ok(dir)
+    }
+
+    /// The directory a relative root is resolved against: the crate's
+    /// manifest directory, or the runfiles, by the resolution.
+    #[cfg(feature = "fs")]
+    pub(crate) fn base(
+        &self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<PathBuf, &'static str> {
+        let dir = match self.resolution {
+            Resolution::Manifest => match &self.manifest_dir {
+                Some(dir) => dir.clone(),
+                None => PathBuf::from(var("CARGO_MANIFEST_DIR").ok_or(
+                    "CARGO_MANIFEST_DIR should be set; outside of cargo use Resolution::Runfiles or an absolute root",
+                )?),
+            },
+            Resolution::Runfiles => {
+                let mut dir = PathBuf::from(
+                    var("RUNFILES_DIR")
+                        .or_else(|| var("TEST_SRCDIR"))
+                        .ok_or("RUNFILES_DIR or TEST_SRCDIR should be set to resolve runfiles")?,
+                );
+                if let Some(workspace) = var("TEST_WORKSPACE") {
+                    dir.push(workspace);
+                }
+                dir
+            }
+        };
+        Ok(dir)
+    }
+}
+
+/// The shape of `archetype.toml` and `[package.metadata.archetype]`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    root: Option<PathBuf>,
+    ci: Option<bool>,
+    update: Option<Update>,
+    color: Option<Color>,
+    palette: Option<Palette>,
+    extension: Option<String>,
+    #[serde(default)]
+    filters: Vec<FileFilter>,
+    #[serde(default)]
+    allow_missing: Vec<String>,
+    #[serde(default)]
+    allow_secrets: Vec<String>,
+    #[serde(default)]
+    scrub: Vec<FileScrub>,
+    resolution: Option<Resolution>,
+    read_only: Option<bool>,
+    layout: Option<Layout>,
+    dry_run: Option<bool>,
+    github_actions: Option<bool>,
+    coverage: Option<PathBuf>,
+    provenance: Option<bool>,
+    patches: Option<bool>,
+    diff_tool: Option<String>,
+    out: Option<PathBuf>,
+    lossy_utf8: Option<bool>,
+    socket: Option<String>,
+    #[serde(default)]
+    routes: Vec<FileRoute>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FileFilter {
+    pub(crate) pattern: String,
+    pub(crate) replacement: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRoute {
+    prefix: String,
+    root: PathBuf,
+}
+
+/// A detector to scrub with, either by name alone or with a replacement.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FileScrub {
+    Default(Pii),
+    With(FileScrubWith),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileScrubWith {
+    detector: Pii,
+    replacement: String,
+}
+
+impl File {
+    /// Find the configuration for the crate whose manifest is in `dir`.
+    fn find(dir: &Path) -> Option<File> {
+        let path = dir.join("archetype.toml");
+        if path.exists() {
+            let contents = read(&path);
+            return Some(
+                toml::from_str(&contents)
+                    .unwrap_or_else(|err| panic!("invalid {}: {}", path.display(), err)),
+            );
+        }
+        let path = dir.join("Cargo.toml");
+        if !path.exists() {
+            return None;
+        }
+        let manifest: toml::Table = toml::from_str(&read(&path))
+            .unwrap_or_else(|err| panic!("invalid {}: {}", path.display(), err));
+        let table = manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("archetype"))?;
+        Some(table.clone().try_into().unwrap_or_else(|err| {
+            panic!(
+                "invalid [package.metadata.archetype] in {}: {}",
+                path.display(),
+                err
+            )
+        }))
+    }
+
+    fn apply(self, mut config: GlobalConfig) -> GlobalConfig {
+        if let Some(root) = self.root {
+            config = config.root(root);
+        }
+        if let Some(ci) = self.ci {
+            config = config.ci(ci);
+        }
+        if let Some(update) = self.update {
+            config = config.update(update);
+        }
+        if let Some(color) = self.color {
+            config = config.color(color);
+        }
+        if let Some(palette) = self.palette {
+            config = config.palette(palette);
+        }
+        if let Some(extension) = self.extension {
+            config = config.extension(extension);
+        }
+        for filter in self.filters {
+            config = config.filter(&filter.pattern, filter.replacement);
+        }
+        for scrub in self.scrub {
+            config = match scrub {
+                FileScrub::Default(pii) => config.scrub(pii),
+                FileScrub::With(with) => config.scrub_with(with.detector, with.replacement),
+            };
+        }
+        if let Some(resolution) = self.resolution {
+            config = config.resolution(resolution);
+        }
+        if let Some(read_only) = self.read_only {
+            config = config.read_only(read_only);
+        }
+        if let Some(layout) = self.layout {
+            config = config.layout(layout);
+        }
+        if let Some(dry_run) = self.dry_run {
+            config = config.dry_run(dry_run);
+        }
+        if let Some(github_actions) = self.github_actions {
+            config = config.github_actions(github_actions);
+        }
+        if let Some(coverage) = self.coverage {
+            config = config.coverage(coverage);
+        }
+        if let Some(provenance) = self.provenance {
+            config = config.provenance(provenance);
+        }
+        if let Some(patches) = self.patches {
+            config = config.patches(patches);
+        }
+        if let Some(tool) = self.diff_tool {
+            config = config.diff_tool(tool);
+        }
+        if let Some(out) = self.out {
+            config = config.out(out);
+        }
+        if let Some(lossy) = self.lossy_utf8 {
+            config = config.lossy_utf8(lossy);
+        }
+        if let Some(socket) = self.socket {
+            config = config.socket(socket);
+        }
+        for route in self.routes {
+            config = config.route(route.prefix, route.root);
+        }
+        for pattern in self.allow_missing {
+            config = config.allow_missing(pattern);
+        }
+        for pattern in self.allow_secrets {
+            config = config.allow_secrets(pattern);
+        }
+        config
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("unable to read {}: {}", path.display(), err))
+}
+
+/// Configure archetype for the whole process.
+///
+/// This can only happen once and must happen before the first snapshot
+/// assertion, which otherwise fixes the defaults in place. If archetype
+/// is already configured the rejected `config` is returned.
+// The rejected config is handed back as-is, just like `OnceLock::set`.
+#[allow(clippy::result_large_err)]
+pub fn configure(config: GlobalConfig) -> Result<(), GlobalConfig> {
+    GLOBAL.set(config)
+}
+
+/// The process-wide configuration.
+pub(crate) fn global() -> &'static GlobalConfig {
+    GLOBAL.get_or_init(GlobalConfig::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{File, GlobalConfig, Palette, Update};
+    use std::path::PathBuf;
+
+    #[test]
+    fn file_overrides_defaults() {
+        let file: File = toml::from_str(
+            r#"
+            root = "tests/goldens"
+            update = "never"
+            extension = "golden"
+            allow_missing = ["legacy/*"]
+            allow_secrets = ["fixtures/*"]
+            dry_run = true
+            palette = "colorblind"
+            socket = "tcp://127.0.0.1:7878"
+            filters = [
+                { pattern = "\\d{4}-\\d{2}-\\d{2}", replacement = "[date]" },
+                { pattern = "id=(\\w)\\w*", replacement = "id=$1…" },
+            ]
+            scrub = ["email", { detector = "phone", replacement = "[tel]" }]
+            "#,
+        )
+        .unwrap();
+        let config = file.apply(GlobalConfig::new());
+        assert_eq!(config.root, PathBuf::from("tests/goldens"));
+        assert_eq!(config.update, Update::Never);
+        assert_eq!(config.extension, "golden");
+        assert!(config.dry_run);
+        assert_eq!(config.palette, Palette::Colorblind);
+        assert_eq!(config.socket.as_deref(), Some("tcp://127.0.0.1:7878"));
+        assert!(config.allows_missing("legacy/report"));
+        assert!(!config.allows_missing("report"));
+        assert!(config.allows_secrets("fixtures/credentials"));
+        assert!(!config.allows_secrets("report"));
+        assert_eq!(
+            config.apply_filters(String::from("on 2023-10-21 for id=abc123")),
+            "on [date] for id=a…"
+        );
+        assert_eq!(
+            config.apply_filters(String::from("ada@example.com, 555-010-0199")),
+            "[email], [tel]"
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn routes_keys_to_roots() {
+        let file: File = toml::from_str(
+            r#"
+            routes = [
+                { prefix = "api/", root = "tests/goldens/api" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let config = file.apply(GlobalConfig::new());
+        let storage = config.snapshot_storage();
+        let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            PathBuf::from(storage.location("api/users")),
+            manifest.join("tests/goldens/api/users.snap")
+        );
+        assert_eq!(
+            PathBuf::from(storage.location("search")),
+            manifest.join("snapshots/search.snap")
+        );
+    }
+
+    #[test]
+    fn file_rejects_unknown_keys() {
+        assert!(toml::from_str::<File>("snapshot_root = \"goldens\"").is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn snapshot_dir_is_relative_to_manifest() {
+        let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            GlobalConfig::new().snapshot_dir(),
+            manifest.join("snapshots")
+        );
+        assert_eq!(
+            GlobalConfig::new().root("tests/goldens").snapshot_dir(),
+            manifest.join("tests/goldens")
+        );
+        assert_eq!(
+            GlobalConfig::new().root("/tmp/goldens").snapshot_dir(),
+            PathBuf::from("/tmp/goldens")
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn snapshot_dir_in_runfiles() {
+        use super::Resolution;
+        use std::path::Path;
+
+        let env = |name: &str| match name {
+            "TEST_SRCDIR" => Some(String::from("/sandbox/runfiles")),
+            "TEST_WORKSPACE" => Some(String::from("acme")),
+            _ => None,
+        };
+        assert_eq!(
+            GlobalConfig::new()
+                .resolution(Resolution::Runfiles)
+                .resolve(Path::new("services/search/snapshots"), env),
+            PathBuf::from("/sandbox/runfiles/acme/services/search/snapshots")
+        );
+    }
+}
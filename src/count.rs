@@ -0,0 +1,185 @@
+//! Counting the snapshot assertions a test makes.
+//!
+//! Counts are kept per thread, as tests are, so only assertions made on
+//! the thread that created a guard are counted by it.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static RECORDED: RefCell<Recorded> = RefCell::new(Recorded::default());
+}
+
+/// The keys asserted on this thread while any guard is alive.
+#[derive(Default)]
+struct Recorded {
+    guards: usize,
+    keys: Vec<String>,
+}
+
+/// Note that the snapshot under `key` was asserted.
+pub(crate) fn record(key: &str) {
+    RECORDED.with(|recorded| {
+        let mut recorded = recorded.borrow_mut();
+        if recorded.guards > 0 {
+            recorded.keys.push(key.to_string());
+        }
+    });
+}
+
+/// Start counting assertions, returning how many have been recorded.
+fn start() -> usize {
+    RECORDED.with(|recorded| {
+        let mut recorded = recorded.borrow_mut();
+        recorded.guards += 1;
+        recorded.keys.len()
+    })
+}
+
+fn stop() {
+    RECORDED.with(|recorded| {
+        let mut recorded = recorded.borrow_mut();
+        recorded.guards -= 1;
+        if recorded.guards == 0 {
+            recorded.keys.clear();
+        }
+    });
+}
+
+/// How many keys recorded since `start` begin with `prefix`.
+fn since(start: usize, prefix: &str) -> usize {
+    RECORDED.with(|recorded| {
+        recorded.borrow().keys[start..]
+            .iter()
+            .filter(|key| key.starts_with(prefix))
+            .count()
+    })
+}
+
+/// Counts the snapshot assertions made under a key prefix from when it was
+/// created. See `count_snapshots`.
+#[derive(Debug)]
+pub struct SnapshotCount {
+    prefix: String,
+    start: usize,
+}
+
+impl SnapshotCount {
+    /// How many snapshots under the prefix have been asserted so far.
+    pub fn count(&self) -> usize {
+        since(self.start, &self.prefix)
+    }
+
+    /// Panic unless exactly `expected` snapshots under the prefix have
+    /// been asserted.
+    pub fn assert(&self, expected: usize) {
+        let count = self.count();
+        if count != expected {
+            panic!(
+                "expected {} snapshots under {:?} but {} were asserted",
+                expected, self.prefix, count
+            );
+        }
+    }
+}
+
+impl Drop for SnapshotCount {
+    fn drop(&mut self) {
+        stop();
+    }
+}
+
+/// Count the snapshot assertions made on this thread under keys starting
+/// with `prefix`, so that a loop that exits early can't skip assertions
+/// and still pass.
+///
+/// ```
+/// let count = archetype::count_snapshots("count-example/");
+/// for id in [4, 7, 9] {
+///     archetype::snap(&format!("count-example/{}", id), id.to_string());
+/// }
+/// count.assert(3);
+/// ```
+pub fn count_snapshots(prefix: impl Into<String>) -> SnapshotCount {
+    SnapshotCount {
+        prefix: prefix.into(),
+        start: start(),
+    }
+}
+
+/// Fails the test when dropped if fewer snapshot assertions were made than
+/// expected. See `expect_snapshots`.
+#[must_use = "the guard checks the count when it is dropped, so bind it to a variable"]
+#[derive(Debug)]
+pub struct ExpectSnapshots {
+    count: SnapshotCount,
+    expected: usize,
+}
+
+impl Drop for ExpectSnapshots {
+    fn drop(&mut self) {
+        // A test that is already failing has said all it needs to.
+        if std::thread::panicking() {
+            return;
+        }
+        let count = self.count.count();
+        if count < self.expected {
+            panic!(
+                "expected at least {} snapshot assertions but {} ran",
+                self.expected, count
+            );
+        }
+    }
+}
+
+/// Fail the test unless at least `expected` snapshot assertions run on
+/// this thread before the returned guard is dropped, catching a test that
+/// was refactored into accidentally asserting nothing.
+///
+/// ```
+/// let _guard = archetype::expect_snapshots(1);
+/// archetype::snap("expect-example", String::from("asserted"));
+/// ```
+pub fn expect_snapshots(expected: usize) -> ExpectSnapshots {
+    ExpectSnapshots {
+        count: count_snapshots(""),
+        expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_snapshots, expect_snapshots};
+
+    #[test]
+    fn counts_snapshots_under_prefix() {
+        let all = count_snapshots("");
+        let counted = count_snapshots("count/");
+        for id in [4, 7] {
+            crate::snap(&format!("count/{}", id), id.to_string());
+        }
+        crate::snap("count_other", String::from("9"));
+        counted.assert(2);
+        all.assert(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 snapshots under \"count/\" but 1 were asserted")]
+    fn count_mismatch_panics() {
+        let counted = count_snapshots("count/");
+        crate::snap("count/4", String::from("4"));
+        counted.assert(2);
+    }
+
+    #[test]
+    fn expect_snapshots_passes_when_enough_ran() {
+        let _guard = expect_snapshots(2);
+        crate::snap("count/4", String::from("4"));
+        crate::snap("count/7", String::from("7"));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected at least 1 snapshot assertions but 0 ran")]
+    fn expect_snapshots_fails_when_none_ran() {
+        let _guard = expect_snapshots(1);
+    }
+}
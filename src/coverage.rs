@@ -0,0 +1,151 @@
+//! Which stored snapshots a test run actually references.
+//!
+//! With `GlobalConfig::coverage` set, every test binary appends the keys
+//! it asserted against to a file when it exits, so a run over several
+//! binaries builds up a single list. `Coverage` then compares that list
+//! with the snapshots in storage, to find goldens no test exercises.
+
+use crate::store::Storage;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::sync::{Mutex, Once};
+
+static KEYS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static REPORT: Once = Once::new();
+
+/// Note that the snapshot under `key` was referenced, if coverage is
+/// being recorded.
+pub(crate) fn record(config: &crate::GlobalConfig, key: &str) {
+    if config.coverage.is_none() {
+        return;
+    }
+    KEYS.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(key.to_string());
+    REPORT.call_once(|| crate::exit::at_exit(write));
+}
+
+extern "C" fn write() {
+    use std::io::Write;
+
+    let Some(path) = &crate::config::global().coverage else {
+        return;
+    };
+    let keys = KEYS.lock().unwrap_or_else(|err| err.into_inner());
+    let appended = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| keys.iter().try_for_each(|key| writeln!(file, "{}", key)));
+    if let Err(err) = appended {
+        eprintln!(
+            "warning: unable to write snapshot coverage to {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+/// How many of the snapshots in each directory were referenced by a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// The referenced and present snapshot counts by directory within
+    /// storage, where the top level is `.`.
+    pub directories: BTreeMap<String, (usize, usize)>,
+    /// The keys of stored snapshots that weren't referenced.
+    pub unreferenced: Vec<String>,
+}
+
+impl Coverage {
+    /// Compare the snapshots in `storage` with the `referenced` keys, such
+    /// as those listed in a coverage file.
+    pub fn of<S: AsRef<str>>(
+        storage: &dyn Storage,
+        referenced: impl IntoIterator<Item = S>,
+    ) -> io::Result<Coverage> {
+        let referenced: BTreeSet<String> = referenced
+            .into_iter()
+            .map(|key| key.as_ref().to_string())
+            .collect();
+        let mut coverage = Coverage::default();
+        for key in storage.keys()? {
+            let directory = match key.rsplit_once('/') {
+                Some((directory, _)) => directory.to_string(),
+                None => String::from("."),
+            };
+            let (hit, present) = coverage.directories.entry(directory).or_default();
+            *present += 1;
+            if referenced.contains(&key) {
+                *hit += 1;
+            } else {
+                coverage.unreferenced.push(key);
+            }
+        }
+        Ok(coverage)
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |hit: usize, present: usize| match present {
+            0 => 100.0,
+            present => 100.0 * hit as f64 / present as f64,
+        };
+        let (mut hit, mut present) = (0, 0);
+        for (directory, (directory_hit, directory_present)) in &self.directories {
+            hit += directory_hit;
+            present += directory_present;
+            writeln!(
+                f,
+                "{:>6.1}%  {}/{}  {}",
+                percent(*directory_hit, *directory_present),
+                directory_hit,
+                directory_present,
+                directory
+            )?;
+        }
+        write!(
+            f,
+            "{:>6.1}%  {}/{}  total",
+            percent(hit, present),
+            hit,
+            present
+        )?;
+        if !self.unreferenced.is_empty() {
+            write!(f, "\n\nunreferenced:")?;
+            for key in &self.unreferenced {
+                write!(f, "\n  {}", key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Coverage;
+    use crate::store::BundledStorage;
+
+    #[test]
+    fn coverage_by_directory() {
+        let storage = BundledStorage::new()
+            .with("search", "")
+            .with("api/users", "")
+            .with("api/orders", "")
+            .with("api/v2/users", "");
+        let coverage = Coverage::of(&storage, ["search", "api/users", "missing"]).unwrap();
+        assert_eq!(coverage.unreferenced, vec!["api/orders", "api/v2/users"]);
+        assert_eq!(
+            coverage.to_string(),
+            " 100.0%  1/1  .
+  50.0%  1/2  api
+   0.0%  0/1  api/v2
+  50.0%  2/4  total
+
+unreferenced:
+  api/orders
+  api/v2/users"
+        );
+    }
+}
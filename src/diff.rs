@@ -0,0 +1,553 @@
+//! Diffing and rendering of mismatched snapshots.
+
+use crate::{DiffStyle, Frame, Palette, Settings};
+use similar::{ChangeTag, DiffOp, TextDiff};
+
+const RESET: &str = "\x1b[0m";
+
+/// Diff `old` against `new` line-by-line using the algorithm from
+/// `settings`, refining it for no longer than its timeout or deadline.
+pub(crate) fn lines<'a>(
+    settings: &Settings,
+    old: &'a str,
+    new: &'a str,
+) -> TextDiff<'a, 'a, 'a, str> {
+    let mut config = TextDiff::configure();
+    config.algorithm(settings.algorithm.into());
+    let timeout = match (settings.timeout, settings.deadline) {
+        (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+        (timeout, deadline) => timeout.or(deadline),
+    };
+    if let Some(timeout) = timeout {
+        config.timeout(timeout);
+    }
+    config.diff_lines(old, new)
+}
+
+/// Describe how a mismatched snapshot was compared, for verbose output:
+/// where it is stored, the key it was linked from if any, how similar the
+/// two sides are, and what normalization was applied first.
+pub(crate) fn details(
+    settings: &Settings,
+    filters: usize,
+    location: &str,
+    linked_from: Option<&str>,
+    ratio: Option<f32>,
+) -> String {
+    let paths = |paths: &[String]| match paths {
+        [] => String::from("none"),
+        paths => paths.join(", "),
+    };
+    let mut out = format!("stored at: {}\n", location);
+    if let Some(key) = linked_from {
+        out.push_str(&format!("linked from: {}\n", key));
+    }
+    match ratio {
+        Some(ratio) => out.push_str(&format!("similarity: {:.3}\n", ratio)),
+        None => out.push_str("similarity: unknown, the diff timed out\n"),
+    }
+    out.push_str(&format!("comparison: {:?}\n", settings.comparison));
+    out.push_str(&format!("algorithm: {:?}\n", settings.algorithm));
+    out.push_str(&format!("filters: {}\n", filters));
+    out.push_str(&format!(
+        "ignored paths: {}\n",
+        paths(&settings.ignore_paths)
+    ));
+    out.push_str(&format!(
+        "unordered paths: {}",
+        paths(&settings.unordered_paths)
+    ));
+    out
+}
+
+/// Summarize two snapshots that differ, in `frame` around `key`, for
+/// when diffing them took too long.
+pub(crate) fn timed_out(frame: Frame, key: &str, stored: &str, subject: &str) -> String {
+    let gutter = frame.gutter;
+    format!(
+        "{}\n {gutter} contents differ (diff timed out)\n\
+         -{gutter} {} bytes, hash {}\n\
+         +{gutter} {} bytes, hash {}\n{}",
+        frame.open(key),
+        stored.len(),
+        crate::store::hash(stored),
+        subject.len(),
+        crate::store::hash(subject),
+        frame.close(key),
+    )
+}
+
+/// How many characters either side of the first difference are excerpted.
+const EXCERPT: usize = 30;
+
+/// Locate the first character that differs between `stored` and
+/// `subject` by its line and column, with an excerpt of each side's line
+/// around it and a caret beneath, so a single changed character is easy
+/// to find in a large snapshot. Lines too long to show whole are cut
+/// with `frame`'s ellipsis.
+pub(crate) fn first_difference(frame: Frame, stored: &str, subject: &str) -> Option<String> {
+    let (mut old, mut new) = (stored.split('\n'), subject.split('\n'));
+    let mut line = 1;
+    let (old, new) = loop {
+        match (old.next(), new.next()) {
+            (None, None) => return None,
+            (old, new) if old == new => line += 1,
+            sides => break sides,
+        }
+    };
+    let chars = |side: Option<&str>| side.unwrap_or_default().chars().collect::<Vec<char>>();
+    let (old_chars, new_chars) = (chars(old), chars(new));
+    let column = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let start = column.saturating_sub(EXCERPT);
+    let excerpt = |side: Option<&str>, chars: &[char]| {
+        if side.is_none() {
+            return String::from("(no such line)");
+        }
+        let end = chars.len().min(column + EXCERPT);
+        let mut excerpt: String = chars[start.min(end)..end].iter().collect();
+        if start > 0 {
+            excerpt.insert(0, frame.ellipsis);
+        }
+        if end < chars.len() {
+            excerpt.push(frame.ellipsis);
+        }
+        excerpt
+    };
+    let caret = column - start + usize::from(start > 0);
+    Some(format!(
+        "first difference at line {}, column {}:\n  stored: {}\n  new:    {}\n          {}^",
+        line,
+        column + 1,
+        excerpt(old, &old_chars),
+        excerpt(new, &new_chars),
+        " ".repeat(caret)
+    ))
+}
+
+/// How many differing lines are annotated with their location.
+const ANNOTATED: usize = 5;
+
+/// A differing line, located by its line in the stored snapshot.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Annotation {
+    pub(crate) line: usize,
+    pub(crate) expected: Option<String>,
+    pub(crate) got: Option<String>,
+}
+
+impl Annotation {
+    /// Describe what was expected and what was got instead.
+    pub(crate) fn message(&self) -> String {
+        let quote = |line: &Option<String>| {
+            line.as_ref()
+                .map_or(String::from("nothing"), |line| format!("{:?}", line))
+        };
+        format!(
+            "expected {} got {}",
+            quote(&self.expected),
+            quote(&self.got)
+        )
+    }
+
+    /// Render as `path:line: expected ... got ...` for the stored snapshot
+    /// at `location`, for editors and CI problem matchers to link to.
+    pub(crate) fn render(&self, location: &str) -> String {
+        format!("{}:{}: {}", location, self.line, self.message())
+    }
+}
+
+/// Annotate the first few differing lines of `diff` with their line in the
+/// stored snapshot.
+pub(crate) fn annotations<'a>(diff: &TextDiff<'a, 'a, 'a, str>) -> Vec<Annotation> {
+    let old = diff.old_slices();
+    let new = diff.new_slices();
+    let line = |lines: &[&str], i: usize, len: usize| {
+        (i < len).then(|| lines[i].trim_end_matches('\n').to_string())
+    };
+    let mut out = Vec::new();
+    for op in diff.ops() {
+        let (old_index, old_len, new_index, new_len) = match *op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+                ..
+            } => (old_index, old_len, new_index, 0),
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+                ..
+            } => (old_index, 0, new_index, new_len),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => (old_index, old_len, new_index, new_len),
+        };
+        for i in 0..old_len.max(new_len) {
+            if out.len() == ANNOTATED {
+                return out;
+            }
+            out.push(Annotation {
+                line: old_index + i.min(old_len.saturating_sub(1)) + 1,
+                expected: line(&old[old_index..], i, old_len),
+                got: line(&new[new_index..], i, new_len),
+            });
+        }
+    }
+    out
+}
+
+/// The fewest lines a block must span to be shown as moved, as single
+/// lines such as `}` are too common to have meaningfully moved.
+const MOVED: usize = 2;
+
+/// Render `diff` framed by `key` in the style from `settings`.
+pub(crate) fn render<'a>(
+    settings: &Settings,
+    key: &str,
+    diff: &TextDiff<'a, 'a, 'a, str>,
+) -> String {
+    let config = crate::config::global();
+    let palette = config.color.enabled().then_some(config.palette);
+    match settings.diff_style {
+        DiffStyle::Interleaved => paint(settings.frame, key, diff, palette),
+        DiffStyle::SideBySide => side_by_side(settings.frame, key, diff, palette, settings.width()),
+    }
+}
+
+/// Render `diff` in `frame` around `key`, in the colors of `palette`, if
+/// any.
+///
+/// Blocks of lines that were deleted in one place and inserted verbatim
+/// in another are marked as moved, with `<` where they were and `>`
+/// where they are now, rather than as unrelated deletions and
+/// insertions. Trailing commas are disregarded when matching blocks, so
+/// reordered elements of a JSON array are moves too.
+fn paint<'a>(
+    frame: Frame,
+    key: &str,
+    diff: &TextDiff<'a, 'a, 'a, str>,
+    palette: Option<Palette>,
+) -> String {
+    let changes: Vec<_> = diff.iter_all_changes().collect();
+    let moved = moved(&changes);
+    let mut out = format!("{}\n", frame.open(key));
+    for (change, moved) in changes.iter().zip(moved) {
+        let (sign, style) = sign(change.tag(), moved, palette);
+        let sign = format!("{}{}", sign, frame.gutter);
+        if !style.is_empty() {
+            let line = change.value();
+            let (line, newline) = match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            };
+            out.push_str(&format!("{}{}{}{}{}", style, sign, line, RESET, newline));
+        } else {
+            out.push_str(&format!("{}{}", sign, change));
+        }
+    }
+    out.push_str(&frame.close(key));
+    out
+}
+
+/// The sign of a line changed with `tag`, and `moved` or not, and its
+/// color from `palette`, if any.
+fn sign(tag: ChangeTag, moved: bool, palette: Option<Palette>) -> (char, &'static str) {
+    let (sign, style) = match (tag, moved) {
+        (ChangeTag::Delete, false) => ('-', 0),
+        (ChangeTag::Insert, false) => ('+', 1),
+        (ChangeTag::Delete, true) => ('<', 2),
+        (ChangeTag::Insert, true) => ('>', 2),
+        (ChangeTag::Equal, _) => return (' ', ""),
+    };
+    (sign, palette.map_or("", |palette| palette.styles()[style]))
+}
+
+/// Render `diff` in `frame` around `key` in two columns `width` wide in all, the
+/// stored snapshot on the left and the new one on the right, in the
+/// colors of `palette`, if any. Changed lines are aligned with the lines
+/// that replaced them, and lines too long for their column are cut short.
+fn side_by_side<'a>(
+    frame: Frame,
+    key: &str,
+    diff: &TextDiff<'a, 'a, 'a, str>,
+    palette: Option<Palette>,
+    width: usize,
+) -> String {
+    let column = width.saturating_sub(5).max(20) / 2;
+    let changes: Vec<_> = diff.iter_all_changes().collect();
+    let moved = moved(&changes);
+    let cell = |index: Option<usize>| -> (String, &str) {
+        let Some(index) = index else {
+            return (format!(" {}{}", frame.gutter, " ".repeat(column)), "");
+        };
+        let change = &changes[index];
+        let (sign, style) = sign(change.tag(), moved[index], palette);
+        let line = change.value().trim_end_matches(['\n', '\r']);
+        let mut text: String = line.chars().take(column).collect();
+        if line.chars().count() > column {
+            text.pop();
+            text.push(frame.ellipsis);
+        }
+        let padding = column - text.chars().count();
+        let cell = format!("{}{}{}{}", sign, frame.gutter, text, " ".repeat(padding));
+        (cell, style)
+    };
+    let mut out = format!("{}\n", frame.open(key));
+    let mut index = 0;
+    for op in diff.ops() {
+        let (old_len, new_len, equal) = match *op {
+            DiffOp::Equal { len, .. } => (len, len, true),
+            DiffOp::Delete { old_len, .. } => (old_len, 0, false),
+            DiffOp::Insert { new_len, .. } => (0, new_len, false),
+            DiffOp::Replace {
+                old_len, new_len, ..
+            } => (old_len, new_len, false),
+        };
+        for row in 0..old_len.max(new_len) {
+            let (left, right) = match equal {
+                // Equal lines are the same on both sides.
+                true => (Some(index + row), Some(index + row)),
+                false => (
+                    (row < old_len).then_some(index + row),
+                    (row < new_len).then_some(index + old_len + row),
+                ),
+            };
+            for (i, side) in [left, right].into_iter().enumerate() {
+                let (text, style) = cell(side);
+                let text = if i == 1 { text.trim_end() } else { &text };
+                if !style.is_empty() {
+                    out.push_str(&format!("{}{}{}", style, text, RESET));
+                } else {
+                    out.push_str(text);
+                }
+                out.push(if i == 0 { ' ' } else { '\n' });
+            }
+        }
+        index += if equal { old_len } else { old_len + new_len };
+    }
+    out.push_str(&frame.close(key));
+    out
+}
+
+/// How many changed lines moves are looked for among, as finding them
+/// is quadratic.
+const MOVES_WITHIN: usize = 2_000;
+
+/// Which of `changes` belong to blocks that were moved rather than
+/// changed: the longest runs of lines deleted in one place and inserted in
+/// another, repeatedly, down to `MOVED` lines.
+fn moved(changes: &[similar::Change<&str>]) -> Vec<bool> {
+    let mut moved = vec![false; changes.len()];
+    let changed = changes
+        .iter()
+        .filter(|change| change.tag() != ChangeTag::Equal)
+        .count();
+    if changed > MOVES_WITHIN {
+        return moved;
+    }
+    let mut runs: Vec<(ChangeTag, usize, usize)> = Vec::new();
+    for (i, change) in changes.iter().enumerate() {
+        match runs.last_mut() {
+            Some((tag, _, end)) if *tag == change.tag() => *end = i + 1,
+            _ => runs.push((change.tag(), i, i + 1)),
+        }
+    }
+    let lines: Vec<&str> = changes
+        .iter()
+        .map(|change| change.value().trim_end().trim_end_matches(','))
+        .collect();
+    let deleted = runs.iter().filter(|(tag, _, _)| *tag == ChangeTag::Delete);
+    let inserted: Vec<_> = runs
+        .iter()
+        .filter(|(tag, _, _)| *tag == ChangeTag::Insert)
+        .collect();
+    let pairs: Vec<_> = deleted
+        .flat_map(|deleted| inserted.iter().map(move |inserted| (deleted, *inserted)))
+        // An insertion right beside the deletion is a change in place, such
+        // as a comma being added, not a move.
+        .filter(|((_, start, end), (_, from, to))| from != end && to != start)
+        .collect();
+    loop {
+        // The longest block, as its length and where it starts on each side.
+        let mut best = (0, 0, 0);
+        for ((_, start, end), (_, from, to)) in &pairs {
+            let mut previous = vec![0; to - from + 1];
+            for old in *start..*end {
+                let mut current = vec![0; to - from + 1];
+                for new in *from..*to {
+                    if !moved[old] && !moved[new] && lines[old] == lines[new] {
+                        let len = previous[new - from] + 1;
+                        current[new - from + 1] = len;
+                        if len > best.0 {
+                            best = (len, old + 1 - len, new + 1 - len);
+                        }
+                    }
+                }
+                previous = current;
+            }
+        }
+        let (len, old, new) = best;
+        if len < MOVED {
+            return moved;
+        }
+        moved[old..old + len].fill(true);
+        moved[new..new + len].fill(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Frame, Palette, Settings};
+
+    #[test]
+    fn annotations_locate_differing_lines() {
+        let diff = super::lines(&Settings::new(), "a\nb\nc\nd\n", "a\nB\nc\nd\ne\n");
+        let rendered: Vec<String> = super::annotations(&diff)
+            .iter()
+            .map(|annotation| annotation.render("snapshots/search.snap"))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "snapshots/search.snap:2: expected \"b\" got \"B\"",
+                "snapshots/search.snap:5: expected nothing got \"e\"",
+            ]
+        );
+        let old: String = (0..10).map(|i| format!("{}\n", i)).collect();
+        let diff = super::lines(&Settings::new(), &old, "");
+        assert_eq!(super::annotations(&diff).len(), 5);
+    }
+
+    #[test]
+    fn first_difference_is_pinpointed() {
+        let stored = "{\n  \"price\": 10.50,\n  \"currency\": \"NZD\"\n}\n";
+        let subject = "{\n  \"price\": 10.05,\n  \"currency\": \"NZD\"\n}\n";
+        assert_eq!(
+            super::first_difference(Frame::ASCII, stored, subject).unwrap(),
+            "first difference at line 2, column 15:\n  \
+             stored:   \"price\": 10.50,\n  \
+             new:      \"price\": 10.05,\n                        \
+             ^"
+        );
+        let long = format!("{}x{}", "a".repeat(40), "b".repeat(40));
+        let changed = format!("{}y{}", "a".repeat(40), "b".repeat(40));
+        assert_eq!(
+            super::first_difference(Frame::ASCII, &long, &changed).unwrap(),
+            format!(
+                "first difference at line 1, column 41:\n  \
+                 stored: ~{a}x{b}~\n  \
+                 new:    ~{a}y{b}~\n          {pad}^",
+                a = "a".repeat(30),
+                b = "b".repeat(29),
+                pad = " ".repeat(31)
+            )
+        );
+        assert_eq!(
+            super::first_difference(Frame::ASCII, "a\n", "a\nb\n").unwrap(),
+            "first difference at line 2, column 1:\n  stored: \n  new:    b\n          ^"
+        );
+        assert_eq!(super::first_difference(Frame::ASCII, "a\n", "a\n"), None);
+    }
+
+    #[test]
+    fn moved_blocks_are_marked() {
+        let old = "[\n  {\n    \"id\": 4,\n    \"name\": \"ada\"\n  },\n  \"x\",\n  {\n    \"id\": 7,\n    \"name\": \"bob\"\n  }\n]\n";
+        let new = "[\n  \"x\",\n  {\n    \"id\": 7,\n    \"name\": \"bob\"\n  },\n  {\n    \"id\": 4,\n    \"name\": \"ada\"\n  }\n]\n";
+        let diff = super::lines(&Settings::new(), old, new);
+        assert_eq!(
+            super::paint(Frame::UNICODE, "people", &diff, None),
+            " ┏━━━━━━━━ people ━━━━━
+ ┃[
+<┃  {
+<┃    \"id\": 4,
+<┃    \"name\": \"ada\"
+-┃  },
+ ┃  \"x\",
+ ┃  {
+ ┃    \"id\": 7,
+ ┃    \"name\": \"bob\"
++┃  },
+>┃  {
+>┃    \"id\": 4,
+>┃    \"name\": \"ada\"
+ ┃  }
+ ┃]
+ ┗━━━━━━━━ people ━━━━━"
+        );
+        let diff = super::lines(&Settings::new(), "a\nb\n", "a,\nb,\n");
+        assert!(!super::paint(Frame::UNICODE, "commas", &diff, None).contains('<'));
+    }
+
+    #[test]
+    fn changes_are_colored_from_the_palette() {
+        let diff = super::lines(&Settings::new(), "a\nb\n", "a\nc\n");
+        assert_eq!(
+            super::paint(Frame::ASCII, "x", &diff, Some(Palette::Colorblind)),
+            " +-------- x -----\n \
+             |a\n\
+             \x1b[38;5;208m-|b\x1b[0m\n\
+             \x1b[38;5;33m+|c\x1b[0m\n \
+             +-------- x -----"
+        );
+    }
+
+    #[test]
+    fn side_by_side_aligns_changed_lines() {
+        let old = "{\n  \"id\": 4,\n  \"name\": \"ada lovelace\"\n}\n";
+        let new = "{\n  \"id\": 7,\n  \"name\": \"ada lovelace\",\n  \"role\": \"admin\"\n}\n";
+        let diff = super::lines(&Settings::new(), old, new);
+        assert_eq!(
+            super::side_by_side(Frame::ASCII, "user", &diff, None, 45),
+            " +-------- user -----
+ |{                     |{
+-|  \"id\": 4,           +|  \"id\": 7,
+-|  \"name\": \"ada love~ +|  \"name\": \"ada love~
+ |                     +|  \"role\": \"admin\"
+ |}                     |}
+ +-------- user -----"
+        );
+    }
+
+    #[test]
+    fn timed_out_summarizes_both_sides() {
+        assert_eq!(
+            super::timed_out(Frame::UNICODE, "search", "a", "bc"),
+            " ┏━━━━━━━━ search ━━━━━
+ ┃ contents differ (diff timed out)
+-┃ 1 bytes, hash af63dc4c8601ec8c
++┃ 2 bytes, hash 08a63507b54dd372
+ ┗━━━━━━━━ search ━━━━━"
+        );
+    }
+
+    #[test]
+    fn details_describe_comparison() {
+        let settings = Settings::new().ignore_path("/meta/request-id");
+        assert_eq!(
+            super::details(
+                &settings,
+                2,
+                "snapshots/search.snap",
+                Some("search_again"),
+                Some(0.5)
+            ),
+            "stored at: snapshots/search.snap
+linked from: search_again
+similarity: 0.500
+comparison: Lines
+algorithm: Myers
+filters: 2
+ignored paths: /meta/request-id
+unordered paths: none"
+        );
+    }
+}
@@ -0,0 +1,558 @@
+//! Snapshots of Graphviz DOT graphs.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Keywords that can't be written as bare IDs.
+const KEYWORDS: &[&str] = &["strict", "graph", "digraph", "subgraph", "node", "edge"];
+
+/// Take a snapshot of a Graphviz DOT graph under a file with the name
+/// `key`.
+///
+/// Graph builders are free to emit nodes and edges in whatever order
+/// they were visited and to name nodes with generated IDs, which makes
+/// for noisy diffs. The graph is canonicalized before it is stored:
+///
+/// * every node with a `label` is renamed `n0`, `n1`, and so on, in
+///   order of its label and then its other attributes,
+/// * graph attributes come first, then nodes, edges, and subgraphs,
+///   each sorted, though never past a `graph`, `node`, or `edge` default,
+///   since those only apply to what follows them,
+/// * attributes are sorted by name, quotes are dropped where they aren't
+///   needed, and indentation is normalized.
+///
+/// Nodes whose labels and attributes are identical keep the order they
+/// were declared in. This is a light canonicalizer over the DOT that
+/// graph builders emit, not a full DOT parser: edges between subgraphs
+/// aren't supported.
+///
+/// If the snapshot mismatches and Graphviz's `dot` is installed, both
+/// graphs are also rendered as SVG in the temporary directory, and the
+/// failure names them, for a visual comparison.
+///
+/// ```
+/// archetype::snap_dot(
+///     "dot-example",
+///     "digraph { node_0x2a [label=\"fetch\"]; node_0x1f [label=\"build\"]; node_0x2a -> node_0x1f; }",
+/// );
+/// ```
+///
+/// Panics if the graph isn't valid DOT.
+pub fn snap_dot(key: &str, dot: &str) {
+    let subject =
+        canonicalize(dot).unwrap_or_else(|err| panic!("invalid DOT for {}: {}", key, err));
+    let (config, settings) = crate::overrides::resolve(key);
+    let (storage, _) = crate::storage(&config, &settings);
+    let (_, stored) = crate::read(&*storage, key).unwrap_or_else(|err| panic!("{}", err));
+    if let Err(failure) = crate::check(key, subject.clone(), false) {
+        let stored = crate::meta::split(stored.as_deref().unwrap_or_default()).1;
+        match render(key, stored, &subject) {
+            Some(rendered) => panic!("{}\n{}", failure, rendered),
+            None => panic!("{}", failure),
+        }
+    }
+}
+
+/// Render the stored and new graphs as SVG with `dot`, describing where
+/// they were written, or `None` if they can't be.
+fn render(key: &str, stored: &str, subject: &str) -> Option<String> {
+    let dir = std::env::temp_dir().join("archetype-dot");
+    std::fs::create_dir_all(&dir).ok()?;
+    let name = key.replace('/', "-");
+    let mut paths = Vec::new();
+    for (side, dot) in [("expected", stored), ("actual", subject)] {
+        let mut child = Command::new("dot")
+            .arg("-Tsvg")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        child.stdin.take()?.write_all(dot.as_bytes()).ok()?;
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let path = dir.join(format!("{}.{}.svg", name, side));
+        std::fs::write(&path, output.stdout).ok()?;
+        paths.push(path);
+    }
+    Some(format!(
+        "rendered: expected at {}, actual at {}",
+        paths[0].display(),
+        paths[1].display()
+    ))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Id(String),
+    Punct(&'static str),
+}
+
+/// Split DOT into IDs and punctuation, dropping comments.
+fn tokenize(dot: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = dot.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '#' || (c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '-' && matches!(next, Some('>') | Some('-')) {
+            tokens.push(Token::Punct(if next == Some('>') { "->" } else { "--" }));
+            i += 2;
+        } else if let Some(punct) = ["{", "}", "[", "]", "=", ";", ",", ":"]
+            .into_iter()
+            .find(|punct| punct.starts_with(c))
+        {
+            tokens.push(Token::Punct(punct));
+            i += 1;
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            if i >= chars.len() {
+                return Err(String::from("unterminated string"));
+            }
+            i += 1;
+            tokens.push(Token::Id(unquote(chars[start..i].iter().collect())));
+        } else if c == '<' {
+            let start = i;
+            let mut depth = 0;
+            loop {
+                match chars.get(i) {
+                    Some('<') => depth += 1,
+                    Some('>') => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(String::from("unterminated HTML string")),
+                }
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            tokens.push(Token::Id(chars[start..i].iter().collect()));
+        } else if bare(c) || c == '-' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && bare(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token::Id(chars[start..i].iter().collect()));
+        } else {
+            return Err(format!("unexpected {:?}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+fn bare(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || !c.is_ascii()
+}
+
+/// Drop the quotes around `quoted` if the ID means the same without them.
+fn unquote(quoted: String) -> String {
+    let inner = &quoted[1..quoted.len() - 1];
+    let identifier = inner
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_ascii_digit() && bare(c) && c != '.')
+        && inner.chars().all(|c| bare(c) && c != '.');
+    if identifier && !KEYWORDS.contains(&inner.to_lowercase().as_str()) {
+        inner.to_string()
+    } else {
+        quoted
+    }
+}
+
+type Attrs = Vec<(String, String)>;
+
+/// A node, with any port.
+#[derive(Clone, Debug)]
+struct Endpoint {
+    id: String,
+    port: String,
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Attr(String, String),
+    Default(String, Attrs),
+    Node(Endpoint, Attrs),
+    Edge(Vec<Endpoint>, &'static str, Attrs),
+    Subgraph(Block),
+}
+
+#[derive(Clone, Debug)]
+struct Block {
+    header: String,
+    statements: Vec<Statement>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn punct(&mut self, punct: &'static str) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    fn id(&mut self) -> Result<String, String> {
+        match self.tokens.get(self.at).cloned() {
+            Some(Token::Id(id)) => {
+                self.at += 1;
+                Ok(id)
+            }
+            Some(Token::Punct(punct)) => Err(format!("expected an ID, not {:?}", punct)),
+            None => Err(String::from("expected an ID, not the end")),
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), String> {
+        if self.punct(punct) {
+            Ok(())
+        } else {
+            Err(format!("expected {:?}", punct))
+        }
+    }
+
+    fn graph(&mut self) -> Result<Block, String> {
+        let mut header = Vec::new();
+        if self.keyword("strict") {
+            header.push(self.id()?.to_lowercase());
+        }
+        if !self.keyword("graph") && !self.keyword("digraph") {
+            return Err(String::from("expected graph or digraph"));
+        }
+        header.push(self.id()?.to_lowercase());
+        if let Some(Token::Id(_)) = self.peek() {
+            header.push(self.id()?);
+        }
+        let block = self.block(header.join(" "))?;
+        match self.peek() {
+            None => Ok(block),
+            Some(_) => Err(String::from("expected the end after the graph")),
+        }
+    }
+
+    fn block(&mut self, header: String) -> Result<Block, String> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while !self.punct("}") {
+            if self.peek().is_none() {
+                return Err(String::from("expected \"}\""));
+            }
+            if self.punct(";") || self.punct(",") {
+                continue;
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(Block { header, statements })
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.keyword("subgraph") || self.peek() == Some(&Token::Punct("{")) {
+            let mut header = Vec::new();
+            if self.keyword("subgraph") {
+                self.id()?;
+                header.push(String::from("subgraph"));
+                if let Some(Token::Id(_)) = self.peek() {
+                    header.push(self.id()?);
+                }
+            }
+            let block = self.block(header.join(" "))?;
+            if matches!(self.peek(), Some(Token::Punct("->" | "--"))) {
+                return Err(String::from("edges between subgraphs aren't supported"));
+            }
+            return Ok(Statement::Subgraph(block));
+        }
+        if ["graph", "node", "edge"]
+            .iter()
+            .any(|kind| self.keyword(kind))
+        {
+            let kind = self.id()?.to_lowercase();
+            return Ok(Statement::Default(kind, self.attrs()?));
+        }
+        let first = self.endpoint()?;
+        if first.port.is_empty() && self.punct("=") {
+            return Ok(Statement::Attr(first.id, self.id()?));
+        }
+        let mut endpoints = vec![first];
+        let mut op = "";
+        for candidate in ["->", "--"] {
+            while self.punct(candidate) {
+                op = candidate;
+                if self.keyword("subgraph") || self.peek() == Some(&Token::Punct("{")) {
+                    return Err(String::from("edges between subgraphs aren't supported"));
+                }
+                endpoints.push(self.endpoint()?);
+            }
+        }
+        let attrs = self.attrs()?;
+        match endpoints.len() {
+            1 => Ok(Statement::Node(endpoints.remove(0), attrs)),
+            _ => Ok(Statement::Edge(endpoints, op, attrs)),
+        }
+    }
+
+    fn endpoint(&mut self) -> Result<Endpoint, String> {
+        let id = self.id()?;
+        let mut port = String::new();
+        while self.punct(":") {
+            port.push(':');
+            port.push_str(&self.id()?);
+        }
+        Ok(Endpoint { id, port })
+    }
+
+    fn attrs(&mut self) -> Result<Attrs, String> {
+        let mut attrs = Vec::new();
+        while self.punct("[") {
+            while !self.punct("]") {
+                if self.punct(",") || self.punct(";") {
+                    continue;
+                }
+                let name = self.id()?;
+                self.expect("=")?;
+                attrs.push((name, self.id()?));
+            }
+        }
+        attrs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(attrs)
+    }
+}
+
+/// Canonicalize DOT as described by `snap_dot`.
+fn canonicalize(dot: &str) -> Result<String, String> {
+    let mut parser = Parser {
+        tokens: tokenize(dot)?,
+        at: 0,
+    };
+    let mut graph = parser.graph()?;
+    let mut labelled = Vec::new();
+    collect_labelled(&graph, &mut labelled);
+    labelled.sort_by(|(_, a), (_, b)| a.cmp(b));
+    let mut ids = HashMap::new();
+    for (id, _) in labelled {
+        let next = format!("n{}", ids.len());
+        ids.entry(id).or_insert(next);
+    }
+    rename(&mut graph, &ids);
+    let mut out = String::new();
+    write_block(&graph, 0, &mut out);
+    Ok(out)
+}
+
+/// Every node declared with a label, and what it sorts by.
+fn collect_labelled(block: &Block, labelled: &mut Vec<(String, (String, String))>) {
+    for statement in &block.statements {
+        match statement {
+            Statement::Node(node, attrs) if attrs.iter().any(|(name, _)| name == "label") => {
+                let label = attrs.iter().find(|(name, _)| name == "label");
+                let label = label.map_or("", |(_, label)| label.as_str());
+                labelled.push((node.id.clone(), (label.to_string(), render_attrs(attrs))));
+            }
+            Statement::Subgraph(block) => collect_labelled(block, labelled),
+            _ => {}
+        }
+    }
+}
+
+fn rename(block: &mut Block, ids: &HashMap<String, String>) {
+    let rename_endpoint = |endpoint: &mut Endpoint| {
+        if let Some(id) = ids.get(&endpoint.id) {
+            endpoint.id = id.clone();
+        }
+    };
+    for statement in &mut block.statements {
+        match statement {
+            Statement::Node(node, _) => rename_endpoint(node),
+            Statement::Edge(endpoints, _, _) => endpoints.iter_mut().for_each(rename_endpoint),
+            Statement::Subgraph(block) => rename(block, ids),
+            Statement::Attr(..) | Statement::Default(..) => {}
+        }
+    }
+}
+
+fn render_attrs(attrs: &Attrs) -> String {
+    let attrs: Vec<String> = attrs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    attrs.join(", ")
+}
+
+fn render_endpoint(endpoint: &Endpoint) -> String {
+    format!("{}{}", endpoint.id, endpoint.port)
+}
+
+fn write_block(block: &Block, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    if block.header.is_empty() {
+        out.push_str("{\n");
+    } else {
+        out.push_str(&format!("{} {{\n", block.header));
+    }
+    // Attributes of the graph itself apply wherever they are, so they
+    // come first.
+    let mut attrs: Vec<String> = block
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Attr(name, value) => Some(format!("{}={};", name, value)),
+            _ => None,
+        })
+        .collect();
+    attrs.sort();
+    for attr in attrs {
+        out.push_str(&format!("{}  {}\n", indent, attr));
+    }
+    // Defaults only apply to the statements after them, so statements
+    // are only sorted between them.
+    let mut run: Vec<(u8, String)> = Vec::new();
+    let flush = |run: &mut Vec<(u8, String)>, out: &mut String| {
+        run.sort();
+        for (_, line) in run.drain(..) {
+            out.push_str(&format!("{}  {}\n", indent, line));
+        }
+    };
+    for statement in &block.statements {
+        let with_attrs = |line: String, attrs: &Attrs| match attrs.is_empty() {
+            true => format!("{};", line),
+            false => format!("{} [{}];", line, render_attrs(attrs)),
+        };
+        match statement {
+            Statement::Attr(..) => {}
+            Statement::Default(kind, attrs) => {
+                flush(&mut run, out);
+                out.push_str(&format!(
+                    "{}  {}\n",
+                    indent,
+                    with_attrs(kind.clone(), attrs)
+                ));
+            }
+            Statement::Node(node, attrs) => run.push((0, with_attrs(render_endpoint(node), attrs))),
+            Statement::Edge(endpoints, op, attrs) => {
+                let endpoints: Vec<String> = endpoints.iter().map(render_endpoint).collect();
+                let line = endpoints.join(&format!(" {} ", op));
+                run.push((1, with_attrs(line, attrs)));
+            }
+            Statement::Subgraph(subgraph) => {
+                let mut rendered = String::new();
+                write_block(subgraph, depth + 1, &mut rendered);
+                run.push((2, rendered));
+            }
+        }
+    }
+    flush(&mut run, out);
+    out.push_str(&format!("{}}}", indent));
+    if depth == 0 {
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, snap_dot};
+
+    #[test]
+    fn canonical_regardless_of_order_and_ids() {
+        let a = r#"digraph deps {
+            // generated
+            rankdir = "LR";
+            node [shape=box];
+            p2 [label="serde", color=red];
+            p1 [label="archetype"];
+            p1 -> p2 [style=dashed];
+        }"#;
+        let b = r#"digraph deps {
+            node [shape=box]
+            x9 [color="red" label="serde"]
+            x8 -> x9 [style="dashed"]
+            x8 [label=archetype]
+            rankdir=LR
+        }"#;
+        assert_eq!(
+            canonicalize(a).unwrap(),
+            "digraph deps {
+  rankdir=LR;
+  node [shape=box];
+  n0 [label=archetype];
+  n1 [color=red, label=serde];
+  n0 -> n1 [style=dashed];
+}
+"
+        );
+        assert_eq!(canonicalize(a), canonicalize(b));
+    }
+
+    #[test]
+    fn subgraphs_and_ports() {
+        assert_eq!(
+            canonicalize(
+                "graph { subgraph cluster_b { b } subgraph cluster_a { a:n -- b:s; \"node\" } }"
+            )
+            .unwrap(),
+            "graph {
+  subgraph cluster_a {
+    \"node\";
+    a:n -- b:s;
+  }
+  subgraph cluster_b {
+    b;
+  }
+}
+"
+        );
+    }
+
+    #[test]
+    fn invalid_dot_is_reported() {
+        assert!(canonicalize("digraph { a -> }").is_err());
+        assert!(canonicalize("digraph { \"a }").is_err());
+        assert!(canonicalize("digraph { a -> { b c } }").is_err());
+    }
+
+    #[test]
+    fn snapshot_planner_graph() {
+        snap_dot(
+            "dot_planner",
+            "digraph plan {
+                scan_0x7f1 [label=\"scan users\"];
+                filter_0x7f2 [label=\"filter active\"];
+                project_0x7f3 [label=\"project id, name\"];
+                scan_0x7f1 -> filter_0x7f2;
+                filter_0x7f2 -> project_0x7f3;
+            }",
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Why a snapshot assertion failed.
+
+use std::fmt;
+use std::io;
+
+/// Why a snapshot assertion failed, as returned by `try_snap` and
+/// `try_snap_json`, and as the panic message of every other assertion.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot was missing or mismatched, or was refused, as
+    /// described.
+    Failed(String),
+    /// Reading or writing a snapshot failed.
+    Io {
+        /// What was being done, such as `read` or `write`.
+        operation: &'static str,
+        /// Where the snapshot is kept.
+        path: String,
+        /// Why it failed.
+        source: io::Error,
+    },
+}
+
+impl SnapshotError {
+    pub(crate) fn io(operation: &'static str, path: String, source: io::Error) -> SnapshotError {
+        SnapshotError::Io {
+            operation,
+            path,
+            source,
+        }
+    }
+
+    /// The kind of IO error that caused the failure, if it was one.
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            SnapshotError::Failed(_) => None,
+            SnapshotError::Io { source, .. } => Some(source.kind()),
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Failed(failure) => f.write_str(failure),
+            SnapshotError::Io {
+                operation,
+                path,
+                source,
+            } => write!(
+                f,
+                "unable to {} snapshot at {} ({:?}): {}",
+                operation,
+                path,
+                source.kind(),
+                source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Failed(_) => None,
+            SnapshotError::Io { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<String> for SnapshotError {
+    fn from(failure: String) -> SnapshotError {
+        SnapshotError::Failed(failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotError;
+    use std::io;
+
+    #[test]
+    fn io_errors_describe_what_failed() {
+        let err = SnapshotError::io(
+            "write",
+            String::from("snapshots/search.snap"),
+            io::Error::new(io::ErrorKind::PermissionDenied, "read-only file system"),
+        );
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::PermissionDenied));
+        assert_eq!(
+            err.to_string(),
+            "unable to write snapshot at snapshots/search.snap (PermissionDenied): read-only file system"
+        );
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(
+            SnapshotError::from(String::from("snapshot missing for search")).io_kind(),
+            None
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Structured events describing the outcome of each snapshot assertion.
+//!
+//! With the `tracing` feature every assertion emits a `tracing` event
+//! under the `archetype` target, named `snapshot_created`,
+//! `snapshot_matched`, `snapshot_mismatched`, or `snapshot_updated`, so
+//! that observability tooling can subscribe rather than scrape stdout.
+//! Without the feature these are no-ops.
+
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+
+/// A missing snapshot was written, or would have been in a dry run.
+pub(crate) fn created(key: &str, location: &str, dry_run: bool) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(name: "snapshot_created", target: "archetype", key, location, dry_run);
+}
+
+/// A stored snapshot was overwritten, or would have been in a dry run.
+pub(crate) fn updated(key: &str, location: &str, dry_run: bool) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(name: "snapshot_updated", target: "archetype", key, location, dry_run);
+}
+
+/// A snapshot matched the stored one.
+pub(crate) fn matched(key: &str, location: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(name: "snapshot_matched", target: "archetype", key, location);
+}
+
+/// A snapshot didn't match the stored one. `similarity` gives the ratio
+/// of the line diff between them, if diffing them didn't time out, and is
+/// only called if the event is going to be recorded.
+pub(crate) fn mismatched(
+    key: &str,
+    location: &str,
+    similarity: impl FnOnce() -> Option<f32>,
+    provisional: bool,
+) {
+    #[cfg(feature = "tracing")]
+    if tracing::enabled!(target: "archetype", tracing::Level::WARN) {
+        let similarity = similarity();
+        tracing::warn!(
+            name: "snapshot_mismatched",
+            target: "archetype",
+            key,
+            location,
+            similarity,
+            provisional
+        );
+    }
+}
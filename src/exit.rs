@@ -0,0 +1,22 @@
+//! Running reports when the test binary exits.
+//!
+//! In the browser there is no exit to hook, so nothing is run there.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+}
+
+/// Run `callback` when the process exits normally.
+#[cfg_attr(
+    all(target_arch = "wasm32", target_os = "unknown"),
+    allow(unused_variables)
+)]
+pub(crate) fn at_exit(callback: extern "C" fn()) {
+    // SAFETY: `callback` is a plain function that lives for the life of
+    // the process, which is all `atexit` requires of it.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    unsafe {
+        atexit(callback);
+    }
+}
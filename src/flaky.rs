@@ -0,0 +1,167 @@
+//! Catching nondeterministic subjects before they are recorded.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Generate the subject for the snapshot under `key` `runs` times with
+/// `generate`, failing with the lines that vary if the runs don't agree,
+/// and otherwise snapshot it as `snap` would.
+///
+/// Hash map ordering, timestamps, and the like make for snapshots that
+/// pass locally and then flake in CI. Generating the subject a few times
+/// catches them when the snapshot is written instead.
+///
+/// ```
+/// archetype::snap_deterministic("deterministic-example", 3, || format!("{:?}", [4, 7, 9]));
+/// ```
+pub fn snap_deterministic(key: &str, runs: usize, mut generate: impl FnMut() -> String) {
+    let first = generate();
+    let mut varying: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for _ in 1..runs {
+        let other = generate();
+        let mut lines = first.lines().map(Some).chain(std::iter::repeat(None));
+        let mut others = other.lines().map(Some).chain(std::iter::repeat(None));
+        for i in 0.. {
+            match (lines.next().flatten(), others.next().flatten()) {
+                (None, None) => break,
+                (line, other) if line != other => {
+                    note(&mut varying, i + 1, line.unwrap_or("<missing>"), other)
+                }
+                _ => {}
+            }
+        }
+    }
+    report(key, "line", varying);
+    crate::snap(key, first);
+}
+
+/// Like `snap_deterministic`, but for a subject snapshotted as JSON, as
+/// with `snap_json`. The failure names the JSON pointers that vary.
+///
+/// ```
+/// archetype::snap_json_deterministic("json-deterministic-example", 3, || vec![4, 7, 9]);
+/// ```
+pub fn snap_json_deterministic<A: Serialize>(
+    key: &str,
+    runs: usize,
+    mut generate: impl FnMut() -> A,
+) {
+    let subject = generate();
+    let first = serde_json::to_value(&subject).expect("should serialize");
+    let mut varying = BTreeMap::new();
+    for _ in 1..runs {
+        let other = serde_json::to_value(generate()).expect("should serialize");
+        differences(&first, &other, &mut String::new(), &mut varying);
+    }
+    report(key, "path", varying);
+    crate::snap_json(key, &subject);
+}
+
+/// Note that `other` was seen where `first` was expected at `at`.
+fn note<K: Ord>(varying: &mut BTreeMap<K, Vec<String>>, at: K, first: &str, other: Option<&str>) {
+    let values = varying.entry(at).or_insert_with(|| vec![first.to_string()]);
+    let other = other.unwrap_or("<missing>").to_string();
+    if !values.contains(&other) {
+        values.push(other);
+    }
+}
+
+/// Collect the JSON pointer of every value that differs between `first`
+/// and `other`.
+fn differences(
+    first: &Value,
+    other: &Value,
+    path: &mut String,
+    varying: &mut BTreeMap<String, Vec<String>>,
+) {
+    let mut descend = |token: &str, first: Option<&Value>, other: Option<&Value>| {
+        let len = path.len();
+        path.push('/');
+        path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        match (first, other) {
+            (Some(first), Some(other)) => differences(first, other, path, varying),
+            (first, other) => note(
+                varying,
+                path.clone(),
+                &first.map_or(String::from("<missing>"), Value::to_string),
+                other.map(Value::to_string).as_deref(),
+            ),
+        }
+        path.truncate(len);
+    };
+    match (first, other) {
+        (Value::Object(first), Value::Object(other)) => {
+            for key in first
+                .keys()
+                .chain(other.keys().filter(|k| !first.contains_key(*k)))
+            {
+                descend(key, first.get(key), other.get(key));
+            }
+        }
+        (Value::Array(first), Value::Array(other)) => {
+            for i in 0..first.len().max(other.len()) {
+                descend(&i.to_string(), first.get(i), other.get(i));
+            }
+        }
+        (first, other) if first != other => note(
+            varying,
+            path.clone(),
+            &first.to_string(),
+            Some(&other.to_string()),
+        ),
+        _ => {}
+    }
+}
+
+/// Panic with every `what` that varied between runs, if any did.
+fn report<K: std::fmt::Display>(key: &str, what: &str, varying: BTreeMap<K, Vec<String>>) {
+    if varying.is_empty() {
+        return;
+    }
+    let mut failure = format!("snapshot for {} varies between runs:", key);
+    for (at, values) in varying {
+        failure.push_str(&format!("\n  {} {}: {}", what, at, values.join(" | ")));
+    }
+    panic!("{}", failure);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::cell::Cell;
+
+    #[test]
+    fn snapshot_deterministic() {
+        crate::snap_deterministic("deterministic_report", 3, || {
+            String::from("status: ok\nids: 4, 7, 9\n")
+        });
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "snapshot for flaky_report varies between runs:\n  line 2: took: 0ms | took: 1ms | took: 2ms"
+    )]
+    fn varying_lines_fail() {
+        let run = Cell::new(0);
+        crate::snap_deterministic("flaky_report", 3, || {
+            run.set(run.get() + 1);
+            format!("status: ok\ntook: {}ms\n", run.get() - 1)
+        });
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "snapshot for flaky_json varies between runs:\n  path /meta/took: 0 | 1\n  path /tags/1: \"b\" | <missing>"
+    )]
+    fn varying_paths_fail() {
+        let run = Cell::new(0);
+        crate::snap_json_deterministic("flaky_json", 2, || {
+            run.set(run.get() + 1);
+            match run.get() {
+                1 => json!({ "meta": { "took": 0 }, "tags": ["a", "b"] }),
+                _ => json!({ "meta": { "took": 1 }, "tags": ["a"] }),
+            }
+        });
+    }
+}
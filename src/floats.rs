@@ -0,0 +1,173 @@
+//! Snapshots of arrays of floats, compared with tolerance.
+//!
+//! Numerical code rarely computes the same floats bit for bit across
+//! platforms, compilers, or reordered sums, so each value is stored at
+//! full precision but a new one matches so long as it's within tolerance
+//! of the stored one.
+
+/// How far a float may be from the stored one and still match it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tolerance {
+    /// At most this many representable floats apart, which scales with
+    /// the magnitude of the values compared. `Ulps(0)` only matches the
+    /// same value.
+    Ulps(u64),
+    /// At most this far apart.
+    Absolute(f64),
+    /// At most this far apart, as a fraction of the larger magnitude.
+    Relative(f64),
+}
+
+impl Tolerance {
+    /// How far apart `stored` and `subject` are, as a multiple of the
+    /// tolerance, so anything over `1.0` is out of tolerance, along with
+    /// the distance as it's reported.
+    pub(crate) fn score(self, stored: f64, subject: f64) -> (f64, String) {
+        if stored == subject || (stored.is_nan() && subject.is_nan()) {
+            return (0.0, String::new());
+        }
+        if stored.is_nan() || subject.is_nan() {
+            return (f64::INFINITY, String::from("one is NaN"));
+        }
+        let apart = (stored - subject).abs();
+        match self {
+            Tolerance::Ulps(ulps) => {
+                let distance = ordered(stored).abs_diff(ordered(subject));
+                let score = distance as f64 / ulps as f64;
+                (score, format!("{} ulps apart, beyond {}", distance, ulps))
+            }
+            Tolerance::Absolute(tolerance) => {
+                let score = apart / tolerance;
+                (score, format!("{:e} apart, beyond {:e}", apart, tolerance))
+            }
+            Tolerance::Relative(tolerance) => {
+                let relative = apart / stored.abs().max(subject.abs());
+                let score = relative / tolerance;
+                (
+                    score,
+                    format!("{:e} apart relatively, beyond {:e}", relative, tolerance),
+                )
+            }
+        }
+    }
+}
+
+/// `value` as an integer that orders floats as they're ordered, so that
+/// consecutive floats are consecutive integers.
+fn ordered(value: f64) -> i64 {
+    let bits = value.to_bits() as i64;
+    match bits < 0 {
+        true => i64::MIN - bits,
+        false => bits,
+    }
+}
+
+/// Take a snapshot of `values` under a file with the name `key`, a value
+/// per line, at full precision.
+///
+/// The snapshot matches if every value is within `tolerance` of the
+/// stored one, and otherwise fails as `snap` would, naming the index of
+/// the value furthest out of tolerance. `NaN`s match each other.
+///
+/// ```
+/// use archetype::Tolerance;
+///
+/// let roots: Vec<f64> = (1..=4).map(|n| f64::from(n).sqrt()).collect();
+/// archetype::snap_f64s("floats-example", &roots, Tolerance::Ulps(4));
+/// ```
+pub fn snap_f64s(key: &str, values: &[f64], tolerance: Tolerance) {
+    let subject = render(values);
+    let Some(stored) = crate::stored(key) else {
+        return crate::snap(key, subject);
+    };
+    match outside(&stored, values, tolerance) {
+        // Asserting what's stored counts the snapshot as matched, and
+        // leaves it as it is.
+        None => crate::snap(key, stored),
+        Some(worst) => {
+            if let Err(failure) = crate::check(key, subject, false) {
+                panic!("{}\n{}", failure, worst);
+            }
+        }
+    }
+}
+
+fn render(values: &[f64]) -> String {
+    // Debug prints the shortest digits that read back as the same float.
+    values
+        .iter()
+        .map(|value| format!("{:?}\n", value))
+        .collect()
+}
+
+/// How the value of `values` furthest out of `tolerance` of `stored`
+/// differs, unless every value is within tolerance.
+fn outside(stored: &str, values: &[f64], tolerance: Tolerance) -> Option<String> {
+    let lines: Vec<&str> = stored.lines().collect();
+    if lines.len() != values.len() {
+        return Some(format!(
+            "{} values were stored, but there are {}",
+            lines.len(),
+            values.len()
+        ));
+    }
+    let mut worst: Option<(f64, String)> = None;
+    for (index, (line, &value)) in lines.into_iter().zip(values).enumerate() {
+        let Ok(old) = line.trim().parse::<f64>() else {
+            return Some(format!("unable to read stored value {:?}", line));
+        };
+        let (score, difference) = tolerance.score(old, value);
+        if score > 1.0 && worst.as_ref().is_none_or(|(worst, _)| score > *worst) {
+            let at = format!(
+                "furthest out of tolerance at index {}: {:?} -> {:?}, {}",
+                index, old, value, difference
+            );
+            worst = Some((score, at));
+        }
+    }
+    worst.map(|(_, at)| at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outside, render, snap_f64s, Tolerance};
+
+    #[test]
+    fn values_are_stored_at_full_precision() {
+        let values = [0.1 + 0.2, -0.0, 1e-300, f64::NAN, f64::INFINITY];
+        assert_eq!(
+            render(&values),
+            "0.30000000000000004\n-0.0\n1e-300\nNaN\ninf\n"
+        );
+        snap_f64s("floats", &values, Tolerance::Ulps(0));
+    }
+
+    #[test]
+    fn values_match_within_tolerance() {
+        let stored = render(&[1.0, 100.0, 0.0, f64::NAN]);
+        let nudged = f64::from_bits(1.0f64.to_bits() + 3);
+        let values = [nudged, 100.0, -0.0, f64::NAN];
+        assert_eq!(outside(&stored, &values, Tolerance::Ulps(4)), None);
+        assert_eq!(
+            outside(&stored, &values, Tolerance::Ulps(2)).as_deref(),
+            Some("furthest out of tolerance at index 0: 1.0 -> 1.0000000000000007, 3 ulps apart, beyond 2")
+        );
+
+        let values = [1.05, 100.5, 0.0, f64::NAN];
+        assert_eq!(outside(&stored, &values, Tolerance::Relative(0.05)), None);
+        assert_eq!(
+            outside(&stored, &values, Tolerance::Absolute(0.1)).as_deref(),
+            Some("furthest out of tolerance at index 1: 100.0 -> 100.5, 5e-1 apart, beyond 1e-1")
+        );
+
+        let values = [1.0, 100.0, 0.0, 0.0];
+        assert_eq!(
+            outside(&stored, &values, Tolerance::Absolute(1.0)).as_deref(),
+            Some("furthest out of tolerance at index 3: NaN -> 0.0, one is NaN")
+        );
+        assert_eq!(
+            outside(&stored, &[1.0], Tolerance::Ulps(4)).as_deref(),
+            Some("4 values were stored, but there are 1")
+        );
+    }
+}
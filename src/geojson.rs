@@ -0,0 +1,263 @@
+//! Snapshots of GeoJSON, normalized for geometry pipeline golden tests.
+//!
+//! Geometry libraries disagree in the last few decimals of what they
+//! compute and in which way round they wind polygons, neither of which
+//! changes the shape described, so coordinates are rounded and rings
+//! rewound before the document is stored.
+
+use serde::Serialize;
+use serde_json::{Number, Value};
+
+/// A snapshot of a GeoJSON document, normalized before it is stored:
+///
+/// * each coordinate, and each number of a `bbox`, is rounded to a fixed
+///   number of decimal places, and written as an integer if it's whole,
+/// * polygons are wound by the right-hand rule, with their exterior rings
+///   counterclockwise and their holes clockwise, as RFC 7946 recommends,
+/// * all objects, including each feature's `properties`, are sorted by
+///   key.
+///
+/// ```
+/// archetype::GeoJson::new(
+///     "geojson-example",
+///     &serde_json::json!({
+///         "type": "Feature",
+///         "properties": { "name": "depot", "id": 7 },
+///         "geometry": { "type": "Point", "coordinates": [151.209295999999995, -33.8688197] },
+///     }),
+/// )
+/// .precision(5)
+/// .snap();
+/// ```
+#[must_use = "the snapshot is only asserted by `snap`"]
+#[derive(Clone, Debug)]
+pub struct GeoJson {
+    key: String,
+    document: Value,
+    precision: i32,
+}
+
+impl GeoJson {
+    /// Start a snapshot under `key` of `document`, which may be a
+    /// `serde_json::Value` or any typed model that serializes to one.
+    ///
+    /// Panics if `document` fails to serialize.
+    pub fn new<A: Serialize>(key: impl Into<String>, document: &A) -> GeoJson {
+        GeoJson {
+            key: key.into(),
+            document: serde_json::to_value(document).expect("should serialize"),
+            precision: 7,
+        }
+    }
+
+    /// Round coordinates to `decimals` places. Defaults to 7, roughly a
+    /// centimetre in longitude and latitude.
+    pub fn precision(mut self, decimals: i32) -> GeoJson {
+        self.precision = decimals;
+        self
+    }
+
+    /// Assert the normalized document against the snapshot under the
+    /// key, as `snap_json` would.
+    pub fn snap(self) {
+        crate::snap_json(&self.key, &self.normalize());
+    }
+
+    fn normalize(&self) -> Value {
+        let mut document = self.document.clone();
+        self.object(&mut document);
+        document.sort_all_objects();
+        document
+    }
+
+    /// Normalize `value` if it's a GeoJSON object, by its `type`.
+    fn object(&self, value: &mut Value) {
+        let Value::Object(object) = value else {
+            return;
+        };
+        if let Some(bbox) = object.get_mut("bbox") {
+            self.positions(bbox, 1);
+        }
+        let kind = object.get("type").and_then(Value::as_str).unwrap_or("");
+        let (field, depth) = match kind {
+            "FeatureCollection" => ("features", None),
+            "Feature" => ("geometry", None),
+            "GeometryCollection" => ("geometries", None),
+            "Point" => ("coordinates", Some(0)),
+            "MultiPoint" | "LineString" => ("coordinates", Some(1)),
+            "MultiLineString" | "Polygon" => ("coordinates", Some(2)),
+            "MultiPolygon" => ("coordinates", Some(3)),
+            _ => return,
+        };
+        let polygon = matches!(kind, "Polygon" | "MultiPolygon");
+        let Some(inner) = object.get_mut(field) else {
+            return;
+        };
+        match (depth, inner) {
+            (None, Value::Array(members)) => members.iter_mut().for_each(|m| self.object(m)),
+            (None, member) => self.object(member),
+            (Some(depth), coordinates) => {
+                self.positions(coordinates, depth);
+                if polygon {
+                    wind(coordinates, depth);
+                }
+            }
+        }
+    }
+
+    /// Round the numbers of the positions `depth` arrays deep in
+    /// `coordinates`, or of `coordinates` itself at a depth of 0.
+    fn positions(&self, coordinates: &mut Value, depth: usize) {
+        let Value::Array(items) = coordinates else {
+            return;
+        };
+        for item in items {
+            match depth {
+                0 | 1 if item.is_number() => *item = self.round(item),
+                0 => {}
+                _ => self.positions(item, depth - 1),
+            }
+        }
+    }
+
+    /// `number` rounded to the precision, written as an integer if it's
+    /// whole, since producers differ over writing `10` or `10.0`.
+    fn round(&self, number: &Value) -> Value {
+        let Some(x) = number.as_f64().filter(|_| number.is_f64()) else {
+            return number.clone();
+        };
+        let scale = 10f64.powi(self.precision);
+        let rounded = (x * scale).round() / scale;
+        if rounded.fract() == 0.0 && rounded.abs() < 2f64.powi(53) {
+            return Value::from(rounded as i64);
+        }
+        Number::from_f64(rounded).map_or_else(|| number.clone(), Value::Number)
+    }
+}
+
+/// Wind the rings of the polygons `depth` arrays deep in `coordinates`,
+/// where a depth of 2 is a polygon's, by the right-hand rule.
+fn wind(coordinates: &mut Value, depth: usize) {
+    let Value::Array(items) = coordinates else {
+        return;
+    };
+    if depth > 2 {
+        return items.iter_mut().for_each(|item| wind(item, depth - 1));
+    }
+    for (i, ring) in items.iter_mut().enumerate() {
+        let Value::Array(positions) = ring else {
+            continue;
+        };
+        let area = area(positions);
+        // The exterior ring comes first, and the holes after it.
+        let reversed = match i {
+            0 => area < 0.0,
+            _ => area > 0.0,
+        };
+        if reversed {
+            positions.reverse();
+        }
+    }
+}
+
+/// Twice the signed area of `ring`, positive if it's counterclockwise.
+fn area(ring: &[Value]) -> f64 {
+    let point = |position: &Value| {
+        let x = position.get(0).and_then(Value::as_f64).unwrap_or(0.0);
+        let y = position.get(1).and_then(Value::as_f64).unwrap_or(0.0);
+        (x, y)
+    };
+    ring.windows(2)
+        .map(|pair| {
+            let ((x1, y1), (x2, y2)) = (point(&pair[0]), point(&pair[1]));
+            x1 * y2 - x2 * y1
+        })
+        .sum()
+}
+
+/// Take a snapshot of the GeoJSON `document` under a file with the name
+/// `key`, normalized as `GeoJson` would with its default precision.
+///
+/// ```
+/// archetype::snap_geojson(
+///     "geojson-line-example",
+///     &serde_json::json!({
+///         "type": "LineString",
+///         "coordinates": [[0.30000000000000004, 1], [0.7, 1.0000000000000002]],
+///     }),
+/// );
+/// ```
+pub fn snap_geojson<A: Serialize>(key: &str, document: &A) {
+    GeoJson::new(key, document).snap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GeoJson;
+    use serde_json::json;
+
+    fn parcels() -> serde_json::Value {
+        json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "zone": "R2", "area": 42.5 },
+                    "bbox": [0.0, 0.0, 10.000000000000002, 10.0],
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [
+                            // A clockwise exterior, around a counterclockwise
+                            // hole.
+                            [[0, 0], [0, 10], [10.000000000000002, 10], [10, 0], [0, 0]],
+                            [[2, 2], [4, 2], [4, 4], [2, 4], [2, 2]],
+                        ],
+                    },
+                },
+                {
+                    "type": "Feature",
+                    "properties": null,
+                    "geometry": {
+                        "type": "GeometryCollection",
+                        "geometries": [
+                            { "type": "Point", "coordinates": [-0.00000000001, 51.4779] },
+                            {
+                                "type": "MultiPolygon",
+                                "coordinates": [[[[0, 0], [1, 0], [1, 1], [0, 0]]]],
+                            },
+                        ],
+                    },
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn geojson_is_rounded_and_rewound() {
+        let document = GeoJson::new("geojson", &parcels()).normalize();
+        let parcel = &document["features"][0];
+        assert_eq!(parcel["bbox"], json!([0, 0, 10, 10]));
+        assert_eq!(
+            parcel["geometry"]["coordinates"],
+            json!([
+                [[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                [[2, 2], [2, 4], [4, 4], [4, 2], [2, 2]],
+            ])
+        );
+        let geometries = &document["features"][1]["geometry"]["geometries"];
+        assert_eq!(geometries[0]["coordinates"], json!([0, 51.4779]));
+        assert_eq!(
+            geometries[1]["coordinates"],
+            json!([[[[0, 0], [1, 0], [1, 1], [0, 0]]]])
+        );
+        let keys: Vec<&String> = parcel["properties"].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["area", "zone"]);
+    }
+
+    #[test]
+    fn snapshot_parcels() {
+        GeoJson::new("geojson_parcels", &parcels())
+            .precision(3)
+            .snap();
+    }
+}
@@ -0,0 +1,69 @@
+//! GitHub Actions workflow commands, which annotate pull requests.
+
+/// Whether we're running in GitHub Actions.
+pub(crate) fn detected() -> bool {
+    std::env::var("GITHUB_ACTIONS").as_deref() == Ok("true")
+}
+
+/// An `::error` workflow command annotating `line` of `file`, if given,
+/// with `message`.
+pub(crate) fn error(file: Option<&str>, line: Option<usize>, message: &str) -> String {
+    let mut properties = Vec::new();
+    if let Some(file) = file {
+        properties.push(format!("file={}", escape_property(&relative(file))));
+    }
+    if let Some(line) = line {
+        properties.push(format!("line={}", line));
+    }
+    let properties = if properties.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", properties.join(","))
+    };
+    format!("::error{}::{}", properties, escape_data(message))
+}
+
+/// `file` relative to the checkout, as annotations require.
+fn relative(file: &str) -> String {
+    let workspace = std::env::var("GITHUB_WORKSPACE").unwrap_or_default();
+    match file
+        .strip_prefix(&workspace)
+        .filter(|_| !workspace.is_empty())
+    {
+        Some(file) => file.trim_start_matches(['/', '\\']).to_string(),
+        None => file.to_string(),
+    }
+}
+
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error;
+
+    #[test]
+    fn error_escapes_workflow_command() {
+        assert_eq!(
+            error(
+                Some("snapshots/a,b.snap"),
+                Some(3),
+                "snapshot mismatch: 100%\nexpected"
+            ),
+            "::error file=snapshots/a%2Cb.snap,line=3::snapshot mismatch: 100%25%0Aexpected"
+        );
+        assert_eq!(
+            error(None, None, "snapshot missing for search"),
+            "::error::snapshot missing for search"
+        );
+    }
+}
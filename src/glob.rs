@@ -0,0 +1,56 @@
+//! Minimal glob matching for snapshot keys and JSON paths.
+//!
+//! `*` matches any run of characters, including none, and `?` matches
+//! exactly one character. Everything else matches itself.
+
+/// Whether `text` matches the glob `pattern` in its entirety.
+pub(crate) fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` seen, and where in `text` it started
+    // matching, so we can backtrack and let it consume one more character.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches("search_by_term", "search_by_term"));
+        assert!(!matches("search_by_term", "search_by_ids"));
+        assert!(matches("search_*", "search_by_term"));
+        assert!(matches("*", ""));
+        assert!(matches("api/*/v?", "api/users/v2"));
+        assert!(!matches("api/*/v?", "api/users/v10"));
+        assert!(matches("*a*b", "xaxxab"));
+        assert!(!matches("*a*b", "xaxxa"));
+    }
+}
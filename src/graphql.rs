@@ -0,0 +1,329 @@
+//! Snapshots of GraphQL schemas (SDL) and GraphQL JSON responses.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Placeholder written in place of error locations.
+const LOCATIONS: &str = "[locations]";
+
+/// Keywords that begin a top-level definition in SDL.
+const DEFINITIONS: &[&str] = &[
+    "schema",
+    "scalar",
+    "type",
+    "interface",
+    "union",
+    "enum",
+    "input",
+    "directive",
+    "extend",
+];
+
+/// Take a snapshot of GraphQL SDL under a file with the name `key`.
+///
+/// Schema printers are free to emit types and fields in whatever order
+/// they were registered, which makes for noisy diffs when a resolver is
+/// moved around. The SDL is canonicalized before it is stored: top-level
+/// definitions are sorted by name, the fields (or enum values) within
+/// each definition are sorted by name, and indentation is normalized.
+/// Descriptions and comments travel with the item that follows them.
+///
+/// This is a light canonicalizer over the layout schema printers emit,
+/// not a full GraphQL parser.
+///
+/// ```
+/// archetype::snap_graphql_sdl(
+///     "graphql-sdl-example",
+///     "type Query {\n  search(term: String!): [Result!]!\n  node(id: ID!): Node\n}\n",
+/// );
+/// ```
+pub fn snap_graphql_sdl(key: &str, sdl: &str) {
+    crate::snap(key, canonicalize(sdl));
+}
+
+/// Take a snapshot of a GraphQL JSON response under a file with the
+/// name `key`.
+///
+/// The `locations` of every entry in `errors` point into the query
+/// document and move whenever the query is reformatted, so they are
+/// replaced with `[locations]`. The error messages and paths are kept.
+///
+/// ```
+/// archetype::snap_graphql_response(
+///     "graphql-response-example",
+///     &serde_json::json!({
+///         "data": { "node": null },
+///         "errors": [{
+///             "message": "not found",
+///             "locations": [{ "line": 2, "column": 3 }],
+///             "path": ["node"],
+///         }],
+///     }),
+/// );
+/// ```
+pub fn snap_graphql_response<A: Serialize>(key: &str, response: &A) {
+    let mut response = serde_json::to_value(response).expect("should serialize");
+    if let Some(Value::Array(errors)) = response.get_mut("errors") {
+        for error in errors {
+            if let Some(locations) = error.get_mut("locations") {
+                *locations = Value::String(LOCATIONS.to_string());
+            }
+        }
+    }
+    crate::snap_json(key, &response);
+}
+
+/// A definition or member along with the descriptions and comments that
+/// precede it.
+struct Item {
+    leading: Vec<String>,
+    lines: Vec<String>,
+    members: Vec<Item>,
+    close: Option<String>,
+}
+
+impl Item {
+    fn new(leading: &mut Vec<String>) -> Item {
+        Item {
+            leading: std::mem::take(leading),
+            lines: Vec::new(),
+            members: Vec::new(),
+            close: None,
+        }
+    }
+
+    /// The name this item is ordered by: the identifier following the
+    /// definition keyword, or the leading identifier of a member.
+    fn name(&self) -> String {
+        let head = self.lines.first().map(String::as_str).unwrap_or_default();
+        let mut words = head
+            .split(|c: char| c.is_whitespace() || "(:{=".contains(c))
+            .filter(|word| !word.is_empty());
+        let first = words.next().unwrap_or_default();
+        if first == "extend" {
+            words.nth(1).unwrap_or_default().to_string()
+        } else if DEFINITIONS.contains(&first) && first != "schema" {
+            words.next().unwrap_or_default().to_string()
+        } else {
+            first.to_string()
+        }
+    }
+
+    fn render(&self, indent: &str, out: &mut String) {
+        for line in &self.leading {
+            out.push_str(indent);
+            out.push_str(line);
+            out.push('\n');
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            out.push_str(indent);
+            if i > 0 && !line.starts_with(')') {
+                out.push_str("  ");
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        for member in &self.members {
+            member.render("  ", out);
+        }
+        if let Some(close) = &self.close {
+            out.push_str(close);
+            out.push('\n');
+        }
+    }
+}
+
+fn is_description(line: &str) -> bool {
+    line.starts_with('"') || line.starts_with('#')
+}
+
+fn is_definition(line: &str) -> bool {
+    let first = line.split_whitespace().next().unwrap_or_default();
+    DEFINITIONS.contains(&first)
+}
+
+fn depth_change(line: &str, open: char, close: char) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in line.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => break,
+            c if c == open && !in_string => depth += 1,
+            c if c == close && !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn canonicalize(sdl: &str) -> String {
+    let mut definitions: Vec<Item> = Vec::new();
+    let mut leading: Vec<String> = Vec::new();
+    let mut in_block_string = false;
+    let mut braces = 0;
+    let mut parens = 0;
+    let mut member_open = false;
+
+    for line in sdl.lines().map(str::trim) {
+        if in_block_string || line.starts_with("\"\"\"") {
+            let toggles = line.matches("\"\"\"").count();
+            if toggles % 2 == 1 {
+                in_block_string = !in_block_string;
+            }
+            leading.push(line.to_string());
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if is_description(line) {
+            leading.push(line.to_string());
+            continue;
+        }
+        if braces == 0 {
+            match definitions.last_mut() {
+                Some(definition) if !is_definition(line) && leading.is_empty() => {
+                    // A continuation of a bodiless definition such as a
+                    // union whose members are split over several lines.
+                    definition.lines.push(line.to_string());
+                }
+                _ => {
+                    let mut definition = Item::new(&mut leading);
+                    definition.lines.push(line.to_string());
+                    definitions.push(definition);
+                }
+            }
+            braces += depth_change(line, '{', '}');
+            continue;
+        }
+        let definition = definitions.last_mut().expect("an open definition");
+        let change = depth_change(line, '{', '}');
+        if braces + change == 0 && line.starts_with('}') {
+            definition.close = Some(line.to_string());
+            definition.members.sort_by_key(Item::name);
+            braces = 0;
+            member_open = false;
+            continue;
+        }
+        braces += change;
+        if member_open {
+            let member = definition.members.last_mut().expect("an open member");
+            member.lines.push(line.to_string());
+        } else {
+            let mut member = Item::new(&mut leading);
+            member.lines.push(line.to_string());
+            definition.members.push(member);
+        }
+        parens += depth_change(line, '(', ')');
+        member_open = parens > 0;
+    }
+
+    definitions.sort_by_key(Item::name);
+    let mut out = String::new();
+    for (i, definition) in definitions.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        definition.render("", &mut out);
+    }
+    for line in leading {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_definition;
+
+    const SDL: &str = r#"
+type Query {
+    search(term: String!, limit: Int = 10): [Result!]!
+    "Look up any node by its global ID."
+    node(
+      id: ID!
+    ): Node
+}
+
+"""
+Anything with a global ID.
+"""
+interface Node {
+  id: ID!
+}
+
+union Result = User
+  | Post
+
+enum Role {
+  VIEWER
+  ADMIN
+}
+
+scalar DateTime
+
+type User implements Node {
+  name: String!
+  id: ID!
+  role: Role!
+}
+
+type Post implements Node {
+  title: String
+  id: ID!
+  publishedAt: DateTime
+}
+"#;
+
+    #[test]
+    fn canonicalize_orders_definitions_and_fields() {
+        let canonical = super::canonicalize(SDL);
+        let names: Vec<&str> = canonical
+            .lines()
+            .filter(|line| is_definition(line))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "scalar DateTime",
+                "interface Node {",
+                "type Post implements Node {",
+                "type Query {",
+                "union Result = User",
+                "enum Role {",
+                "type User implements Node {",
+            ]
+        );
+    }
+
+    #[test]
+    fn canonicalize_is_stable_under_reordering() {
+        let reordered =
+            "type User implements Node {\n  role: Role!\n  id: ID!\n  name: String!\n}\n";
+        assert_eq!(
+            super::canonicalize(reordered),
+            "type User implements Node {\n  id: ID!\n  name: String!\n  role: Role!\n}\n"
+        );
+    }
+
+    #[test]
+    fn snapshot_graphql_sdl() {
+        crate::snap_graphql_sdl("graphql_sdl", SDL);
+    }
+
+    #[test]
+    fn snapshot_graphql_response() {
+        crate::snap_graphql_response(
+            "graphql_response",
+            &serde_json::json!({
+                "data": null,
+                "errors": [{
+                    "message": "Cannot query field \"nmae\" on type \"User\".",
+                    "locations": [{ "line": 3, "column": 5 }],
+                }],
+            }),
+        );
+    }
+}
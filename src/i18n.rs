@@ -0,0 +1,363 @@
+//! Snapshots of localized message catalogs, in Fluent and gettext
+//! formats.
+//!
+//! Each catalog is stored a message per line, as `key = value`, sorted by
+//! key so that reordering a catalog changes nothing and deleting a key
+//! shows up as a lost line. Messages with plural forms are expanded into
+//! a line per form, so a form dropped by a translation pipeline is caught
+//! too. Newlines within a message are stored as `\n`.
+
+use std::collections::BTreeMap;
+
+/// A message's place in a catalog: its ID, then its attribute and plural
+/// form, if any, as they're ordered.
+type Key = (String, Option<String>, Option<String>);
+
+/// A Fluent message or attribute: its ID, its attribute, if it's one, and
+/// the lines of its value.
+type Entry<'a> = (String, Option<String>, Vec<&'a str>);
+
+/// Take a snapshot of a Fluent (`.ftl`) catalog under a file with the
+/// name `key`.
+///
+/// Messages and terms are stored by ID, with their attributes following
+/// them as `id.attribute`. A message made of a select expression over
+/// its variants, such as its plural forms, is expanded into a line per
+/// variant, as `id[variant]`, with the default variant marked as `*`.
+/// Comments are dropped.
+///
+/// This is a light canonicalizer over the layout Fluent catalogs are
+/// written in, where each variant starts its own line, not a full Fluent
+/// parser.
+///
+/// ```
+/// archetype::snap_fluent(
+///     "fluent-example",
+///     "# Shown in the inbox.\n\
+///      emails = { $count ->\n\
+///      \x20   [one] You have one email.\n\
+///      \x20  *[other] You have { $count } emails.\n\
+///      }\n\
+///      hello = Hello, { $name }!\n\
+///      \x20   .title = Greeting\n",
+/// );
+/// ```
+pub fn snap_fluent(key: &str, catalog: &str) {
+    crate::snap(key, render(fluent(catalog)));
+}
+
+/// Take a snapshot of a gettext (`.po`) catalog under a file with the
+/// name `key`.
+///
+/// Messages are stored by their `msgid`, prefixed by their `msgctxt` as
+/// `context|msgid` if they have one, and each plural form of a message
+/// with `msgid_plural` is stored on a line of its own, as `msgid[n]`.
+/// Untranslated messages are kept, with nothing after the `=`. The
+/// header, along with comments and obsolete messages, is dropped, since
+/// it mostly records when and by what the catalog was generated.
+///
+/// ```
+/// archetype::snap_gettext(
+///     "gettext-example",
+///     "msgid \"\"\n\
+///      msgstr \"POT-Creation-Date: 2024-03-01 09:30+0000\\n\"\n\
+///      \n\
+///      #: src/inbox.rs:12\n\
+///      msgid \"You have one email.\"\n\
+///      msgid_plural \"You have %d emails.\"\n\
+///      msgstr[0] \"Vous avez un e-mail.\"\n\
+///      msgstr[1] \"Vous avez %d e-mails.\"\n\
+///      \n\
+///      msgctxt \"menu\"\n\
+///      msgid \"Open\"\n\
+///      msgstr \"Ouvrir\"\n",
+/// );
+/// ```
+pub fn snap_gettext(key: &str, catalog: &str) {
+    crate::snap(key, render(gettext(catalog)));
+}
+
+fn render(messages: BTreeMap<Key, String>) -> String {
+    let mut out = String::new();
+    for ((id, attribute, variant), value) in messages {
+        out.push_str(&id);
+        if let Some(attribute) = attribute {
+            out.push('.');
+            out.push_str(&attribute);
+        }
+        if let Some(variant) = variant {
+            out.push_str(&format!("[{}]", variant));
+        }
+        out.push_str(" =");
+        if !value.is_empty() {
+            out.push(' ');
+            out.push_str(&value.replace('\n', "\\n"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The messages in a Fluent catalog, with any select expressions
+/// expanded.
+fn fluent(catalog: &str) -> BTreeMap<Key, String> {
+    let mut values: Vec<Entry> = Vec::new();
+    for line in catalog.lines() {
+        // The brace closing a select expression may be left unindented.
+        let continued = line.starts_with([' ', '\t', '}']);
+        let trimmed = line.trim();
+        if !continued {
+            match trimmed.split_once('=') {
+                Some((id, value)) if !trimmed.starts_with('#') => {
+                    values.push((id.trim().to_string(), None, vec![value.trim()]));
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let Some((id, _, lines)) = values.last_mut() else {
+            continue;
+        };
+        match trimmed
+            .strip_prefix('.')
+            .and_then(|rest| rest.split_once('='))
+        {
+            Some((attribute, value)) => {
+                let entry = (
+                    id.clone(),
+                    Some(attribute.trim().to_string()),
+                    vec![value.trim()],
+                );
+                values.push(entry);
+            }
+            None => lines.push(trimmed),
+        }
+    }
+
+    let mut messages = BTreeMap::new();
+    for (id, attribute, lines) in values {
+        // Lines of a multiline value join with a newline, except where a
+        // value begins on the line after its `=`.
+        let lines = match lines.first() {
+            Some(&"") => &lines[1..],
+            _ => &lines[..],
+        };
+        match variants(lines) {
+            Some(variants) => {
+                for (variant, value) in variants {
+                    messages.insert((id.clone(), attribute.clone(), Some(variant)), value);
+                }
+            }
+            None => {
+                messages.insert((id, attribute, None), lines.join("\n"));
+            }
+        }
+    }
+    messages
+}
+
+/// Each variant of the select expression that `lines` hold, with the
+/// text around the expression, if they hold one, such as:
+///
+/// ```text
+/// { $count ->
+///     [one] one email
+///    *[other] { $count } emails
+/// } today
+/// ```
+fn variants(lines: &[&str]) -> Option<Vec<(String, String)>> {
+    let first = lines.first()?;
+    let open = first.find('{')?;
+    let (prefix, selector) = (&first[..open], first[open + 1..].trim());
+    if !selector.ends_with("->") {
+        return None;
+    }
+    let mut variants: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut suffix = None;
+    for line in &lines[1..] {
+        if let Some(rest) = line.strip_prefix('}') {
+            suffix = Some(rest);
+            break;
+        }
+        let (default, rest) = match line.strip_prefix('*') {
+            Some(rest) => (true, rest),
+            None => (false, *line),
+        };
+        let variant = rest.strip_prefix('[').and_then(|rest| rest.split_once(']'));
+        match (variant, variants.last_mut()) {
+            (Some((name, text)), _) => {
+                let name = match default {
+                    true => format!("*{}", name.trim()),
+                    false => name.trim().to_string(),
+                };
+                variants.push((name, vec![text.trim()]));
+            }
+            (None, Some((_, text))) => text.push(line),
+            (None, None) => return None,
+        }
+    }
+    let suffix = suffix?;
+    Some(
+        variants
+            .into_iter()
+            .map(|(name, text)| (name, format!("{}{}{}", prefix, text.join("\n"), suffix)))
+            .collect(),
+    )
+}
+
+/// The messages in a gettext catalog, but for its header.
+fn gettext(catalog: &str) -> BTreeMap<Key, String> {
+    let mut messages = BTreeMap::new();
+    let mut entry: Vec<(String, String)> = Vec::new();
+    let lines = catalog.lines().chain([""]);
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            add(&mut messages, std::mem::take(&mut entry));
+        } else if line.starts_with('#') {
+            continue;
+        } else if let Some(continued) = line.strip_prefix('"') {
+            if let Some((_, text)) = entry.last_mut() {
+                text.push_str(&unquote(continued));
+            }
+        } else if let Some((keyword, quoted)) = line.split_once(' ') {
+            // A new `msgctxt` or `msgid` after a `msgstr` starts a new
+            // entry, even without a blank line between them.
+            let starts = keyword == "msgctxt" || keyword == "msgid";
+            if starts && entry.iter().any(|(seen, _)| seen.starts_with("msgstr")) {
+                add(&mut messages, std::mem::take(&mut entry));
+            }
+            let quoted = quoted.trim();
+            let text = quoted.strip_prefix('"').map(unquote).unwrap_or_default();
+            entry.push((keyword.to_string(), text));
+        }
+    }
+    messages
+}
+
+/// Add the message held by the keywords of `entry` to `messages`, unless
+/// it's the header.
+fn add(messages: &mut BTreeMap<Key, String>, entry: Vec<(String, String)>) {
+    let field = |keyword: &str| {
+        entry
+            .iter()
+            .find(|(seen, _)| seen == keyword)
+            .map(|(_, text)| text.clone())
+    };
+    let Some(msgid) = field("msgid").filter(|msgid| !msgid.is_empty()) else {
+        return;
+    };
+    let id = match field("msgctxt") {
+        Some(context) => format!("{}|{}", context, msgid),
+        None => msgid,
+    };
+    if field("msgid_plural").is_none() {
+        messages.insert((id, None, None), field("msgstr").unwrap_or_default());
+        return;
+    }
+    for (keyword, text) in &entry {
+        if let Some(form) = keyword
+            .strip_prefix("msgstr[")
+            .and_then(|form| form.strip_suffix(']'))
+        {
+            messages.insert((id.clone(), None, Some(form.to_string())), text.clone());
+        }
+    }
+}
+
+/// The text of a quoted gettext string, given what follows its opening
+/// quote, with its escapes resolved.
+fn unquote(quoted: &str) -> String {
+    let quoted = quoted.trim_end().strip_suffix('"').unwrap_or(quoted);
+    let mut text = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('r') => text.push('\r'),
+            Some(escaped) => text.push(escaped),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fluent, gettext, render};
+
+    #[test]
+    fn fluent_is_sorted_and_expanded() {
+        let catalog = "\
+## Inbox
+
+-brand = Archetype
+hello = Hello, { $name }!
+    .title = Greeting
+    .aria-label =
+        Greet { $name }
+        warmly
+emails = You have { $count ->
+    [zero] no emails
+    [one] one email
+   *[other] { $count } emails
+} waiting.
+hello-world = Hello, world!
+";
+        assert_eq!(
+            render(fluent(catalog)),
+            "-brand = Archetype\n\
+             emails[*other] = You have { $count } emails waiting.\n\
+             emails[one] = You have one email waiting.\n\
+             emails[zero] = You have no emails waiting.\n\
+             hello = Hello, { $name }!\n\
+             hello.aria-label = Greet { $name }\\nwarmly\n\
+             hello.title = Greeting\n\
+             hello-world = Hello, world!\n"
+        );
+    }
+
+    #[test]
+    fn gettext_is_sorted_and_expanded() {
+        let catalog = r#"
+msgid ""
+msgstr ""
+"Project-Id-Version: inbox 1.0\n"
+"POT-Creation-Date: 2024-03-01 09:30+0000\n"
+
+#: src/inbox.rs:12
+#, c-format
+msgid "You have one email."
+msgid_plural "You have %d emails."
+msgstr[0] "Vous avez un e-mail."
+msgstr[1] "Vous avez %d e-mails."
+msgctxt "menu"
+msgid "Open"
+msgstr "Ouvrir"
+
+msgid "Open"
+msgstr ""
+"Ouvrir le "
+"\"fichier\""
+
+msgid "Close"
+msgstr ""
+
+#~ msgid "Obsolete"
+#~ msgstr "Obsolète"
+"#;
+        assert_eq!(
+            render(gettext(catalog)),
+            "Close =\n\
+             Open = Ouvrir le \"fichier\"\n\
+             You have one email.[0] = Vous avez un e-mail.\n\
+             You have one email.[1] = Vous avez %d e-mails.\n\
+             menu|Open = Ouvrir\n"
+        );
+    }
+}
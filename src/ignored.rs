@@ -0,0 +1,137 @@
+//! Reporting the fields of stored snapshots that a type ignores.
+
+use crate::store::Storage;
+use serde::de::DeserializeOwned;
+use serde_ignored::Path;
+use std::io;
+
+/// Deserialize every snapshot stored under a key starting with `prefix`
+/// as `T`, and take a snapshot under a file with the name `key` of the
+/// fields that `T` ignored in each.
+///
+/// Serde skips fields a type doesn't know about, so a consumer whose
+/// types have fallen behind the recorded wire format keeps deserializing
+/// without a word. The report lists each ignored field by its JSON
+/// pointer, under the key of the snapshot it is in, so a field the type
+/// starts ignoring shows up as a mismatch. Snapshots that ignore nothing
+/// are left out, and those that don't deserialize at all are reported as
+/// such, as `assert_corpus` would fail them.
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Search {
+///     term: String,
+/// }
+///
+/// archetype::snap_json("ignored-example/v2", &serde_json::json!({ "term": "by-term", "limit": 10 }));
+/// archetype::snap_ignored_fields::<Search>("ignored-example-report", "ignored-example/");
+/// ```
+pub fn snap_ignored_fields<T: DeserializeOwned>(key: &str, prefix: &str) {
+    let storage = crate::config::global().snapshot_storage();
+    let report = report::<T>(&*storage, prefix, key).unwrap_or_else(|err| {
+        panic!(
+            "unable to read snapshots under {} ({:?}): {}",
+            prefix,
+            err.kind(),
+            err
+        )
+    });
+    crate::snap(key, report);
+}
+
+/// Report the fields `T` ignores in each snapshot in `storage` under a key
+/// starting with `prefix`, other than the report itself under `key`.
+/// Links are skipped, as what they link to is reported in its own right.
+fn report<T: DeserializeOwned>(
+    storage: &dyn Storage,
+    prefix: &str,
+    key: &str,
+) -> io::Result<String> {
+    let mut out = String::new();
+    for entry in crate::store::list_in(storage)? {
+        if !entry.key.starts_with(prefix) || entry.key == key || entry.link.is_some() {
+            continue;
+        }
+        let Some(contents) = storage.read(&entry.key)? else {
+            continue;
+        };
+        let mut ignored = Vec::new();
+        let body = crate::meta::split(&contents).1;
+        let mut deserializer = serde_json::Deserializer::from_str(body);
+        let lines = match serde_ignored::deserialize::<_, _, T>(&mut deserializer, |path| {
+            ignored.push(pointer(&path))
+        }) {
+            Ok(_) => ignored,
+            Err(err) => vec![format!("(doesn't deserialize: {})", err)],
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        out.push_str(&entry.key);
+        out.push('\n');
+        for line in lines {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("no fields ignored\n");
+    }
+    Ok(out)
+}
+
+/// The JSON pointer to the value at `path`.
+fn pointer(path: &Path<'_>) -> String {
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}/{}", pointer(parent), index),
+        Path::Map { parent, key } => format!(
+            "{}/{}",
+            pointer(parent),
+            key.replace('~', "~0").replace('/', "~1")
+        ),
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => pointer(parent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::report;
+    use crate::store::BundledStorage;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Item {
+        id: u32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Order {
+        items: Vec<Item>,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn reports_ignored_fields_by_pointer() {
+        let storage = BundledStorage::new()
+            .with("orders/v1", "{ \"items\": [{ \"id\": 4 }] }\n")
+            .with(
+                "orders/v2",
+                "{ \"items\": [{ \"id\": 4, \"sku/code\": \"a1\" }], \"total\": 3 }\n",
+            )
+            .with("orders/v3", "{ \"lines\": [] }\n")
+            .with("orders/report", "orders/v2\n  /total\n");
+        assert_eq!(
+            report::<Order>(&storage, "orders/", "orders/report").unwrap(),
+            "orders/v2\n  /items/0/sku~1code\n  /total\norders/v3\n  \
+             (doesn't deserialize: missing field `items` at line 1 column 15)\n"
+        );
+        assert_eq!(
+            report::<Order>(&storage, "orders/v1", "orders/report").unwrap(),
+            "no fields ignored\n"
+        );
+    }
+}
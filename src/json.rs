@@ -0,0 +1,773 @@
+//! Helpers for snapshotting JSON.
+
+use crate::Settings;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::Value;
+
+/// Take a snapshot of only the part of `subject` at the JSON `pointer`,
+/// under a file with the name `key`.
+///
+/// Large responses churn in places a test doesn't care about. Pinning
+/// just the subtree under test keeps the snapshot focused. The pointer
+/// follows RFC 6901, e.g. `/data/items/0`, and the empty pointer refers
+/// to the whole document.
+///
+/// Panics if nothing exists at `pointer`.
+///
+/// ```
+/// let response = serde_json::json!({
+///     "meta": { "request-id": "8f14e45f" },
+///     "data": { "items": [1, 2, 3] },
+/// });
+/// archetype::snap_json_at("json-at-example", &response, "/data/items");
+/// ```
+pub fn snap_json_at<A: Serialize>(key: &str, subject: &A, pointer: &str) {
+    let value = serde_json::to_value(subject).expect("should serialize");
+    let subtree = value
+        .pointer(pointer)
+        .unwrap_or_else(|| panic!("nothing at {:?} to snapshot for {}", pointer, key));
+    crate::snap_json(key, subtree);
+}
+
+/// Take a snapshot of a JSON `value` under a file with the name `key`.
+///
+/// This suits ad-hoc structures that aren't worth defining a type for,
+/// typically built with `serde_json::json!`, or more simply with
+/// `snap_json!`.
+///
+/// ```
+/// archetype::snap_value("value-example", serde_json::json!({ "ids": [4, 7, 9] }));
+/// ```
+pub fn snap_value(key: &str, value: Value) {
+    crate::snap_json(key, &value);
+}
+
+/// Take a snapshot of JSON written with the literal syntax of
+/// `serde_json::json!` under a file with the name `key`.
+///
+/// ```
+/// archetype::snap_json!("json-macro-example", {
+///     "type": "by-ids",
+///     "ids": [4, 7, 9],
+/// });
+/// ```
+#[macro_export]
+macro_rules! snap_json {
+    ($key:expr, $($json:tt)+) => {
+        $crate::snap_value($key, $crate::serde_json::json!($($json)+))
+    };
+}
+
+/// Build the key for a case of a table-driven test from its `base` name
+/// and its `params`, as `base/params`.
+///
+/// The params are serialized and written compactly: the fields of a
+/// struct or map as `name=value`, the items of a tuple or list, or a lone
+/// value, separated by commas, with strings unquoted. Anything that
+/// isn't safe in a file name on every platform is replaced with `_`.
+///
+/// ```
+/// let params = serde_json::json!({ "rate": 10, "tier": "gold" });
+/// assert_eq!(archetype::keyed("discount", &params), "discount/rate=10,tier=gold");
+/// assert_eq!(archetype::keyed("discount", &(10, "gold/2")), "discount/10,gold_2");
+/// ```
+pub fn keyed<P: Serialize>(base: &str, params: &P) -> String {
+    let params = serde_json::to_value(params)
+        .unwrap_or_else(|err| panic!("should serialize the params for {}: {}", base, err));
+    let part = |value: &Value| match value {
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    };
+    let params = match &params {
+        Value::Object(map) => map
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, part(value)))
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Array(items) => items.iter().map(part).collect::<Vec<_>>().join(","),
+        value => part(value),
+    };
+    let mut params: String = params
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '=' | ',' => c,
+            _ => '_',
+        })
+        .collect();
+    // A leading dot would hide the file, or climb out of the directory.
+    if params.starts_with('.') {
+        params.replace_range(..1, "_");
+    }
+    format!("{}/{}", base, params)
+}
+
+/// Take a snapshot of JSON under a key built from a `base` name and a
+/// bundle of `params` with `keyed`, for table-driven tests.
+///
+/// ```
+/// for (rate, total) in [(0, 100), (10, 90)] {
+///     let params = serde_json::json!({ "rate": rate });
+///     archetype::snap_json_keyed!("keyed-example", params, &total);
+/// }
+/// ```
+#[macro_export]
+macro_rules! snap_json_keyed {
+    ($base:expr, $params:expr, $subject:expr) => {
+        $crate::snap_json(&$crate::keyed($base, &$params), $subject)
+    };
+}
+
+/// Pretty print `subject` with the indentation, array width, and
+/// elision from `settings`. With the defaults this is exactly
+/// `to_string_pretty`.
+pub(crate) fn pretty<A: Serialize>(subject: &A, settings: &Settings) -> String {
+    if settings.json_max_depth.is_none() && settings.json_max_items.is_none() {
+        return print(subject, settings);
+    }
+    let mut value = serde_json::to_value(subject).expect("should serialize");
+    elide(&mut value, 0, settings);
+    print(&value, settings)
+}
+
+/// Replace what lies deeper than the maximum depth in `settings` with a
+/// marker of how much was there, as `"... object with 3 keys"` or
+/// `"... array of 3 items"`, and drop the items of arrays beyond the
+/// maximum length in favor of one marker, as `"... 950 more items"`.
+/// `value` is `depth` levels below the root.
+fn elide(value: &mut Value, depth: usize, settings: &Settings) {
+    let beyond = settings.json_max_depth.is_some_and(|max| depth > max);
+    match value {
+        Value::Array(items) if beyond && !items.is_empty() => {
+            let len = items.len();
+            *value = Value::String(format!("... array of {} {}", len, plural(len, "item")));
+        }
+        Value::Object(fields) if beyond && !fields.is_empty() => {
+            let len = fields.len();
+            *value = Value::String(format!("... object with {} {}", len, plural(len, "key")));
+        }
+        Value::Array(items) => {
+            if let Some(max) = settings.json_max_items.filter(|max| items.len() > *max) {
+                let more = items.len() - max;
+                items.truncate(max);
+                let marker = format!("... {} more {}", more, plural(more, "item"));
+                items.push(Value::String(marker));
+            }
+            for item in items {
+                elide(item, depth + 1, settings);
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                elide(field, depth + 1, settings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `noun` as there are `n` of it, as `item` or `items`.
+fn plural(n: usize, noun: &str) -> String {
+    match n {
+        1 => noun.to_string(),
+        _ => format!("{}s", noun),
+    }
+}
+
+/// Pretty print `subject` with the indentation and array width from
+/// `settings`.
+fn print<A: Serialize>(subject: &A, settings: &Settings) -> String {
+    let indent = " ".repeat(settings.json_indent);
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(
+        &mut out,
+        PrettyFormatter::with_indent(indent.as_bytes()),
+    );
+    subject
+        .serialize(&mut serializer)
+        .expect("should serialize");
+    let out = String::from_utf8(out).expect("serde_json produces UTF-8");
+    match settings.json_array_width {
+        0 => out,
+        width => collapse_arrays(&out, width),
+    }
+}
+
+/// Rewrite arrays of scalars in pretty printed JSON onto a single line
+/// wherever that line fits within `width` columns.
+fn collapse_arrays(pretty: &str, width: usize) -> String {
+    let lines: Vec<&str> = pretty.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.ends_with('[') {
+            // Lines ending in `[` or `{` open a nested value. Brackets within
+            // strings never confuse this, as a string line ends in a quote.
+            let elements: Vec<&str> = lines[i + 1..]
+                .iter()
+                .take_while(|element| {
+                    let element = element.trim_start();
+                    !element.starts_with(']') && !element.ends_with('[') && !element.ends_with('{')
+                })
+                .map(|element| element.trim())
+                .collect();
+            let close = lines
+                .get(i + 1 + elements.len())
+                .map(|close| close.trim_start());
+            if let Some(close) = close.filter(|close| close.starts_with(']')) {
+                let collapsed = format!("{}{}{}", line, elements.join(" "), close);
+                if collapsed.chars().count() <= width {
+                    out.push(collapsed);
+                    i += elements.len() + 2;
+                    continue;
+                }
+            }
+        }
+        out.push(line.to_string());
+        i += 1;
+    }
+    out.join("\n")
+}
+
+/// The key of an inline matcher in a stored JSON snapshot, such as
+/// `{"$matches": "uuid"}`.
+const MATCHES: &str = "$matches";
+
+/// Whether `stored` and `subject` are equal JSON documents once the
+/// values at the `ignore` paths are disregarded and the arrays at the
+/// `unordered` paths are compared as sets, and with the inline matchers
+/// in `stored` matching the values in their place. Always false when there
+/// are no such paths or matchers, or either side isn't JSON.
+///
+/// Each path is a JSON pointer whose tokens may be globs, so `/items/*/id`
+/// refers to the `id` of every item.
+///
+/// A matcher is an object whose only field is `$matches`, naming what
+/// the value must be: `uuid`, `iso8601`, `number`, `string`, `bool`,
+/// `present` for anything at all, or `regex:...` for a string the regular
+/// expression matches in full. Matchers are written into the stored
+/// snapshot by hand, for values such as IDs that must merely be present
+/// and well-formed.
+pub(crate) fn equivalent(
+    stored: &str,
+    subject: &str,
+    ignore: &[String],
+    unordered: &[String],
+) -> bool {
+    if ignore.is_empty() && unordered.is_empty() && !stored.contains(MATCHES) {
+        return false;
+    }
+    let (Ok(mut stored), Ok(mut subject)) = (
+        serde_json::from_str::<Value>(stored),
+        serde_json::from_str::<Value>(subject),
+    ) else {
+        return false;
+    };
+    let ignore: Vec<Vec<String>> = ignore.iter().map(|path| tokens(path)).collect();
+    let unordered: Vec<Vec<String>> = unordered.iter().map(|path| tokens(path)).collect();
+    for value in [&mut stored, &mut subject] {
+        remove(value, &mut Vec::new(), &ignore);
+        sort(value, &mut Vec::new(), &unordered);
+    }
+    conforms(&stored, &subject)
+}
+
+/// Whether `actual` equals `expected`, but for the inline matchers in
+/// `expected`, which need only match the values in their place.
+fn conforms(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Object(expected), actual)
+            if expected.len() == 1 && expected.contains_key(MATCHES) =>
+        {
+            match expected[MATCHES].as_str() {
+                Some(matcher) => satisfies(matcher, actual),
+                None => false,
+            }
+        }
+        (Value::Object(expected), Value::Object(actual)) => {
+            expected.len() == actual.len()
+                && expected.iter().all(|(key, expected)| {
+                    actual
+                        .get(key)
+                        .is_some_and(|actual| conforms(expected, actual))
+                })
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(expected, actual)| conforms(expected, actual))
+        }
+        (expected, actual) => expected == actual,
+    }
+}
+
+/// Whether `value` is what the inline `matcher` names. Unknown matchers
+/// match nothing.
+fn satisfies(matcher: &str, value: &Value) -> bool {
+    let full = |pattern: &str, value: &Value| {
+        let Some(string) = value.as_str() else {
+            return false;
+        };
+        regex::Regex::new(&format!(r"\A(?:{})\z", pattern))
+            .is_ok_and(|regex| regex.is_match(string))
+    };
+    match matcher {
+        "present" => true,
+        "uuid" => full(crate::template::UUID, value),
+        "iso8601" => full(crate::template::ISO8601, value),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "bool" => value.is_boolean(),
+        matcher => matcher
+            .strip_prefix("regex:")
+            .is_some_and(|pattern| full(pattern, value)),
+    }
+}
+
+/// The most differences listed by `summary`.
+const SUMMARIZED: usize = 20;
+
+/// The widest a value is printed in a `summary` before it is cut short.
+const SUMMARY_VALUE_WIDTH: usize = 40;
+
+/// Summarize how `subject` differs from `stored` as the JSON pointer of
+/// each value that differs, one per line, such as `/items/3/price: 10 ->
+/// 12`. A value only on one side is `(missing)` on the other. The paths
+/// in `settings` are ignored or compared as sets, as they are when
+/// comparing. `None` unless both sides are JSON.
+pub(crate) fn summary(stored: &str, subject: &str, settings: &Settings) -> Option<String> {
+    let mut stored = serde_json::from_str::<Value>(stored).ok()?;
+    let mut subject = serde_json::from_str::<Value>(subject).ok()?;
+    let ignore: Vec<Vec<String>> = settings
+        .ignore_paths
+        .iter()
+        .map(|path| tokens(path))
+        .collect();
+    let unordered: Vec<Vec<String>> = settings
+        .unordered_paths
+        .iter()
+        .map(|path| tokens(path))
+        .collect();
+    for value in [&mut stored, &mut subject] {
+        remove(value, &mut Vec::new(), &ignore);
+        sort(value, &mut Vec::new(), &unordered);
+    }
+    let mut differences = Vec::new();
+    differ(
+        Some(&stored),
+        Some(&subject),
+        &mut String::new(),
+        &mut differences,
+    );
+    let ellipsis = settings.frame.ellipsis;
+    let show = |value: Option<&Value>| match value {
+        None => String::from("(missing)"),
+        Some(value) => {
+            let value = value.to_string();
+            match value.char_indices().nth(SUMMARY_VALUE_WIDTH) {
+                Some((end, _)) => format!("{}{}", &value[..end], ellipsis),
+                None => value,
+            }
+        }
+    };
+    let mut out = String::new();
+    for (pointer, old, new) in differences.iter().take(SUMMARIZED) {
+        let pointer = if pointer.is_empty() {
+            "(root)"
+        } else {
+            pointer
+        };
+        out.push_str(&format!("{}: {} -> {}\n", pointer, show(*old), show(*new)));
+    }
+    if differences.len() > SUMMARIZED {
+        out.push_str(&format!("and {} more\n", differences.len() - SUMMARIZED));
+    }
+    Some(out)
+}
+
+/// A value that differs: its pointer and the value on each side, if any.
+type Difference<'a> = (String, Option<&'a Value>, Option<&'a Value>);
+
+/// Collect every difference between `old` and `new` at `pointer`, the
+/// deepest place each is found. Keys only in `new` come after the rest.
+fn differ<'a>(
+    old: Option<&'a Value>,
+    new: Option<&'a Value>,
+    pointer: &mut String,
+    out: &mut Vec<Difference<'a>>,
+) {
+    let mut within = |token: &str, old, new, out: &mut Vec<Difference<'a>>| {
+        let len = pointer.len();
+        pointer.push('/');
+        pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        differ(old, new, pointer, out);
+        pointer.truncate(len);
+    };
+    match (old, new) {
+        (Some(old), Some(new)) if old == new => {}
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            for (key, value) in old {
+                within(key, Some(value), new.get(key), out);
+            }
+            for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                within(key, None, Some(value), out);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                within(&i.to_string(), old.get(i), new.get(i), out);
+            }
+        }
+        (old, new) => out.push((pointer.clone(), old, new)),
+    }
+}
+
+/// Split a JSON pointer into its unescaped tokens.
+fn tokens(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    pattern.len() == path.len()
+        && pattern
+            .iter()
+            .zip(path)
+            .all(|(pattern, token)| crate::glob::matches(pattern, token))
+}
+
+/// Whether the value at `token` within `path` matches one of `patterns`.
+fn matched(path: &mut Vec<String>, token: &str, patterns: &[Vec<String>]) -> bool {
+    path.push(token.to_string());
+    let matched = patterns.iter().any(|pattern| matches(pattern, path));
+    path.pop();
+    matched
+}
+
+/// Remove every value in `value` whose path matches one of `patterns`.
+/// Array elements are replaced with `null` so that later indices don't
+/// shift.
+fn remove(value: &mut Value, path: &mut Vec<String>, patterns: &[Vec<String>]) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| !matched(path, key, patterns));
+            for (key, inner) in map.iter_mut() {
+                path.push(key.clone());
+                remove(inner, path, patterns);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (i, inner) in items.iter_mut().enumerate() {
+                if matched(path, &i.to_string(), patterns) {
+                    *inner = Value::Null;
+                } else {
+                    path.push(i.to_string());
+                    remove(inner, path, patterns);
+                    path.pop();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace every value in `value` whose path matches one of the glob
+/// pointers in `patterns`, as `equivalent` takes them, with `replacement`.
+pub(crate) fn replace(value: &mut Value, patterns: &[String], replacement: &str) {
+    let patterns: Vec<Vec<String>> = patterns.iter().map(|path| tokens(path)).collect();
+    replace_at(value, &mut Vec::new(), &patterns, replacement);
+}
+
+fn replace_at(
+    value: &mut Value,
+    path: &mut Vec<String>,
+    patterns: &[Vec<String>],
+    replacement: &str,
+) {
+    let mut visit = |token: String, inner: &mut Value| {
+        if matched(path, &token, patterns) {
+            *inner = Value::String(replacement.to_string());
+        } else {
+            path.push(token);
+            replace_at(inner, path, patterns, replacement);
+            path.pop();
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, inner) in map.iter_mut() {
+                visit(key.clone(), inner);
+            }
+        }
+        Value::Array(items) => {
+            for (i, inner) in items.iter_mut().enumerate() {
+                visit(i.to_string(), inner);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sort every array in `value` whose path matches one of `patterns`, so
+/// that arrays holding the same elements in any order compare equal.
+/// Nested values are sorted first so that equal elements sort alike.
+fn sort(value: &mut Value, path: &mut Vec<String>, patterns: &[Vec<String>]) {
+    match value {
+        Value::Object(map) => {
+            for (key, inner) in map.iter_mut() {
+                path.push(key.clone());
+                sort(inner, path, patterns);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (i, inner) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                sort(inner, path, patterns);
+                path.pop();
+            }
+            if patterns.iter().any(|pattern| matches(pattern, path)) {
+                items.sort_by_cached_key(|item| {
+                    let mut item = item.clone();
+                    item.sort_all_objects();
+                    item.to_string()
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Settings;
+    use serde_json::json;
+
+    #[test]
+    fn huge_values_are_elided() {
+        let settings = Settings::new()
+            .json_max_depth(2)
+            .json_max_items(3)
+            .json_array_width(80);
+        let subject = json!({
+            "results": (0..1000).collect::<Vec<_>>(),
+            "page": { "cursor": { "next": { "offset": 1000 } }, "size": 1000 },
+            "empty": { "nested": {} },
+        });
+        assert_eq!(
+            super::pretty(&subject, &settings),
+            r#"{
+  "empty": {
+    "nested": {}
+  },
+  "page": {
+    "cursor": {
+      "next": "... object with 1 key"
+    },
+    "size": 1000
+  },
+  "results": [0, 1, 2, "... 997 more items"]
+}"#
+        );
+    }
+
+    #[test]
+    fn inline_matchers() {
+        let stored = json!({
+            "id": { "$matches": "uuid" },
+            "created": { "$matches": "iso8601" },
+            "sku": { "$matches": "regex:[A-Z]{3}-\\d+" },
+            "items": [{ "id": { "$matches": "number" }, "name": "one" }],
+        })
+        .to_string();
+        let subject = json!({
+            "id": "8f14e45f-ceea-467a-9575-0d7a8a8e1b2c",
+            "created": "2024-03-01T09:30:00Z",
+            "sku": "ABC-12",
+            "items": [{ "id": 4, "name": "one" }],
+        });
+        assert!(super::equivalent(&stored, &subject.to_string(), &[], &[]));
+        let mut malformed = subject.clone();
+        malformed["id"] = json!("8f14e45f");
+        assert!(!super::equivalent(
+            &stored,
+            &malformed.to_string(),
+            &[],
+            &[]
+        ));
+        let mut missing = subject.clone();
+        missing.as_object_mut().unwrap().remove("sku");
+        assert!(!super::equivalent(&stored, &missing.to_string(), &[], &[]));
+        let mut renamed = subject;
+        renamed["items"][0]["name"] = json!("two");
+        assert!(!super::equivalent(&stored, &renamed.to_string(), &[], &[]));
+    }
+
+    #[test]
+    fn keys_are_safe_file_names() {
+        assert_eq!(super::keyed("search", &"by term?"), "search/by_term_");
+        assert_eq!(super::keyed("search", &".."), "search/_.");
+        assert_eq!(
+            super::keyed("search", &json!({ "ids": [4, 7] })),
+            "search/ids=_4,7_"
+        );
+    }
+
+    #[test]
+    fn summary_lists_differing_pointers() {
+        let stored = json!({
+            "items": [{ "sku": "a/1", "price": 10 }, { "sku": "b2", "price": 4 }],
+            "total": 14,
+            "took": 3,
+        })
+        .to_string();
+        let subject = json!({
+            "items": [
+                { "sku": "a/1", "price": 12 },
+                { "sku": "b2", "price": 4 },
+                { "sku": "c3", "price": 1 },
+            ],
+            "total": 17,
+            "took": 5,
+            "currency": "NZD",
+        })
+        .to_string();
+        let settings = Settings::new().ignore_path("/took");
+        assert_eq!(
+            super::summary(&stored, &subject, &settings).unwrap(),
+            "/items/0/price: 10 -> 12\n\
+             /items/2: (missing) -> {\"price\":1,\"sku\":\"c3\"}\n\
+             /total: 14 -> 17\n\
+             /currency: (missing) -> \"NZD\"\n"
+        );
+        assert_eq!(
+            super::summary("1", "2", &settings).unwrap(),
+            "(root): 1 -> 2\n"
+        );
+        assert_eq!(super::summary("not json", "{}", &settings), None);
+    }
+
+    #[test]
+    fn equivalent_ignoring_paths() {
+        let stored = json!({
+            "meta": { "request-id": "8f14e45f", "took": 12 },
+            "items": [{ "id": "a1", "name": "one" }, { "id": "b2", "name": "two" }],
+        })
+        .to_string();
+        let subject = json!({
+            "meta": { "request-id": "c9f0f895", "took": 12 },
+            "items": [{ "id": "c3", "name": "one" }, { "id": "d4", "name": "two" }],
+        })
+        .to_string();
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert!(!super::equivalent(&stored, &subject, &[], &[]));
+        assert!(!super::equivalent(
+            &stored,
+            &subject,
+            &paths(&["/meta/request-id"]),
+            &[]
+        ));
+        assert!(super::equivalent(
+            &stored,
+            &subject,
+            &paths(&["/meta/request-id", "/items/*/id"]),
+            &[]
+        ));
+        assert!(!super::equivalent(
+            "not json",
+            "not json",
+            &paths(&["/a"]),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn equivalent_unordered_paths() {
+        let stored = json!({
+            "tags": ["b", "a"],
+            "groups": [{ "members": [2, 1], "name": "x" }, { "members": [3], "name": "y" }],
+        })
+        .to_string();
+        let subject = json!({
+            "tags": ["a", "b"],
+            "groups": [{ "members": [3], "name": "y" }, { "name": "x", "members": [1, 2] }],
+        })
+        .to_string();
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert!(!super::equivalent(
+            &stored,
+            &subject,
+            &[],
+            &paths(&["/tags"])
+        ));
+        assert!(super::equivalent(
+            &stored,
+            &subject,
+            &[],
+            &paths(&["/tags", "/groups", "/groups/*/members"])
+        ));
+    }
+
+    #[test]
+    fn pretty_defaults_match_serde_json() {
+        let value = json!({ "a": [1, 2], "b": { "c": [] }, "d": [{}] });
+        assert_eq!(
+            super::pretty(&value, &crate::Settings::new()),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn pretty_indents_and_collapses_arrays() {
+        let value = json!({
+            "ids": [4, 7, 9],
+            "names": ["a really long name", "another really long name"],
+            "nested": [[1], { "a": 1 }],
+        });
+        let settings = crate::Settings::new().json_indent(4).json_array_width(40);
+        assert_eq!(
+            super::pretty(&value, &settings),
+            r#"{
+    "ids": [4, 7, 9],
+    "names": [
+        "a really long name",
+        "another really long name"
+    ],
+    "nested": [
+        [1],
+        {
+            "a": 1
+        }
+    ]
+}"#
+        );
+    }
+
+    #[test]
+    fn snapshot_json_macro() {
+        let ids = vec![4, 7, 9];
+        crate::snap_json!("json_macro", { "type": "by-ids", "ids": ids });
+    }
+
+    #[test]
+    fn snapshot_json_at() {
+        crate::snap_json_at(
+            "json_at_items",
+            &json!({ "data": { "items": [{ "id": 1 }, { "id": 2 }], "cursor": "abc" } }),
+            "/data/items",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nothing at \"/data/missing\" to snapshot for json_at_missing")]
+    fn snap_json_at_missing_pointer() {
+        crate::snap_json_at("json_at_missing", &json!({ "data": {} }), "/data/missing");
+    }
+}
@@ -0,0 +1,148 @@
+//! Warnings about JSON snapshots that are unlikely to be deterministic.
+
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Something volatile found in a snapshot, along with how to redact it.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Warning {
+    path: String,
+    value: String,
+    kind: &'static str,
+    pattern: &'static str,
+    replacement: &'static str,
+}
+
+impl Warning {
+    /// Describe the warning for the snapshot under `key`.
+    pub(crate) fn render(&self, key: &str) -> String {
+        format!(
+            "warning: {} at {} is {} ({}), which is unlikely to be deterministic; \
+             consider GlobalConfig::filter({:?}, {:?}) or Settings::ignore_path({:?})",
+            key, self.path, self.kind, self.value, self.pattern, self.replacement, self.path
+        )
+    }
+}
+
+/// Each kind of volatile value detected, with the pattern that detects it
+/// and a suggested replacement.
+const VOLATILE: &[(&str, &str, &str)] = &[
+    (
+        "a timestamp",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?",
+        "[timestamp]",
+    ),
+    (
+        "a UUID",
+        r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+        "[uuid]",
+    ),
+    (
+        "an ephemeral port",
+        r"(?:localhost|127\.0\.0\.1|0\.0\.0\.0|\[::1\]):(?:3[2-9]\d{3}|[4-6]\d{4})\b",
+        "[host]:[port]",
+    ),
+    (
+        "a temporary directory",
+        r"(?:/private)?/var/folders/[^\s\x22]*|/tmp/[^\s\x22]*|(?i:\\AppData\\Local\\Temp\\[^\s\x22]*)",
+        "[temp]",
+    ),
+];
+
+fn patterns() -> &'static [(&'static str, Regex, &'static str, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(&str, Regex, &str, &str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        VOLATILE
+            .iter()
+            .map(|(kind, pattern, replacement)| {
+                let regex = Regex::new(pattern).expect("valid volatile pattern");
+                (*kind, regex, *pattern, *replacement)
+            })
+            .collect()
+    })
+}
+
+/// Every string in the JSON `subject` that looks volatile. Anything that
+/// isn't JSON is left alone.
+pub(crate) fn lint(subject: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if let Ok(value) = serde_json::from_str::<Value>(subject) {
+        walk(&value, &mut String::new(), &mut warnings);
+    }
+    warnings
+}
+
+fn walk(value: &Value, path: &mut String, warnings: &mut Vec<Warning>) {
+    let mut descend = |token: &str, value: &Value| {
+        let len = path.len();
+        path.push('/');
+        path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        walk(value, path, warnings);
+        path.truncate(len);
+    };
+    match value {
+        Value::Object(map) => map.iter().for_each(|(key, value)| descend(key, value)),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .for_each(|(i, value)| descend(&i.to_string(), value)),
+        Value::String(text) => {
+            let found = patterns()
+                .iter()
+                .find(|(_, regex, _, _)| regex.is_match(text));
+            if let Some((kind, _, pattern, replacement)) = found {
+                warnings.push(Warning {
+                    path: path.clone(),
+                    value: text.clone(),
+                    kind,
+                    pattern,
+                    replacement,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint;
+    use serde_json::json;
+
+    #[test]
+    fn lint_finds_volatile_values() {
+        let subject = json!({
+            "id": "6f1c2a9e-3b4d-4e5f-8a7b-9c0d1e2f3a4b",
+            "created": "2023-10-21T08:30:00Z",
+            "server": "http://127.0.0.1:54321/search",
+            "scratch": ["/tmp/.tmpA1b2C3/out.json"],
+            "term": "an example search term",
+            "port": "localhost:8080",
+        })
+        .to_string();
+        let warnings = lint(&subject);
+        let found: Vec<(&str, &str)> = warnings
+            .iter()
+            .map(|warning| (warning.path.as_str(), warning.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("/created", "a timestamp"),
+                ("/id", "a UUID"),
+                ("/scratch/0", "a temporary directory"),
+                ("/server", "an ephemeral port"),
+            ]
+        );
+        assert_eq!(
+            warnings[0].render("search"),
+            r#"warning: search at /created is a timestamp (2023-10-21T08:30:00Z), which is unlikely to be deterministic; consider GlobalConfig::filter("\\d{4}-\\d{2}-\\d{2}[T ]\\d{2}:\\d{2}(?::\\d{2}(?:\\.\\d+)?)?(?:Z|[+-]\\d{2}:?\\d{2})?", "[timestamp]") or Settings::ignore_path("/created")"#
+        );
+    }
+
+    #[test]
+    fn lint_ignores_text() {
+        assert!(lint("created 2023-10-21T08:30:00Z").is_empty());
+    }
+}
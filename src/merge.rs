@@ -0,0 +1,457 @@
+//! Structural three-way merging of JSON snapshots.
+//!
+//! When a branch is rebased, both the stored snapshot and the code that
+//! produces it may have changed. Rather than resolving the golden file
+//! by hand, the two sides can be merged against their common ancestor:
+//! changes that touch different parts of the document are combined, and
+//! only changes to the same value on both sides are reported as
+//! conflicts.
+//!
+//! ```
+//! let base = r#"{ "name": "archetype", "version": 1 }"#;
+//! let theirs = r#"{ "name": "archetype", "version": 2 }"#;
+//! let mine = r#"{ "name": "golden", "version": 1 }"#;
+//!
+//! let merged = archetype::merge::snapshots(base, theirs, mine).unwrap();
+//! assert_eq!(merged, "{\n  \"name\": \"golden\",\n  \"version\": 2\n}");
+//! ```
+
+use crate::meta::{self, Meta};
+use crate::Settings;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A value that was changed differently on both sides of a merge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    /// The JSON pointer to the conflicting value.
+    pub path: String,
+    /// The value in the common ancestor, or `None` if it was absent.
+    pub base: Option<Value>,
+    /// The value on their side, or `None` if it was removed.
+    pub theirs: Option<Value>,
+    /// The value on our side, or `None` if it was removed.
+    pub mine: Option<Value>,
+}
+
+/// Why a merge could not be completed.
+#[derive(Debug)]
+pub enum MergeError {
+    /// One of the inputs was not valid JSON.
+    Parse(&'static str, serde_json::Error),
+    /// The two sides made overlapping changes.
+    Conflicts(Vec<Conflict>),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::Parse(side, err) => write!(f, "{} is not valid JSON: {}", side, err),
+            MergeError::Conflicts(conflicts) => {
+                write!(f, "{} conflicting change", conflicts.len())?;
+                if conflicts.len() != 1 {
+                    write!(f, "s")?;
+                }
+                for conflict in conflicts {
+                    write!(
+                        f,
+                        "\n  {}: base {}, theirs {}, mine {}",
+                        if conflict.path.is_empty() {
+                            "/"
+                        } else {
+                            &conflict.path
+                        },
+                        show(&conflict.base),
+                        show(&conflict.theirs),
+                        show(&conflict.mine),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+fn show(value: &Option<Value>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => String::from("(absent)"),
+    }
+}
+
+/// Merge the text of three JSON snapshots, returning the merged snapshot
+/// pretty printed the same way `snap_json` writes it, with the settings
+/// of the current thread.
+///
+/// Keys are written in the order `mine` has them, followed by any only
+/// `theirs` or `base` has, so the merged snapshot still matches the
+/// output it was taken from.
+pub fn snapshots(base: &str, theirs: &str, mine: &str) -> Result<String, MergeError> {
+    let parse = |side, text: &str| {
+        let value =
+            serde_json::from_str::<Value>(text).map_err(|err| MergeError::Parse(side, err))?;
+        let order =
+            serde_json::from_str::<Order>(text).map_err(|err| MergeError::Parse(side, err))?;
+        Ok((value, order))
+    };
+    let (base, base_order) = parse("base", base)?;
+    let (theirs, their_order) = parse("theirs", theirs)?;
+    let (mine, my_order) = parse("mine", mine)?;
+    let merged = json(&base, &theirs, &mine)?;
+    let ordered = Ordered {
+        value: &merged,
+        orders: vec![&my_order, &their_order, &base_order],
+    };
+    Ok(crate::json::pretty(&ordered, &Settings::current()))
+}
+
+/// The order the keys of each object in a JSON document were written in,
+/// which `Value` doesn't keep, as serde_json's `preserve_order` isn't
+/// enabled.
+#[derive(Debug, Default)]
+struct Order {
+    keys: Vec<(String, Order)>,
+    items: Vec<Order>,
+}
+
+impl Order {
+    fn key(&self, key: &str) -> Option<&Order> {
+        self.keys
+            .iter()
+            .find(|(other, _)| other == key)
+            .map(|(_, order)| order)
+    }
+}
+
+impl<'de> Deserialize<'de> for Order {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Order, D::Error> {
+        deserializer.deserialize_any(OrderVisitor)
+    }
+}
+
+struct OrderVisitor;
+
+impl<'de> Visitor<'de> for OrderVisitor {
+    type Value = Order;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_unit<E>(self) -> Result<Order, E> {
+        Ok(Order::default())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Order, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Order {
+            keys: Vec::new(),
+            items,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Order, A::Error> {
+        let mut keys = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            keys.push(entry);
+        }
+        Ok(Order {
+            keys,
+            items: Vec::new(),
+        })
+    }
+}
+
+/// `value` serialized with the keys of its objects in the first of
+/// `orders` that has them, and otherwise sorted.
+struct Ordered<'a> {
+    value: &'a Value,
+    orders: Vec<&'a Order>,
+}
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Object(fields) => {
+                let mut keys: Vec<&String> = Vec::with_capacity(fields.len());
+                let written = self.orders.iter().flat_map(|order| &order.keys);
+                for key in written.map(|(key, _)| key).chain(fields.keys()) {
+                    if fields.contains_key(key) && !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+                let mut map = serializer.serialize_map(Some(keys.len()))?;
+                for key in keys {
+                    let value = Ordered {
+                        value: &fields[key],
+                        orders: self
+                            .orders
+                            .iter()
+                            .filter_map(|order| order.key(key))
+                            .collect(),
+                    };
+                    map.serialize_entry(key, &value)?;
+                }
+                map.end()
+            }
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for (i, item) in items.iter().enumerate() {
+                    let value = Ordered {
+                        value: item,
+                        orders: self
+                            .orders
+                            .iter()
+                            .filter_map(|order| order.items.get(i))
+                            .collect(),
+                    };
+                    seq.serialize_element(&value)?;
+                }
+                seq.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+/// Merge the text of three snapshot files, as they're stored, with any
+/// metadata header above each snapshot.
+///
+/// The snapshots beneath the headers are merged as `snapshots` would.
+/// The headers are merged field by field, taking each from whichever side
+/// changed it, or from `mine` if both did, since they're never compared.
+///
+/// ```
+/// let base = "archetype-reason: first\n---\n{ \"version\": 1 }";
+/// let theirs = "archetype-reason: bumped\n---\n{ \"version\": 2 }";
+/// let mine = "archetype-reason: first\narchetype-locked: true\n---\n{ \"version\": 1 }";
+///
+/// let merged = archetype::merge::files(base, theirs, mine).unwrap();
+/// assert_eq!(
+///     merged,
+///     "archetype-reason: bumped\narchetype-locked: true\n---\n{\n  \"version\": 2\n}"
+/// );
+/// ```
+pub fn files(base: &str, theirs: &str, mine: &str) -> Result<String, MergeError> {
+    let (base_meta, base) = meta::split(base);
+    let (their_meta, theirs) = meta::split(theirs);
+    let (my_meta, mine) = meta::split(mine);
+    let merged = snapshots(base, theirs, mine)?;
+
+    let mut meta = Meta::default();
+    let names = my_meta.fields().iter().chain(their_meta.fields());
+    for (name, _) in names {
+        let (base, theirs, mine) = (base_meta.get(name), their_meta.get(name), my_meta.get(name));
+        let value = if base == mine { theirs } else { mine };
+        if let Some(value) = value {
+            meta.set(name, value);
+        }
+    }
+    Ok(meta.join(&merged))
+}
+
+/// Merge `theirs` and `mine` against their common ancestor `base`.
+///
+/// Objects are merged key by key and arrays of the same length element
+/// by element. Any other value is taken from whichever side changed it;
+/// if both sides changed it differently, it is a conflict.
+pub fn json(base: &Value, theirs: &Value, mine: &Value) -> Result<Value, MergeError> {
+    let mut conflicts = Vec::new();
+    let merged = merge(
+        String::new(),
+        Some(base),
+        Some(theirs),
+        Some(mine),
+        &mut conflicts,
+    );
+    if conflicts.is_empty() {
+        Ok(merged.unwrap_or(Value::Null))
+    } else {
+        Err(MergeError::Conflicts(conflicts))
+    }
+}
+
+fn merge(
+    path: String,
+    base: Option<&Value>,
+    theirs: Option<&Value>,
+    mine: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if theirs == mine {
+        return theirs.cloned();
+    }
+    if base == mine {
+        return theirs.cloned();
+    }
+    if base == theirs {
+        return mine.cloned();
+    }
+    match (base, theirs, mine) {
+        (Some(Value::Object(base)), Some(Value::Object(theirs)), Some(Value::Object(mine))) => {
+            let mut keys: Vec<&String> = base
+                .keys()
+                .chain(theirs.keys())
+                .chain(mine.keys())
+                .collect();
+            keys.sort();
+            keys.dedup();
+            let mut merged = Map::new();
+            for key in keys {
+                let value = merge(
+                    format!("{}/{}", path, escape(key)),
+                    base.get(key),
+                    theirs.get(key),
+                    mine.get(key),
+                    conflicts,
+                );
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Some(Value::Object(merged))
+        }
+        (Some(Value::Array(base)), Some(Value::Array(theirs)), Some(Value::Array(mine)))
+            if base.len() == theirs.len() && base.len() == mine.len() =>
+        {
+            let merged = (0..base.len())
+                .map(|i| {
+                    merge(
+                        format!("{}/{}", path, i),
+                        Some(&base[i]),
+                        Some(&theirs[i]),
+                        Some(&mine[i]),
+                        conflicts,
+                    )
+                    .unwrap_or(Value::Null)
+                })
+                .collect();
+            Some(Value::Array(merged))
+        }
+        _ => {
+            conflicts.push(Conflict {
+                path,
+                base: base.cloned(),
+                theirs: theirs.cloned(),
+                mine: mine.cloned(),
+            });
+            base.cloned()
+        }
+    }
+}
+
+/// Escape a key for use as a JSON pointer token.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{files, json, snapshots, Conflict, MergeError};
+    use serde_json::json;
+
+    #[derive(serde::Serialize)]
+    struct Search {
+        r#type: &'static str,
+        ids: Vec<u32>,
+        limit: u32,
+    }
+
+    #[test]
+    fn merged_snapshots_keep_their_key_order() {
+        let base = "{\n  \"type\": \"by-ids\",\n  \"ids\": [4, 7, 9],\n  \"limit\": 10\n}";
+        let theirs = "{\n  \"type\": \"by-ids\",\n  \"ids\": [5, 7, 9],\n  \"limit\": 10\n}";
+        let mine = "{\n  \"type\": \"by-ids\",\n  \"ids\": [4, 7, 9],\n  \"limit\": 20\n}";
+        let merged = snapshots(base, theirs, mine).unwrap();
+        // The merged snapshot is what the merged output would be written as.
+        let search = Search {
+            r#type: "by-ids",
+            ids: vec![5, 7, 9],
+            limit: 20,
+        };
+        crate::snap("merge_search_by_ids", merged);
+        crate::snap_json("merge_search_by_ids", &search);
+    }
+
+    #[test]
+    fn merges_headers_apart_from_snapshots() {
+        let base =
+            "archetype-updated-by: ada\narchetype-reason: first\n---\n{ \"a\": 1, \"b\": 1 }";
+        let theirs =
+            "archetype-updated-by: bob\narchetype-reason: theirs\n---\n{ \"a\": 2, \"b\": 1 }";
+        let mine = "archetype-updated-by: cy\n---\n{ \"a\": 1, \"b\": 3 }";
+        assert_eq!(
+            files(base, theirs, mine).unwrap(),
+            "archetype-updated-by: cy\n---\n{\n  \"a\": 2,\n  \"b\": 3\n}"
+        );
+        assert_eq!(
+            files("{}", "archetype-locked: true\n---\n{}", "{}").unwrap(),
+            "archetype-locked: true\n---\n{}"
+        );
+    }
+
+    #[test]
+    fn merges_non_overlapping_changes() {
+        let base = json!({ "a": 1, "b": { "c": [1, 2], "d": "x" }, "e": true });
+        let theirs = json!({ "a": 2, "b": { "c": [1, 3], "d": "x" }, "e": true });
+        let mine = json!({ "a": 1, "b": { "c": [1, 2], "d": "y" }, "f": null });
+        assert_eq!(
+            json(&base, &theirs, &mine).unwrap(),
+            json!({ "a": 2, "b": { "c": [1, 3], "d": "y" }, "f": null })
+        );
+    }
+
+    #[test]
+    fn reports_overlapping_changes() {
+        let base = json!({ "items": [{ "price": 10 }], "a/b": 1 });
+        let theirs = json!({ "items": [{ "price": 12 }], "a/b": 2 });
+        let mine = json!({ "items": [{ "price": 11 }] });
+        match json(&base, &theirs, &mine) {
+            Err(MergeError::Conflicts(conflicts)) => assert_eq!(
+                conflicts,
+                vec![
+                    Conflict {
+                        path: String::from("/a~1b"),
+                        base: Some(json!(1)),
+                        theirs: Some(json!(2)),
+                        mine: None,
+                    },
+                    Conflict {
+                        path: String::from("/items/0/price"),
+                        base: Some(json!(10)),
+                        theirs: Some(json!(12)),
+                        mine: Some(json!(11)),
+                    },
+                ]
+            ),
+            other => panic!("expected conflicts, got {:?}", other),
+        }
+    }
+}
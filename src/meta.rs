@@ -0,0 +1,197 @@
+//! Metadata stored in a header above a snapshot.
+//!
+//! The header is a run of `archetype-<name>: <value>` lines closed by a
+//! `---` line. It is never compared, only carried along when the snapshot
+//! is rewritten. A snapshot that is `locked` is never rewritten unless
+//! forced:
+//!
+//! ```text
+//! archetype-updated-by: ada
+//! archetype-updated-at: 2023-10-21T08:30:00Z
+//! archetype-reason: search terms are now trimmed
+//! archetype-locked: true
+//! ---
+//! {
+//!   "type": "by-term",
+//!   "term": "an example search term"
+//! }
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PREFIX: &str = "archetype-";
+const END: &str = "---";
+
+/// The metadata fields of a snapshot, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Meta {
+    fields: Vec<(String, String)>,
+}
+
+impl Meta {
+    /// The value of the field `name`, if it is set.
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every field, in order.
+    pub(crate) fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Whether the snapshot is locked against updates, with
+    /// `archetype-locked: true`.
+    pub(crate) fn locked(&self) -> bool {
+        self.get("locked") == Some("true")
+    }
+
+    /// Remove the field `name`, if it is set.
+    pub(crate) fn remove(&mut self, name: &str) {
+        self.fields.retain(|(field, _)| field != name);
+    }
+
+    /// Set the field `name` to `value`, keeping its place if it is already
+    /// set.
+    pub(crate) fn set(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into().replace('\n', " ");
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    /// Record who is updating the snapshot now and, from `ARCHETYPE_REASON`,
+    /// why.
+    pub(crate) fn stamp(&mut self) {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| String::from("unknown"));
+        self.set("updated-by", user);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        self.set("updated-at", timestamp(now));
+        match std::env::var("ARCHETYPE_REASON") {
+            Ok(reason) => self.set("reason", reason),
+            Err(_) => self.remove("reason"),
+        }
+    }
+
+    /// `body` beneath this metadata as a header, or alone if there is none.
+    pub(crate) fn join(&self, body: &str) -> String {
+        if self.fields.is_empty() {
+            return body.to_string();
+        }
+        let mut out = String::new();
+        for (field, value) in &self.fields {
+            out.push_str(&format!("{}{}: {}\n", PREFIX, field, value));
+        }
+        out.push_str(END);
+        out.push('\n');
+        out.push_str(body);
+        out
+    }
+}
+
+/// Split `contents` into its metadata and the snapshot itself.
+pub(crate) fn split(contents: &str) -> (Meta, &str) {
+    let mut meta = Meta::default();
+    let mut rest = contents;
+    loop {
+        let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+        if line.trim_end() == END && !meta.fields.is_empty() {
+            return (meta, after);
+        }
+        let field = line
+            .strip_prefix(PREFIX)
+            .and_then(|line| line.split_once(": "));
+        match field {
+            Some((name, value)) if !name.contains(' ') => {
+                meta.fields
+                    .push((name.to_string(), value.trim_end().to_string()));
+                rest = after;
+            }
+            _ => return (Meta::default(), contents),
+        }
+    }
+}
+
+/// `secs` since the Unix epoch as an RFC 3339 timestamp in UTC.
+pub(crate) fn timestamp(secs: u64) -> String {
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    // The civil-from-days algorithm, counting from 0000-03-01 so that the
+    // leap day falls at the end of each year.
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, timestamp, Meta};
+
+    #[test]
+    fn split_reverses_join() {
+        let mut meta = Meta::default();
+        meta.set("updated-by", "ada");
+        meta.set("reason", "trim\nterms");
+        let contents = meta.join("{}\n");
+        assert_eq!(
+            contents,
+            "archetype-updated-by: ada\narchetype-reason: trim terms\n---\n{}\n"
+        );
+        assert_eq!(split(&contents), (meta, "{}\n"));
+    }
+
+    #[test]
+    fn locked_snapshots() {
+        let (meta, _) = split("archetype-locked: true\n---\n{}\n");
+        assert!(meta.locked());
+        let (mut meta, _) = split("archetype-locked: false\n---\n{}\n");
+        assert!(!meta.locked());
+        meta.remove("locked");
+        assert_eq!(meta, Meta::default());
+    }
+
+    #[test]
+    fn split_leaves_other_snapshots_alone() {
+        for contents in [
+            "{}\n",
+            "---\nyaml: true\n",
+            "archetype-link: search\n",
+            "archetype-note: unterminated\nbody\n",
+        ] {
+            assert_eq!(split(contents), (Meta::default(), contents));
+        }
+    }
+
+    #[test]
+    fn timestamp_is_rfc3339() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(1_697_877_000), "2023-10-21T08:30:00Z");
+        assert_eq!(timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    }
+}
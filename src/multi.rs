@@ -0,0 +1,97 @@
+//! Snapshots of one value rendered in several formats at once.
+
+use crate::Settings;
+use serde::Serialize;
+use std::fmt::Debug;
+
+/// A rendering of a value that can be snapshotted by `snap_multi`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Pretty printed JSON, as written by `snap_json`.
+    Json,
+    /// YAML, via `serde_yaml`.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// The value's pretty printed `Debug` output, `{:#?}`.
+    Debug,
+}
+
+impl Format {
+    /// The suffix appended to the key of snapshots in this format.
+    fn suffix(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "yaml",
+            Format::Debug => "debug",
+        }
+    }
+
+    fn render<A: Serialize + Debug>(self, subject: &A) -> String {
+        match self {
+            Format::Json => crate::json::pretty(subject, &Settings::current()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(subject).expect("should serialize"),
+            Format::Debug => format!("{:#?}", subject),
+        }
+    }
+}
+
+/// Take a snapshot of `subject` in each of `formats`, under files named
+/// `key` suffixed with the format, e.g. `key.json` and `key.debug`.
+///
+/// Every format is checked before failing, and the failure lists all of
+/// the formats that mismatched, so a change that affects both the
+/// machine-readable and the human-readable output is reported in full.
+///
+/// ```
+/// use archetype::Format;
+///
+/// archetype::snap_multi("multi-example", &vec![4, 7, 9], [Format::Json, Format::Debug]);
+/// ```
+pub fn snap_multi<A: Serialize + Debug>(
+    key: &str,
+    subject: &A,
+    formats: impl IntoIterator<Item = Format>,
+) {
+    let failures: Vec<String> = formats
+        .into_iter()
+        .filter_map(|format| {
+            let key = format!("{}.{}", key, format.suffix());
+            crate::check(&key, format.render(subject), false)
+                .err()
+                .map(|failure| failure.to_string())
+        })
+        .collect();
+    if !failures.is_empty() {
+        panic!("{}", failures.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+
+    #[derive(serde::Serialize, Debug)]
+    struct Search {
+        term: String,
+        ids: Vec<i64>,
+    }
+
+    #[test]
+    fn snapshot_multi() {
+        crate::snap_multi(
+            "multi_search",
+            &Search {
+                term: String::from("an example search term"),
+                ids: vec![4, 7, 9],
+            },
+            [
+                Format::Json,
+                #[cfg(feature = "yaml")]
+                Format::Yaml,
+                Format::Debug,
+            ],
+        );
+    }
+}
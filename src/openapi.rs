@@ -0,0 +1,128 @@
+//! Snapshots of OpenAPI documents for contract testing generated specs.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Placeholder written in place of server URLs.
+const SERVER_URL: &str = "[server-url]";
+
+/// Placeholder written in place of generation timestamps.
+const TIMESTAMP: &str = "[timestamp]";
+
+/// Take a snapshot of an OpenAPI document under a file with the name
+/// `key`.
+///
+/// The document may be a `serde_json::Value` or any typed model that
+/// serializes to one. Before it is stored, the parts of a generated spec
+/// that change from build to build are normalized:
+///
+/// * every `servers[].url` is replaced with `[server-url]`,
+/// * any `x-` extension whose name mentions `generated` or `timestamp`
+///   is replaced with `[timestamp]`,
+/// * all objects, including `paths` and `components`, are sorted by key.
+///
+/// ```
+/// archetype::snap_openapi(
+///     "openapi-example",
+///     &serde_json::json!({
+///         "openapi": "3.0.3",
+///         "info": { "title": "example", "version": "1.0.0" },
+///         "servers": [{ "url": "http://localhost:8080" }],
+///         "paths": {},
+///     }),
+/// );
+/// ```
+pub fn snap_openapi<A: Serialize>(key: &str, document: &A) {
+    let document = serde_json::to_value(document).expect("should serialize");
+    crate::snap_json(key, &normalize(document));
+}
+
+fn normalize(mut document: Value) -> Value {
+    redact(&mut document);
+    document.sort_all_objects();
+    document
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, inner) in map.iter_mut() {
+                if name == "servers" {
+                    redact_servers(inner);
+                } else if is_timestamp_extension(name) {
+                    *inner = Value::String(TIMESTAMP.to_string());
+                } else {
+                    redact(inner);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn redact_servers(servers: &mut Value) {
+    if let Value::Array(servers) = servers {
+        for server in servers {
+            if let Some(url) = server.get_mut("url") {
+                *url = Value::String(SERVER_URL.to_string());
+            }
+        }
+    }
+}
+
+fn is_timestamp_extension(name: &str) -> bool {
+    name.starts_with("x-") && (name.contains("generated") || name.contains("timestamp"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    fn petstore() -> serde_json::Value {
+        json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "petstore",
+                "version": "1.0.0",
+                "x-generated-at": "2023-10-21T09:14:00Z",
+            },
+            "servers": [
+                { "url": "https://staging-4f2a.example.com", "description": "staging" },
+            ],
+            "paths": {
+                "/pets/{id}": {
+                    "get": { "operationId": "getPet" },
+                    "servers": [{ "url": "http://127.0.0.1:53211" }],
+                },
+                "/pets": {
+                    "get": { "operationId": "listPets" },
+                },
+            },
+            "components": {
+                "schemas": {
+                    "Pet": { "type": "object" },
+                    "Error": { "type": "object" },
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn normalize_redacts_servers_and_timestamps() {
+        let document = super::normalize(petstore());
+        assert_eq!(document["servers"][0]["url"], "[server-url]");
+        assert_eq!(document["servers"][0]["description"], "staging");
+        assert_eq!(
+            document["paths"]["/pets/{id}"]["servers"][0]["url"],
+            "[server-url]"
+        );
+        assert_eq!(document["info"]["x-generated-at"], "[timestamp]");
+        assert_eq!(document["info"]["title"], "petstore");
+    }
+
+    #[test]
+    fn snapshot_petstore() {
+        crate::snap_openapi("openapi_petstore", &petstore());
+    }
+}
@@ -0,0 +1,24 @@
+//! Where reports such as diffs are written.
+//!
+//! Natively this is stdout, where the test harness captures it. In the
+//! browser stdout goes nowhere, so with the `wasm` feature reports are
+//! written to the console instead, which `wasm-bindgen-test` forwards.
+
+/// Write `text` followed by a newline.
+pub(crate) fn print(text: &str) {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    console::log(text);
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    println!("{}", text);
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod console {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = console)]
+        pub(super) fn log(text: &str);
+    }
+}
@@ -0,0 +1,189 @@
+//! Per-key overrides of the configuration and settings, kept centrally in
+//! `archetype.overrides.toml` in the snapshot directory rather than at
+//! each call site.
+//!
+//! Each `[[override]]` applies to the keys matching its glob, on top of
+//! the configuration and the settings bound where the snapshot is
+//! asserted, with later overrides applied over earlier ones:
+//!
+//! ```toml
+//! [[override]]
+//! key = "api/*"
+//! extension = "json"
+//! filters = [{ pattern = "\\d+ms", replacement = "[duration]" }]
+//!
+//! [[override]]
+//! key = "render/*"
+//! tolerance = 0.95
+//! ```
+//!
+//! A `tolerance` accepts snapshots at least that similar, as
+//! `Comparison::Similarity` would. Snapshots stored under an overridden
+//! `extension` aren't seen when listing the snapshot directory, such as
+//! by `cargo archetype`.
+
+use crate::config::FileFilter;
+use crate::{Comparison, GlobalConfig, Settings};
+use serde::Deserialize;
+use std::borrow::Cow;
+#[cfg(feature = "fs")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "fs")]
+const FILE: &str = "archetype.overrides.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default, rename = "override")]
+    overrides: Vec<Override>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Override {
+    key: String,
+    extension: Option<String>,
+    tolerance: Option<f32>,
+    #[serde(default)]
+    filters: Vec<FileFilter>,
+}
+
+/// The configuration and settings to assert the snapshot under `key`
+/// with: the global configuration and the settings bound to this thread,
+/// with any overrides for `key` applied.
+pub(crate) fn resolve(key: &str) -> (Cow<'static, GlobalConfig>, Settings) {
+    let config = crate::config::global();
+    apply(&loaded(config).overrides, key, config, Settings::current())
+}
+
+fn apply<'a>(
+    overrides: &[Override],
+    key: &str,
+    config: &'a GlobalConfig,
+    mut settings: Settings,
+) -> (Cow<'a, GlobalConfig>, Settings) {
+    let mut config = Cow::Borrowed(config);
+    for matching in overrides
+        .iter()
+        .filter(|matching| crate::glob::matches(&matching.key, key))
+    {
+        let mut owned = config.into_owned();
+        if let Some(extension) = &matching.extension {
+            owned = owned.extension(extension.clone());
+        }
+        for filter in &matching.filters {
+            owned = owned.filter(&filter.pattern, filter.replacement.clone());
+        }
+        if let Some(tolerance) = matching.tolerance {
+            settings = settings.comparison(Comparison::Similarity(tolerance));
+        }
+        config = Cow::Owned(owned);
+    }
+    (config, settings)
+}
+
+/// The overrides in the snapshot directory, read the first time they're
+/// needed.
+#[cfg(feature = "fs")]
+fn loaded(config: &GlobalConfig) -> &'static File {
+    static LOADED: OnceLock<File> = OnceLock::new();
+    LOADED.get_or_init(|| {
+        let Some(path) = path(config, |name| std::env::var(name).ok()) else {
+            return File::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .unwrap_or_else(|err| panic!("invalid {}: {}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => File::default(),
+            Err(err) => panic!("unable to read {}: {}", path.display(), err),
+        }
+    })
+}
+
+/// Where the overrides are read from under `config`, with environment
+/// variables looked up by `var`. Snapshots kept in a configured storage
+/// have no snapshot directory to hold overrides, and neither do those
+/// whose directory can't be resolved, such as in a binary run outside of
+/// cargo, so there are none for either.
+#[cfg(feature = "fs")]
+fn path(config: &GlobalConfig, var: impl Fn(&str) -> Option<String>) -> Option<std::path::PathBuf> {
+    if config.storage.is_some() {
+        return None;
+    }
+    let dir = config.try_resolve(&config.root, var).ok()?;
+    Some(dir.join(FILE))
+}
+
+#[cfg(not(feature = "fs"))]
+fn loaded(_: &GlobalConfig) -> &'static File {
+    static NONE: File = File {
+        overrides: Vec::new(),
+    };
+    &NONE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, File};
+    use crate::{Comparison, GlobalConfig, Settings};
+    use std::borrow::Cow;
+
+    #[test]
+    fn overrides_apply_to_matching_keys() {
+        let file: File = toml::from_str(
+            r#"
+            [[override]]
+            key = "api/*"
+            extension = "json"
+            filters = [{ pattern = "\\d+ms", replacement = "[duration]" }]
+
+            [[override]]
+            key = "api/search/*"
+            extension = "golden"
+            tolerance = 0.9
+            "#,
+        )
+        .unwrap();
+        let config = GlobalConfig::new();
+
+        let (resolved, settings) = apply(
+            &file.overrides,
+            "api/search/by-term",
+            &config,
+            Settings::new(),
+        );
+        assert_eq!(resolved.extension, "golden");
+        assert_eq!(
+            resolved.apply_filters(String::from("took 12ms")),
+            "took [duration]"
+        );
+        assert_eq!(settings.comparison, Comparison::Similarity(0.9));
+
+        let (resolved, settings) = apply(&file.overrides, "render/page", &config, Settings::new());
+        assert!(matches!(resolved, Cow::Borrowed(_)));
+        assert_eq!(settings.comparison, Comparison::Lines);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn overrides_are_only_read_from_a_resolved_directory() {
+        use super::path;
+        use crate::store::BundledStorage;
+
+        let manifest = |name: &str| (name == "CARGO_MANIFEST_DIR").then(|| String::from("/crate"));
+        assert_eq!(
+            path(&GlobalConfig::new(), manifest),
+            Some("/crate/snapshots/archetype.overrides.toml".into())
+        );
+        assert_eq!(path(&GlobalConfig::new(), |_| None), None);
+        let bundled = GlobalConfig::new().storage(BundledStorage::new());
+        assert_eq!(path(&bundled, manifest), None);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let invalid = toml::from_str::<File>("[[override]]\nkey = \"*\"\ntolerence = 0.9\n");
+        assert!(invalid.unwrap_err().to_string().contains("tolerence"));
+    }
+}
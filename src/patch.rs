@@ -0,0 +1,224 @@
+//! Unified diffs: of mismatches, for `git apply`, `patch`, and any diff
+//! viewer, rather than only the rendering printed by the assertion, and
+//! of transformations, to snapshot how they change their input.
+
+use crate::{GlobalConfig, Settings, SnapshotError};
+use serde::Serialize;
+use similar::TextDiff;
+
+/// The unified diff that turns `old` into `new`, as a patch to the file
+/// at `path` that `git apply` or `patch -p1` can apply.
+///
+/// ```
+/// let patch = archetype::patch("snapshots/search.snap", "by-ids\n", "by-term\n");
+/// assert_eq!(
+///     patch,
+///     "--- a/snapshots/search.snap\n\
+///      +++ b/snapshots/search.snap\n\
+///      @@ -1 +1 @@\n\
+///      -by-ids\n\
+///      +by-term\n"
+/// );
+/// ```
+pub fn patch(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Take a snapshot of the unified diff from `before` to `after` under a
+/// file with the name `key`, rather than of either one.
+///
+/// A test of a transformation, such as a migration of a config file,
+/// can pin what the transformation changes instead of both endpoints, so
+/// the snapshot stays small and an unrelated change to the input doesn't
+/// churn it. Identical sides are stored as `no changes`.
+///
+/// ```
+/// let before = "name = \"search\"\nworkers = 4\n";
+/// let after = "name = \"search\"\nworkers = 8\nqueue = \"default\"\n";
+/// archetype::snap_diff("diff-example", before, after);
+/// ```
+pub fn snap_diff(key: &str, before: &str, after: &str) {
+    let diff = TextDiff::from_lines(before, after)
+        .unified_diff()
+        .header("before", "after")
+        .to_string();
+    match diff.as_str() {
+        "" => crate::snap(key, String::from("no changes\n")),
+        _ => crate::snap(key, diff),
+    }
+}
+
+/// Like `snap_diff`, but between `before` and `after` pretty printed as
+/// JSON, as `snap_json` would.
+///
+/// ```
+/// let before = serde_json::json!({ "workers": 4 });
+/// let after = serde_json::json!({ "workers": 8, "queue": "default" });
+/// archetype::snap_json_diff("json-diff-example", &before, &after);
+/// ```
+pub fn snap_json_diff<A: Serialize, B: Serialize>(key: &str, before: &A, after: &B) {
+    let settings = Settings::current();
+    let before = crate::json::pretty(before, &settings);
+    let after = crate::json::pretty(after, &settings);
+    snap_diff(key, &before, &after);
+}
+
+/// Where the patch for the snapshot file at `location` is written, if
+/// `config` writes patches there at all. Patches are only written next
+/// to snapshots kept one per file, and never under the `verify-only`
+/// feature.
+#[cfg(feature = "fs")]
+fn path(config: &GlobalConfig, location: &str) -> Option<std::path::PathBuf> {
+    let files = config.storage.is_none() && config.layout == crate::Layout::Files;
+    let writes = !config.dry_run && !cfg!(feature = "verify-only");
+    (config.patches && files && writes).then(|| format!("{}.patch", location).into())
+}
+
+/// Write the patch that turns the snapshot file at `location`, holding
+/// `old`, into `new`, returning where it was written, if anywhere. The
+/// paths in the patch are relative to the directory the snapshot root is
+/// resolved against, usually the crate's manifest directory, so it
+/// applies from there whatever directory the tests were run in.
+#[cfg(feature = "fs")]
+pub(crate) fn write(
+    config: &GlobalConfig,
+    location: &str,
+    old: &str,
+    new: &str,
+) -> Result<Option<String>, SnapshotError> {
+    let Some(path) = path(config, location) else {
+        return Ok(None);
+    };
+    let relative = config
+        .base(|name| std::env::var(name).ok())
+        .ok()
+        .and_then(|dir| std::path::Path::new(location).strip_prefix(dir).ok())
+        .map(|relative| relative.to_string_lossy().into_owned())
+        .unwrap_or_else(|| location.to_string());
+    std::fs::write(&path, patch(&relative, old, new))
+        .map_err(|err| SnapshotError::io("write the patch of", location.to_string(), err))?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+#[cfg(not(feature = "fs"))]
+pub(crate) fn write(
+    _: &GlobalConfig,
+    _: &str,
+    _: &str,
+    _: &str,
+) -> Result<Option<String>, SnapshotError> {
+    Ok(None)
+}
+
+/// Remove the patch left by an earlier mismatch of the snapshot file at
+/// `location`, now that it matches, so a stale patch isn't applied. A
+/// patch that was never written is fine, but one that can't be removed is
+/// an error.
+#[cfg(feature = "fs")]
+pub(crate) fn discard(config: &GlobalConfig, location: &str) -> Result<(), SnapshotError> {
+    let Some(path) = path(config, location) else {
+        return Ok(());
+    };
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(SnapshotError::io(
+            "remove the patch of",
+            location.to_string(),
+            err,
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "fs"))]
+pub(crate) fn discard(_: &GlobalConfig, _: &str) -> Result<(), SnapshotError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch, snap_json_diff};
+
+    #[test]
+    fn patch_keeps_context_around_changes() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\n";
+        assert_eq!(
+            patch("snapshots/letters.snap", old, new),
+            "--- a/snapshots/letters.snap\n\
+             +++ b/snapshots/letters.snap\n\
+             @@ -2,7 +2,7 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n"
+        );
+    }
+
+    #[test]
+    fn snapshot_migration_diff() {
+        let before = serde_json::json!({
+            "name": "search",
+            "workers": 4,
+            "retries": { "max": 3 },
+        });
+        let after = serde_json::json!({
+            "name": "search",
+            "workers": 4,
+            "retries": { "max": 3, "backoff": "exponential" },
+        });
+        snap_json_diff("diff_migration", &before, &after);
+    }
+
+    #[cfg(all(feature = "fs", not(feature = "verify-only")))]
+    #[test]
+    fn patches_are_written_next_to_snapshots() {
+        use crate::GlobalConfig;
+
+        let dir = std::env::temp_dir().join(format!("archetype-patch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let location = dir.join("search.snap").to_string_lossy().into_owned();
+        let config = GlobalConfig::new();
+        assert_eq!(
+            super::write(&config, &location, "a\n", "b\n").unwrap(),
+            None
+        );
+
+        let config = config.patches(true);
+        let written = super::write(&config, &location, "a\n", "b\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(written, format!("{}.patch", location));
+        let contents = std::fs::read_to_string(&written).unwrap();
+        assert!(contents.ends_with("@@ -1 +1 @@\n-a\n+b\n"));
+        super::discard(&config, &location).unwrap();
+        assert!(!std::path::Path::new(&written).exists());
+        super::discard(&config, &location).unwrap();
+
+        let config = GlobalConfig::load_from(&dir).patches(true);
+        let location = dir.join("goldens").join("search.snap");
+        std::fs::create_dir_all(location.parent().unwrap()).unwrap();
+        let location = location.to_string_lossy().into_owned();
+        let written = super::write(&config, &location, "a\n", "b\n")
+            .unwrap()
+            .unwrap();
+        let contents = std::fs::read_to_string(&written).unwrap();
+        assert!(contents.starts_with("--- a/goldens/search.snap\n+++ b/goldens/search.snap\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(all(feature = "fs", not(feature = "verify-only")))]
+    #[test]
+    fn patches_that_cant_be_written_are_errors() {
+        use crate::GlobalConfig;
+
+        let dir =
+            std::env::temp_dir().join(format!("archetype-patch-missing-{}", std::process::id()));
+        let location = dir.join("search.snap").to_string_lossy().into_owned();
+        let config = GlobalConfig::new().patches(true);
+        let err = super::write(&config, &location, "a\n", "b\n").unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::NotFound));
+        assert!(err.to_string().starts_with(&format!(
+            "unable to write the patch of snapshot at {} (NotFound)",
+            location
+        )));
+    }
+}
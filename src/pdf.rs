@@ -0,0 +1,351 @@
+//! Snapshots of the text in PDFs.
+//!
+//! PDFs embed creation dates, IDs, and compressed streams that differ
+//! from one render to the next, so comparing them byte for byte is
+//! hopeless. The text drawn on each page is what a report is about, so
+//! that is extracted and stored instead, a section per page.
+
+use crate::Sections;
+use lopdf::content::Content;
+use lopdf::{Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+
+/// A transformation matrix, `[a b c d e f]`, as PDFs write them.
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// How far into a `TJ` array, in thousandths of the font size, text must
+/// move right to count as a space between words.
+const WORD_GAP: f32 = 200.0;
+
+/// A run of text drawn from one point on a page.
+struct Run {
+    x: f32,
+    y: f32,
+    text: String,
+}
+
+/// Take a snapshot of the text drawn on each page of `pdf` under a file
+/// with the name `key`.
+///
+/// Each page is a section, named `page 1` and so on, with its text read
+/// top to bottom and left to right: runs of text drawn on the same line
+/// are joined with a space, and each line is on a line of its own.
+///
+/// Panics if `pdf` isn't a PDF that can be read.
+///
+/// ```no_run
+/// let report = std::fs::read("target/report.pdf").unwrap();
+/// archetype::snap_pdf("report", &report);
+/// ```
+pub fn snap_pdf(key: &str, pdf: &[u8]) {
+    snap(key, pdf, |runs| {
+        let mut lines: Vec<(i32, Vec<String>)> = Vec::new();
+        for run in runs {
+            let y = run.y.round() as i32;
+            match lines.last_mut() {
+                Some((line, texts)) if *line == y => texts.push(run.text),
+                _ => lines.push((y, vec![run.text])),
+            }
+        }
+        let lines: Vec<String> = lines
+            .into_iter()
+            .map(|(_, texts)| texts.join(" "))
+            .collect();
+        lines.join("\n")
+    });
+}
+
+/// Like `snap_pdf`, but also stores where each run of text is drawn, as
+/// its distance from the left and the bottom of the page, in points:
+///
+/// ```text
+/// 72 770 Quarterly report
+/// 72 740 Revenue
+/// 400 740 $12,400
+/// ```
+///
+/// Positions are rounded to whole points, so they only change when the
+/// layout does.
+///
+/// Panics if `pdf` isn't a PDF that can be read.
+///
+/// ```no_run
+/// let report = std::fs::read("target/report.pdf").unwrap();
+/// archetype::snap_pdf_layout("report-layout", &report);
+/// ```
+pub fn snap_pdf_layout(key: &str, pdf: &[u8]) {
+    snap(key, pdf, |runs| {
+        let lines: Vec<String> = runs
+            .into_iter()
+            .map(|run| format!("{} {} {}", run.x.round(), run.y.round(), run.text))
+            .collect();
+        lines.join("\n")
+    });
+}
+
+fn snap(key: &str, pdf: &[u8], render: impl Fn(Vec<Run>) -> String) {
+    let document = Document::load_mem(pdf)
+        .unwrap_or_else(|err| panic!("unable to read PDF to snapshot for {}: {}", key, err));
+    let mut sections = Sections::new(key);
+    for (number, page) in document.get_pages() {
+        let mut runs = runs(&document, page)
+            .unwrap_or_else(|err| panic!("unable to read page {} of {}: {}", number, key, err));
+        // Top to bottom, then left to right, as the page is read.
+        runs.sort_by(|a, b| {
+            let across = a.x.round().total_cmp(&b.x.round());
+            b.y.round().total_cmp(&a.y.round()).then(across)
+        });
+        sections = sections.section(format!("page {}", number), render(runs) + "\n");
+    }
+    sections.snap();
+}
+
+/// The runs of text drawn on `page`, in the order they're drawn, with
+/// runs drawn from the same point joined.
+fn runs(document: &Document, page: ObjectId) -> lopdf::Result<Vec<Run>> {
+    let encodings: BTreeMap<Vec<u8>, Encoding> = document
+        .get_page_fonts(page)?
+        .into_iter()
+        .filter_map(|(name, font)| Some((name, font.get_font_encoding(document).ok()?)))
+        .collect();
+    let content = Content::decode(&document.get_page_content(page))?;
+
+    let mut runs: Vec<Run> = Vec::new();
+    let mut encoding = None;
+    let (mut ctm, mut saved) = (IDENTITY, Vec::new());
+    let (mut text, mut line, mut leading) = (IDENTITY, IDENTITY, 0.0);
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        let number = |i: usize| {
+            operands
+                .get(i)
+                .and_then(|n| n.as_float().ok())
+                .unwrap_or(0.0)
+        };
+        let shown: Vec<&Object> = match operation.operator.as_str() {
+            "q" => {
+                saved.push(ctm);
+                continue;
+            }
+            "Q" => {
+                ctm = saved.pop().unwrap_or(IDENTITY);
+                continue;
+            }
+            "cm" => {
+                ctm = multiply(std::array::from_fn(number), ctm);
+                continue;
+            }
+            "BT" => {
+                (text, line) = (IDENTITY, IDENTITY);
+                continue;
+            }
+            "Tm" => {
+                line = std::array::from_fn(number);
+                text = line;
+                continue;
+            }
+            "Td" => {
+                next_line(&mut line, &mut text, number(0), number(1));
+                continue;
+            }
+            "TD" => {
+                leading = -number(1);
+                next_line(&mut line, &mut text, number(0), number(1));
+                continue;
+            }
+            "TL" => {
+                leading = number(0);
+                continue;
+            }
+            "T*" => {
+                next_line(&mut line, &mut text, 0.0, -leading);
+                continue;
+            }
+            "Tf" => {
+                encoding = operands
+                    .first()
+                    .and_then(|font| font.as_name().ok())
+                    .and_then(|font| encodings.get(font));
+                continue;
+            }
+            "Tj" | "TJ" => operands.iter().collect(),
+            "'" => {
+                next_line(&mut line, &mut text, 0.0, -leading);
+                operands.iter().collect()
+            }
+            "\"" => {
+                next_line(&mut line, &mut text, 0.0, -leading);
+                operands.get(2).into_iter().collect()
+            }
+            _ => continue,
+        };
+        let Some(encoding) = encoding else {
+            continue;
+        };
+        let mut drawn = String::new();
+        for object in shown {
+            show(&mut drawn, encoding, object)?;
+        }
+        let [_, _, _, _, x, y] = multiply(text, ctm);
+        match runs.last_mut() {
+            Some(run) if run.x == x && run.y == y => run.text.push_str(&drawn),
+            _ => runs.push(Run { x, y, text: drawn }),
+        }
+    }
+    runs.retain(|run| !run.text.trim().is_empty());
+    for run in &mut runs {
+        run.text = run.text.trim().to_string();
+    }
+    Ok(runs)
+}
+
+/// Append the text `object` shows to `drawn`: a string, or the strings
+/// of a `TJ` array, where moving far enough right between them is a
+/// space.
+fn show(drawn: &mut String, encoding: &Encoding, object: &Object) -> lopdf::Result<()> {
+    match object {
+        Object::String(bytes, _) => drawn.push_str(&Document::decode_text(encoding, bytes)?),
+        Object::Array(items) => {
+            for item in items {
+                match item.as_float() {
+                    Ok(gap) if -gap >= WORD_GAP => drawn.push(' '),
+                    Ok(_) => {}
+                    Err(_) => show(drawn, encoding, item)?,
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Move to the start of the line `x` and `y` from the start of the
+/// current one.
+fn next_line(line: &mut Matrix, text: &mut Matrix, x: f32, y: f32) {
+    *line = multiply([1.0, 0.0, 0.0, 1.0, x, y], *line);
+    *text = *line;
+}
+
+/// `a` then `b`, as PDFs compose transformations.
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snap_pdf, snap_pdf_layout};
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    /// A PDF of a page for each of `pages`, drawing their operations in
+    /// Helvetica.
+    fn pdf(pages: Vec<Vec<Operation>>) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let resources = document.add_object(dictionary! { "Font" => dictionary! { "F1" => font } });
+        let kids: Vec<Object> = pages
+            .into_iter()
+            .map(|operations| {
+                let content = Content { operations }.encode().unwrap();
+                let contents = document.add_object(Stream::new(dictionary! {}, content));
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "Contents" => contents,
+                        "Resources" => resources,
+                        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                    })
+                    .into()
+            })
+            .collect();
+        let count = kids.len() as i64;
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }),
+        );
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog);
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn op(operator: &str, operands: Vec<Object>) -> Operation {
+        Operation::new(operator, operands)
+    }
+
+    #[test]
+    fn text_is_read_in_page_order() {
+        let report = pdf(vec![
+            vec![
+                op("BT", vec![]),
+                op("Tf", vec!["F1".into(), 12.into()]),
+                // The amount is drawn before its label, and lower down the
+                // page, but is read after it, on the same line.
+                op("Td", vec![400.into(), 740.into()]),
+                op("Tj", vec![Object::string_literal("$12,400")]),
+                op(
+                    "Tm",
+                    vec![
+                        1.into(),
+                        0.into(),
+                        0.into(),
+                        1.into(),
+                        72.into(),
+                        740.into(),
+                    ],
+                ),
+                op("Tj", vec![Object::string_literal("Revenue")]),
+                op("ET", vec![]),
+                op("q", vec![]),
+                op(
+                    "cm",
+                    vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), 30.into()],
+                ),
+                op("BT", vec![]),
+                op("Tf", vec!["F1".into(), 18.into()]),
+                op("TL", vec![20.into()]),
+                op("Td", vec![72.into(), 740.into()]),
+                op(
+                    "TJ",
+                    vec![vec![
+                        Object::string_literal("Quarterly"),
+                        (-250).into(),
+                        Object::string_literal("re"),
+                        (-15).into(),
+                        Object::string_literal("port"),
+                    ]
+                    .into()],
+                ),
+                op("'", vec![Object::string_literal("Q3")]),
+                op("ET", vec![]),
+                op("Q", vec![]),
+            ],
+            vec![
+                op("BT", vec![]),
+                op("Tf", vec!["F1".into(), 12.into()]),
+                op("Td", vec![72.into(), 400.into()]),
+                op("Tj", vec![Object::string_literal("Appendix")]),
+                op("ET", vec![]),
+            ],
+        ]);
+        snap_pdf("pdf_report", &report);
+        snap_pdf_layout("pdf_report_layout", &report);
+    }
+}
@@ -0,0 +1,93 @@
+//! Detectors for personally identifiable information.
+
+use serde::Deserialize;
+
+/// A kind of personally identifiable information that can be scrubbed
+/// from snapshots with `GlobalConfig::scrub`.
+///
+/// Detection is by pattern, so it is deliberately broad: it is far better
+/// to redact a value that merely looks like a phone number than to commit
+/// a real one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pii {
+    /// Email addresses, replaced with `[email]` by default.
+    Email,
+    /// Phone numbers of ten digits, with an optional country code and
+    /// separators, replaced with `[phone]` by default.
+    Phone,
+    /// Runs of 13 to 19 digits, optionally grouped by spaces or dashes,
+    /// as card numbers are, replaced with `[card-number]` by default.
+    CardNumber,
+    /// IPv4 and IPv6 addresses, replaced with `[ip]` by default.
+    IpAddress,
+}
+
+impl Pii {
+    /// Every detector, in an order where none hides another: card numbers
+    /// are scrubbed before the phone numbers within them.
+    pub const ALL: [Pii; 4] = [Pii::Email, Pii::CardNumber, Pii::Phone, Pii::IpAddress];
+
+    /// The regular expression matching this kind of information.
+    pub(crate) fn pattern(self) -> &'static str {
+        match self {
+            Pii::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            Pii::Phone => r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\)|\b\d{3})[ .-]?\d{3}[ .-]?\d{4}\b",
+            Pii::CardNumber => r"\b(?:\d[ -]?){12,18}\d\b",
+            Pii::IpAddress => concat!(
+                r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b",
+                r"|\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b",
+                r"|\b(?:[0-9A-Fa-f]{1,4}:){1,6}(?::[0-9A-Fa-f]{1,4}){1,6}\b",
+            ),
+        }
+    }
+
+    /// What this kind of information is replaced with by default.
+    pub fn replacement(self) -> &'static str {
+        match self {
+            Pii::Email => "[email]",
+            Pii::Phone => "[phone]",
+            Pii::CardNumber => "[card-number]",
+            Pii::IpAddress => "[ip]",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pii;
+    use crate::GlobalConfig;
+
+    #[test]
+    fn scrubs_each_kind() {
+        let config = Pii::ALL
+            .into_iter()
+            .fold(GlobalConfig::new(), |config, pii| config.scrub(pii));
+        assert_eq!(
+            config.apply_filters(String::from(
+                "ada.lovelace@example.co.uk paid with 4111 1111 1111 1111 \
+                 from 192.168.0.12 and 2001:db8::ff00:42:8329, \
+                 call +1 (555) 010-0199 or 555.010.0199"
+            )),
+            "[email] paid with [card-number] from [ip] and [ip], call [phone] or [phone]"
+        );
+    }
+
+    #[test]
+    fn leaves_lookalikes_alone() {
+        let config = Pii::ALL
+            .into_iter()
+            .fold(GlobalConfig::new(), |config, pii| config.scrub(pii));
+        let text = "on 2023-10-21 at 12:30:45, version 1.2.3, id 123456";
+        assert_eq!(config.apply_filters(String::from(text)), text);
+    }
+
+    #[test]
+    fn scrubs_with_custom_replacement() {
+        let config = GlobalConfig::new().scrub_with(Pii::Email, "<redacted>");
+        assert_eq!(
+            config.apply_filters(String::from("from ops@example.com")),
+            "from <redacted>"
+        );
+    }
+}
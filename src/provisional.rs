@@ -0,0 +1,71 @@
+//! Provisional snapshots, which are recorded but never fail.
+//!
+//! These are for adopting golden tests on legacy output that isn't yet
+//! stable: the snapshot is kept up to date so changes can be reviewed,
+//! and every provisional key is listed when the test binary exits so
+//! they aren't forgotten. In the browser there is no exit to hook, so the
+//! list is not printed there.
+
+use crate::Settings;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::{Mutex, Once};
+
+static KEYS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static REPORT: Once = Once::new();
+
+/// Take a provisional snapshot of some UTF-8 encoded text under a file
+/// with the name `key`.
+///
+/// This behaves like `snap`, except that a missing or mismatched snapshot
+/// never fails the test. Instead the diff is printed and the snapshot is
+/// rewritten with the new output, unless `Update::Never` is configured.
+///
+/// ```
+/// archetype::snap_provisional("provisional-example", String::from("not stable yet"));
+/// ```
+pub fn snap_provisional(key: &str, subject: String) {
+    record(key);
+    crate::assert_snapshot(key, subject, true);
+}
+
+/// Take a provisional snapshot of JSON under a file with the name `key`.
+///
+/// See `snap_provisional`.
+pub fn snap_json_provisional<A: Serialize>(key: &str, subject: &A) {
+    snap_provisional(key, crate::json::pretty(subject, &Settings::current()));
+}
+
+fn record(key: &str) {
+    KEYS.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(key.to_string());
+    REPORT.call_once(|| crate::exit::at_exit(report));
+}
+
+extern "C" fn report() {
+    let keys = KEYS.lock().unwrap_or_else(|err| err.into_inner());
+    eprintln!(
+        "warning: {} provisional snapshot{} recorded but not enforced:",
+        keys.len(),
+        if keys.len() == 1 { " was" } else { "s were" }
+    );
+    for key in keys.iter() {
+        eprintln!("  {}", key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn snapshot_provisional() {
+        crate::snap_json_provisional(
+            "provisional_legacy_report",
+            &serde_json::json!({ "rows": 3 }),
+        );
+        assert!(super::KEYS
+            .lock()
+            .unwrap()
+            .contains("provisional_legacy_report"));
+    }
+}
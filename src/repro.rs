@@ -0,0 +1,45 @@
+//! How to reproduce a failing snapshot assertion.
+
+/// The command that re-runs just the current test, updating its
+/// snapshots, if the test can be identified.
+///
+/// The test harness names each test's thread after the test, which is
+/// how the test is found; in a single-threaded run it can't be.
+pub(crate) fn update_command() -> Option<String> {
+    let thread = std::thread::current();
+    command(
+        thread.name()?,
+        std::env::var("CARGO_PKG_NAME").ok().as_deref(),
+    )
+}
+
+fn command(test: &str, package: Option<&str>) -> Option<String> {
+    if test == "main" {
+        return None;
+    }
+    let package = package
+        .map(|package| format!(" -p {}", package))
+        .unwrap_or_default();
+    Some(format!(
+        "ARCHETYPE_UPDATE=1 cargo test{} {} -- --exact",
+        package, test
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::command;
+
+    #[test]
+    fn command_reruns_test() {
+        assert_eq!(
+            command("tests::snapshot_search_by_term", Some("archetype")).as_deref(),
+            Some("ARCHETYPE_UPDATE=1 cargo test -p archetype tests::snapshot_search_by_term -- --exact")
+        );
+        assert_eq!(
+            command("snapshot_search_by_term", None).as_deref(),
+            Some("ARCHETYPE_UPDATE=1 cargo test snapshot_search_by_term -- --exact")
+        );
+        assert_eq!(command("main", Some("archetype")), None);
+    }
+}
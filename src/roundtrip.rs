@@ -0,0 +1,236 @@
+//! Snapshots that must still deserialize into the types they describe.
+
+use crate::store::Storage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::io;
+
+/// Take a snapshot of `value` as JSON under a file with the name `key`,
+/// as `snap_json` would, and check that the stored snapshot deserializes
+/// back into a value equal to it.
+///
+/// A snapshot of serialized output only shows that serialization hasn't
+/// changed. Deserializing what was stored as well catches the changes
+/// that break reading data written by an earlier version, such as a
+/// renamed field or a new required one, in the same assertion. When the
+/// snapshot mismatches, the failure says whether the stored snapshot
+/// still deserializes at all.
+///
+/// Filters rewrite the snapshot before it is stored, so a value whose
+/// serialization they change won't round trip.
+///
+/// ```
+/// #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+/// struct Search {
+///     term: String,
+///     limit: u32,
+/// }
+///
+/// let search = Search { term: String::from("by-term"), limit: 10 };
+/// archetype::snap_roundtrip("roundtrip-example", &search);
+/// ```
+pub fn snap_roundtrip<T>(key: &str, value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let subject = crate::json::pretty(value, &crate::Settings::current());
+    if let Err(failure) = crate::check(key, subject, false) {
+        let mut failure = failure.to_string();
+        if let Some(Err(err)) = crate::stored(key).map(|stored| serde_json::from_str::<T>(&stored))
+        {
+            failure.push_str(&format!(
+                "\nthe stored snapshot no longer deserializes as {}: {}",
+                std::any::type_name::<T>(),
+                err
+            ));
+        }
+        panic!("{}", failure);
+    }
+    // A missing snapshot that was only reported, as in a dry run, has
+    // nothing to deserialize.
+    let Some(stored) = crate::stored(key) else {
+        return;
+    };
+    let deserialized: T = serde_json::from_str(&stored).unwrap_or_else(|err| {
+        panic!(
+            "snapshot for {} doesn't deserialize as {}: {}",
+            key,
+            std::any::type_name::<T>(),
+            err
+        )
+    });
+    if deserialized != *value {
+        panic!(
+            "snapshot for {} deserializes as {:?}, not {:?}",
+            key, deserialized, value
+        );
+    }
+}
+
+/// Check that every snapshot stored under a key starting with `prefix`
+/// still deserializes as `T`, failing with each one that doesn't and why.
+///
+/// Snapshots recorded by earlier versions are a corpus of what was once
+/// written, so checking that they all still parse catches changes that
+/// would break reading old data, even after the snapshots themselves have
+/// been updated. Keep the goldens for past versions under their own
+/// keys, such as `searches/v1`, rather than updating them in place.
+///
+/// Panics if nothing is stored under `prefix`, which is more likely a
+/// mistyped prefix than an empty corpus.
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Search {
+///     term: String,
+/// }
+///
+/// archetype::snap_json("corpus-example/v1", &serde_json::json!({ "term": "by-term" }));
+/// archetype::assert_corpus::<Search>("corpus-example/");
+/// ```
+pub fn assert_corpus<T: DeserializeOwned>(prefix: &str) {
+    let storage = crate::config::global().snapshot_storage();
+    let (checked, broken) = corpus::<T>(&*storage, prefix).unwrap_or_else(|err| {
+        panic!(
+            "unable to read snapshots under {} ({:?}): {}",
+            prefix,
+            err.kind(),
+            err
+        )
+    });
+    if checked == 0 {
+        panic!("no snapshots are stored under {}", prefix);
+    }
+    if !broken.is_empty() {
+        let mut failure = format!(
+            "{} of {} snapshots under {} no longer deserialize as {}:",
+            broken.len(),
+            checked,
+            prefix,
+            std::any::type_name::<T>()
+        );
+        for (location, err) in broken {
+            failure.push_str(&format!("\n  {}: {}", location, err));
+        }
+        panic!("{}", failure);
+    }
+}
+
+/// Check that the snapshots stored under each of `keys` still
+/// deserialize as `T`, failing with each one that is missing or doesn't
+/// and why. This is the check `snap_contract_test!` makes of the goldens
+/// pinned for older versions.
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Search {
+///     term: String,
+/// }
+///
+/// archetype::snap_json("pinned-example/v1", &serde_json::json!({ "term": "by-term" }));
+/// archetype::assert_pinned::<Search>(&["pinned-example/v1"]);
+/// ```
+pub fn assert_pinned<T: DeserializeOwned>(keys: &[&str]) {
+    let mut broken = Vec::new();
+    for key in keys {
+        match crate::stored(key).map(|stored| serde_json::from_str::<T>(&stored)) {
+            None => broken.push(format!("{}: no snapshot is stored", key)),
+            Some(Err(err)) => broken.push(format!("{}: {}", key, err)),
+            Some(Ok(_)) => {}
+        }
+    }
+    if !broken.is_empty() {
+        panic!(
+            "{} of {} pinned snapshots no longer deserialize as {}:\n  {}",
+            broken.len(),
+            keys.len(),
+            std::any::type_name::<T>(),
+            broken.join("\n  ")
+        );
+    }
+}
+
+/// Deserialize every snapshot in `storage` under a key starting with
+/// `prefix` as `T`, returning how many were checked and the location of
+/// each that doesn't deserialize, with why. Links are skipped, as what
+/// they link to is checked in its own right.
+fn corpus<T: DeserializeOwned>(
+    storage: &dyn Storage,
+    prefix: &str,
+) -> io::Result<(usize, Vec<(String, serde_json::Error)>)> {
+    let mut checked = 0;
+    let mut broken = Vec::new();
+    for entry in crate::store::list_in(storage)? {
+        if !entry.key.starts_with(prefix) || entry.link.is_some() {
+            continue;
+        }
+        let Some(contents) = storage.read(&entry.key)? else {
+            continue;
+        };
+        checked += 1;
+        if let Err(err) = serde_json::from_str::<T>(crate::meta::split(&contents).1) {
+            broken.push((entry.location, err));
+        }
+    }
+    broken.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok((checked, broken))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{corpus, snap_roundtrip};
+    use crate::store::BundledStorage;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Search {
+        term: String,
+        ids: Vec<u32>,
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let search = Search {
+            term: String::from("by-ids"),
+            ids: vec![4, 7, 9],
+        };
+        snap_roundtrip("roundtrip_search", &search);
+    }
+
+    #[test]
+    fn lossy_serialization_fails() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Lossy {
+            #[serde(skip)]
+            cached: u32,
+        }
+
+        let failure = std::panic::catch_unwind(|| {
+            snap_roundtrip("roundtrip_lossy", &Lossy { cached: 3 });
+        })
+        .unwrap_err();
+        let failure = failure.downcast_ref::<String>().unwrap();
+        assert!(failure.starts_with("snapshot for roundtrip_lossy deserializes as"));
+    }
+
+    #[test]
+    fn corpus_lists_snapshots_that_no_longer_parse() {
+        let storage = BundledStorage::new()
+            .with("searches/v1", "{ \"term\": \"by-term\", \"ids\": [] }\n")
+            .with("searches/v2", "{ \"term\": \"by-ids\", \"ids\": [4] }\n")
+            .with("searches/v0", "{ \"query\": \"by-term\" }\n")
+            .with("searches/latest", "archetype-link: searches/v2\n")
+            .with("users", "[]\n");
+        let (checked, broken) = corpus::<Search>(&storage, "searches/").unwrap();
+        assert_eq!(checked, 3);
+        let broken: Vec<String> = broken
+            .iter()
+            .map(|(location, err)| format!("{}: {}", location, err))
+            .collect();
+        assert_eq!(
+            broken,
+            ["searches/v0 (bundled): missing field `term` at line 1 column 22"]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Snapshots of the rows returned by database queries.
+
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+/// What NULL values are printed as.
+const NULL: &str = "NULL";
+
+/// A row returned by a query, as taken by `snap_rows`.
+///
+/// It is already implemented for maps and lists of column names to
+/// optional values, and for JSON objects, such as rows selected with
+/// `row_to_json`, so most drivers' rows only need collecting into one of
+/// them first.
+pub trait Row {
+    /// The name of each column in the row, with its value, or `None`
+    /// where it is NULL.
+    fn columns(&self) -> Vec<(String, Option<String>)>;
+}
+
+impl<K: Display, V: Display> Row for BTreeMap<K, Option<V>> {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        columns(self)
+    }
+}
+
+impl<K: Display, V: Display> Row for HashMap<K, Option<V>> {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        columns(self)
+    }
+}
+
+impl<K: Display, V: Display> Row for [(K, Option<V>)] {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        columns(self.iter().map(|(name, value)| (name, value)))
+    }
+}
+
+impl<K: Display, V: Display> Row for Vec<(K, Option<V>)> {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        self.as_slice().columns()
+    }
+}
+
+impl Row for serde_json::Map<String, Value> {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        self.iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Null => None,
+                    Value::String(value) => Some(value.clone()),
+                    value => Some(value.to_string()),
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+impl<T: Row + ?Sized> Row for &T {
+    fn columns(&self) -> Vec<(String, Option<String>)> {
+        (**self).columns()
+    }
+}
+
+fn columns<'a, K: Display + 'a, V: Display + 'a>(
+    row: impl IntoIterator<Item = (&'a K, &'a Option<V>)>,
+) -> Vec<(String, Option<String>)> {
+    row.into_iter()
+        .map(|(name, value)| (name.to_string(), value.as_ref().map(ToString::to_string)))
+        .collect()
+}
+
+/// Take a snapshot of the rows returned by a query under a file with the
+/// name `key`, printed as a table.
+///
+/// The columns are ordered by name, so that the order they're selected
+/// or stored in doesn't matter, and are padded to the width of their
+/// widest value. NULL values are printed as `NULL`, and a column missing
+/// from a row is left blank. The rows are kept in the order given, so
+/// queries should be ordered to snapshot reliably.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// let rows = vec![
+///     BTreeMap::from([("id", Some("1")), ("name", Some("Ada")), ("email", None)]),
+///     BTreeMap::from([("id", Some("2")), ("name", Some("Grace")), ("email", Some("grace@navy.mil"))]),
+/// ];
+/// archetype::snap_rows("rows-example", rows);
+/// ```
+pub fn snap_rows<R: Row>(key: &str, rows: impl IntoIterator<Item = R>) {
+    crate::snap(key, render(rows));
+}
+
+fn render<R: Row>(rows: impl IntoIterator<Item = R>) -> String {
+    let rows: Vec<BTreeMap<String, String>> = rows
+        .into_iter()
+        .map(|row| {
+            row.columns()
+                .into_iter()
+                .map(|(name, value)| (name, value.map_or_else(|| NULL.to_string(), escape)))
+                .collect()
+        })
+        .collect();
+    if rows.is_empty() {
+        return String::from("(0 rows)\n");
+    }
+    let names: BTreeSet<&String> = rows.iter().flat_map(|row| row.keys()).collect();
+    let widths: Vec<usize> = names
+        .iter()
+        .map(|&name| {
+            rows.iter()
+                .filter_map(|row| row.get(name))
+                .chain([name])
+                .map(|value| value.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        cells.join(" | ").trim_end().to_string() + "\n"
+    };
+    let mut out = line(names.iter().map(|name| name.as_str()).collect());
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    out.push_str(&rule.join("-+-"));
+    out.push('\n');
+    for row in &rows {
+        out.push_str(&line(
+            names
+                .iter()
+                .map(|&name| row.get(name).map_or("", String::as_str))
+                .collect(),
+        ));
+    }
+    out.push_str(&match rows.len() {
+        1 => String::from("(1 row)\n"),
+        count => format!("({} rows)\n", count),
+    });
+    out
+}
+
+/// `value` with the characters that would break the table's lines
+/// escaped.
+fn escape(value: String) -> String {
+    match value.contains(['\n', '\r', '\t']) {
+        true => value
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t"),
+        false => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use std::collections::HashMap;
+
+    #[test]
+    fn renders_rows_as_a_table() {
+        let rows = vec![
+            vec![("name", Some("Ada")), ("id", Some("1")), ("note", None)],
+            vec![("name", Some("Grace Hopper")), ("id", Some("12"))],
+            vec![
+                ("id", Some("3")),
+                ("note", Some("two\nlines")),
+                ("name", None),
+            ],
+        ];
+        assert_eq!(
+            render(&rows),
+            "id | name         | note\n\
+             ---+--------------+-----------\n\
+             1  | Ada          | NULL\n\
+             12 | Grace Hopper |\n\
+             3  | NULL         | two\\nlines\n\
+             (3 rows)\n"
+        );
+    }
+
+    #[test]
+    fn renders_json_and_maps() {
+        let json = serde_json::json!({ "id": 1, "active": true, "name": null });
+        let row = json.as_object().unwrap();
+        assert_eq!(
+            render([row]),
+            "active | id | name\n-------+----+-----\ntrue   | 1  | NULL\n(1 row)\n"
+        );
+
+        let rows: Vec<HashMap<&str, Option<u32>>> = Vec::new();
+        assert_eq!(render(rows), "(0 rows)\n");
+    }
+}
@@ -0,0 +1,159 @@
+//! Running fixtures in a controlled environment.
+
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+/// Held while a sandbox has the environment, so sandboxes don't clobber
+/// each other's.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// A controlled environment to run a fixture in, so output that depends
+/// on the timezone, the locale, or anything else in the environment is
+/// the same wherever the test runs.
+///
+/// By default `TZ` is `UTC` and `LANG` and `LC_ALL` are `C`. The
+/// environment is restored once the fixture returns or panics.
+///
+/// The environment belongs to the whole process, so while sandboxes are
+/// run one at a time, tests that don't use one can still observe it if
+/// they run at the same time. Keep what runs inside to the fixture, and
+/// snapshot what it returns outside.
+///
+/// Worse, changing the environment while another thread reads it through
+/// the C library, as `getenv` or `localtime` do, is undefined behaviour
+/// on platforms other than Windows, and the test harness runs tests on
+/// threads of their own. A sandbox is only sound in a test binary where
+/// nothing else runs at the same time, such as one with `harness = false`
+/// or run with `--test-threads=1`.
+///
+/// ```
+/// use archetype::Sandbox;
+///
+/// let report = Sandbox::new()
+///     .var("REGION", "nz")
+///     .run(|| format!("{} in {}", std::env::var("TZ").unwrap(), std::env::var("REGION").unwrap()));
+/// archetype::snap("sandbox-example", report);
+/// ```
+#[must_use = "the fixture is only run by `run`"]
+#[derive(Clone, Debug)]
+pub struct Sandbox {
+    clear: bool,
+    vars: Vec<(OsString, Option<OsString>)>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Sandbox {
+        Sandbox {
+            clear: false,
+            vars: Vec::new(),
+        }
+        .var("TZ", "UTC")
+        .var("LANG", "C")
+        .var("LC_ALL", "C")
+    }
+}
+
+impl Sandbox {
+    /// Create a sandbox with the default environment.
+    pub fn new() -> Sandbox {
+        Sandbox::default()
+    }
+
+    /// Clear every variable other than those set with `var`, along with
+    /// `PATH`, which most fixtures that run programs need.
+    pub fn clear(mut self) -> Sandbox {
+        self.clear = true;
+        self
+    }
+
+    /// Set the variable `name` to `value`.
+    pub fn var(mut self, name: impl Into<OsString>, value: impl Into<OsString>) -> Sandbox {
+        let name = name.into();
+        self.vars.retain(|(other, _)| *other != name);
+        self.vars.push((name, Some(value.into())));
+        self
+    }
+
+    /// Remove the variable `name`.
+    pub fn remove(mut self, name: impl Into<OsString>) -> Sandbox {
+        let name = name.into();
+        self.vars.retain(|(other, _)| *other != name);
+        self.vars.push((name, None));
+        self
+    }
+
+    /// Run `fixture` in the sandbox, returning what it returns.
+    pub fn run<R>(&self, fixture: impl FnOnce() -> R) -> R {
+        // The configuration is read from the environment the first time
+        // it's needed, which had better not be while it's sandboxed.
+        crate::config::global();
+        let lock = ENVIRONMENT.lock().unwrap_or_else(|err| err.into_inner());
+        let saved: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+        let changes = self.changes(&saved);
+        let _restore = Restore { saved, _lock: lock };
+        for (name, value) in changes {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        fixture()
+    }
+
+    /// The variables to set, or remove when `None`, to sandbox the
+    /// environment `current`.
+    fn changes(&self, current: &[(OsString, OsString)]) -> Vec<(OsString, Option<OsString>)> {
+        let mut changes: Vec<(OsString, Option<OsString>)> = Vec::new();
+        if self.clear {
+            changes.extend(
+                current
+                    .iter()
+                    .filter(|(name, _)| name != "PATH")
+                    .map(|(name, _)| (name.clone(), None)),
+            );
+        }
+        changes.extend(self.vars.iter().cloned());
+        changes
+    }
+}
+
+/// Puts the environment back as it was when dropped.
+struct Restore<'a> {
+    saved: Vec<(OsString, OsString)>,
+    _lock: std::sync::MutexGuard<'a, ()>,
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        for (name, _) in std::env::vars_os() {
+            if !self.saved.iter().any(|(saved, _)| *saved == name) {
+                std::env::remove_var(name);
+            }
+        }
+        for (name, value) in &self.saved {
+            std::env::set_var(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sandbox;
+    use std::ffi::OsString;
+
+    #[test]
+    fn clearing_keeps_path() {
+        let os = |name: &str| OsString::from(name);
+        let current = [(os("HOME"), os("/home/ada")), (os("PATH"), os("/bin"))];
+        let changes = Sandbox::new().clear().remove("TZ").changes(&current);
+        assert_eq!(
+            changes,
+            [
+                (os("HOME"), None),
+                (os("LANG"), Some(os("C"))),
+                (os("LC_ALL"), Some(os("C"))),
+                (os("TZ"), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Snapshots of JSON Schema documents derived via `schemars`.
+
+use schemars::JsonSchema;
+
+/// Take a snapshot of the JSON Schema for `A` under a file with the
+/// name `key`.
+///
+/// Fixtures only ever exercise the fields they happen to populate, so a
+/// change to an optional or rarely set field can slip past `snap_json`.
+/// Snapshotting the schema itself pins the full wire format of the type.
+///
+/// ```
+/// #[derive(schemars::JsonSchema)]
+/// struct Search {
+///     term: String,
+///     limit: Option<u32>,
+/// }
+///
+/// archetype::snap_schema::<Search>("search-schema");
+/// ```
+pub fn snap_schema<A: JsonSchema>(key: &str) {
+    crate::snap_json(key, &schemars::schema_for!(A));
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[serde(rename_all = "kebab-case", tag = "type")]
+    enum Search {
+        ByTerm { term: String },
+        ByIds { ids: Vec<i64> },
+    }
+
+    #[test]
+    fn snapshot_search_schema() {
+        crate::snap_schema::<Search>("search_schema");
+    }
+}
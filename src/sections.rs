@@ -0,0 +1,153 @@
+//! Snapshots composed of named sections.
+
+/// A snapshot built from named sections, such as the headers and body of
+/// a response, stored one after another under a delimiter naming each:
+///
+/// ```text
+/// === headers ===
+/// content-type: application/json
+/// === body ===
+/// {"ids": [4, 7, 9]}
+/// ```
+///
+/// The whole snapshot is asserted at once, but a mismatch also names the
+/// sections that differ, so a failure in a large composite snapshot is
+/// easy to place.
+///
+/// ```
+/// archetype::Sections::new("sections-example")
+///     .section("headers", "content-type: application/json")
+///     .section("body", r#"{"ids": [4, 7, 9]}"#)
+///     .snap();
+/// ```
+#[must_use = "the snapshot is only asserted by `snap`"]
+#[derive(Clone, Debug)]
+pub struct Sections {
+    key: String,
+    sections: Vec<(String, String)>,
+}
+
+impl Sections {
+    /// Start a snapshot under `key` with no sections.
+    pub fn new(key: impl Into<String>) -> Sections {
+        Sections {
+            key: key.into(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Append a section called `name` holding `contents`.
+    pub fn section(mut self, name: impl Into<String>, contents: impl Into<String>) -> Sections {
+        self.sections.push((name.into(), contents.into()));
+        self
+    }
+
+    /// Assert the sections against the snapshot under the key, exactly as
+    /// `snap` would, naming the sections that differ on a mismatch.
+    pub fn snap(self) {
+        let subject = render(&self.sections);
+        let (config, settings) = crate::overrides::resolve(&self.key);
+        let config = &*config;
+        let (storage, _) = crate::storage(config, &settings);
+        let (_, stored) = crate::read(&*storage, &self.key).unwrap_or_else(|err| panic!("{}", err));
+        if let Err(failure) = crate::check(&self.key, subject.clone(), false) {
+            let stored = parse(crate::meta::split(stored.as_deref().unwrap_or_default()).1);
+            let subject = parse(&crate::transform::apply(config, &settings, subject));
+            match differing(&stored, &subject).as_slice() {
+                [] => panic!("{}", failure),
+                names => panic!("{}\nsections that differ: {}", failure, names.join(", ")),
+            }
+        }
+    }
+}
+
+fn delimiter(name: &str) -> String {
+    format!("=== {} ===", name)
+}
+
+/// Write out `sections` under their delimiters.
+fn render(sections: &[(String, String)]) -> String {
+    let mut out = String::new();
+    for (name, contents) in sections {
+        out.push_str(&delimiter(name));
+        out.push('\n');
+        out.push_str(contents);
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Split a rendered snapshot back into its sections.
+fn parse(snapshot: &str) -> Vec<(String, String)> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    for line in snapshot.split_inclusive('\n') {
+        let name = line
+            .trim_end()
+            .strip_prefix("=== ")
+            .and_then(|line| line.strip_suffix(" ==="));
+        match (name, sections.last_mut()) {
+            (Some(name), _) => sections.push((name.to_string(), String::new())),
+            (None, Some((_, contents))) => contents.push_str(line),
+            (None, None) => sections.push((String::new(), line.to_string())),
+        }
+    }
+    sections
+}
+
+/// The names of the sections that were added, removed, or changed, in the
+/// order they appear.
+fn differing(stored: &[(String, String)], subject: &[(String, String)]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (name, _) in stored.iter().chain(subject) {
+        let find = |sections: &[(String, String)]| {
+            sections
+                .iter()
+                .find(|(other, _)| other == name)
+                .map(|(_, contents)| contents.clone())
+        };
+        if find(stored) != find(subject) && !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{differing, parse, render, Sections};
+
+    fn sections(sections: &[(&str, &str)]) -> Vec<(String, String)> {
+        sections
+            .iter()
+            .map(|(name, contents)| (name.to_string(), contents.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_reverses_render() {
+        let rendered = render(&sections(&[("headers", "a: 1\nb: 2"), ("body", "{}\n")]));
+        assert_eq!(rendered, "=== headers ===\na: 1\nb: 2\n=== body ===\n{}\n");
+        assert_eq!(
+            parse(&rendered),
+            sections(&[("headers", "a: 1\nb: 2\n"), ("body", "{}\n")])
+        );
+    }
+
+    #[test]
+    fn differing_names_changed_sections() {
+        let stored = sections(&[("headers", "a\n"), ("body", "b\n"), ("gone", "c\n")]);
+        let subject = sections(&[("headers", "a\n"), ("body", "x\n"), ("new", "d\n")]);
+        assert_eq!(differing(&stored, &subject), vec!["body", "gone", "new"]);
+    }
+
+    #[test]
+    fn snapshot_sections() {
+        Sections::new("sections_response")
+            .section("status", "200 OK")
+            .section("headers", "content-type: application/json")
+            .section("body", r#"{"ids": [4, 7, 9]}"#)
+            .snap();
+    }
+}
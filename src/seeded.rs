@@ -0,0 +1,77 @@
+//! Snapshots of fixtures that draw random numbers, replayed from the seed
+//! recorded alongside them.
+
+use crate::Settings;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+
+/// Run `fixture` with a seeded RNG, and take a snapshot of what it
+/// returns under a file with the name `key`, recording the seed in the
+/// snapshot's metadata as `archetype-seed`.
+///
+/// The seed is the one in the settings, if `Settings::seed` set one,
+/// otherwise the one recorded in the stored snapshot, so the fixture
+/// replays the same draws each run, otherwise a random one. `rand`'s
+/// thread RNG can't be seeded from outside, so the fixture must draw from
+/// the RNG it's given for its output to be reproducible.
+///
+/// ```
+/// use rand::Rng;
+///
+/// archetype::snap_seeded("seeded-example", |rng| {
+///     let id: u32 = rng.gen_range(1000..10000);
+///     format!("order-{}\n", id)
+/// });
+/// ```
+pub fn snap_seeded(key: &str, fixture: impl FnOnce(&mut StdRng) -> String) {
+    let settings = Settings::current();
+    let seed = settings
+        .seed
+        .or_else(|| stored(key))
+        .unwrap_or_else(rand::random);
+    let subject = fixture(&mut StdRng::seed_from_u64(seed));
+    settings.seed(seed).bind(|| crate::snap(key, subject));
+}
+
+/// Like `snap_seeded`, but takes a snapshot of what `fixture` returns
+/// pretty printed as JSON, as `snap_json` would.
+///
+/// ```
+/// use rand::seq::SliceRandom;
+///
+/// archetype::snap_json_seeded("json-seeded-example", |rng| {
+///     let mut deck = vec!["ace", "king", "queen", "jack"];
+///     deck.shuffle(rng);
+///     deck
+/// });
+/// ```
+pub fn snap_json_seeded<A: Serialize>(key: &str, fixture: impl FnOnce(&mut StdRng) -> A) {
+    snap_seeded(key, |rng| {
+        crate::json::pretty(&fixture(rng), &Settings::current())
+    });
+}
+
+/// The seed recorded in the snapshot stored under `key`, if there is one.
+fn stored(key: &str) -> Option<u64> {
+    let (config, settings) = crate::overrides::resolve(key);
+    let (storage, _) = crate::storage(&config, &settings);
+    let (_, stored) = crate::read(&*storage, key).unwrap_or_else(|err| panic!("{}", err));
+    let (meta, _) = crate::meta::split(stored.as_deref()?);
+    meta.get("seed")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snap_json_seeded, stored};
+    use rand::Rng;
+
+    #[test]
+    fn seeds_are_replayed() {
+        // The stored snapshot was recorded with seed 7.
+        assert_eq!(stored("seeded_sample"), Some(7));
+        snap_json_seeded("seeded_sample", |rng| {
+            (0..5).map(|_| rng.gen_range(0..100)).collect::<Vec<u32>>()
+        });
+    }
+}
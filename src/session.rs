@@ -0,0 +1,140 @@
+//! Assertions whose failures are deferred and reported together.
+
+use crate::{compare, Settings};
+use serde::Serialize;
+
+/// Asserts several snapshots, deferring their failures, and then fails
+/// once with every one of them and its diff.
+///
+/// A test that covers many outputs would otherwise stop at the first that
+/// mismatches, hiding the rest until it is fixed. The failures are
+/// reported by `finish`, or when the session is dropped without it.
+///
+/// ```
+/// let mut session = archetype::SnapshotSession::new();
+/// for rate in [0, 10] {
+///     session.snap_json(&format!("session-example-{}", rate), &(100 - rate));
+/// }
+/// session.finish();
+/// ```
+#[must_use = "failures are only reported by `finish` or when dropped"]
+#[derive(Debug, Default)]
+pub struct SnapshotSession {
+    checked: usize,
+    failures: Vec<String>,
+}
+
+impl SnapshotSession {
+    /// Start a session with nothing asserted.
+    pub fn new() -> SnapshotSession {
+        SnapshotSession::default()
+    }
+
+    /// Assert `subject` against the snapshot under `key`, as `snap` would,
+    /// deferring a failure until the session finishes.
+    pub fn snap(&mut self, key: &str, subject: String) -> &mut SnapshotSession {
+        self.checked += 1;
+        if let Err(failure) = crate::check(key, subject.clone(), false) {
+            match diff(key, &subject) {
+                Some(diff) => self.failures.push(format!("{}\n{}", failure, diff)),
+                None => self.failures.push(failure.to_string()),
+            }
+        }
+        self
+    }
+
+    /// Assert `subject` as JSON against the snapshot under `key`, as
+    /// `snap_json` would, deferring a failure until the session finishes.
+    pub fn snap_json<A: Serialize>(&mut self, key: &str, subject: &A) -> &mut SnapshotSession {
+        self.snap(key, crate::json::pretty(subject, &Settings::current()))
+    }
+
+    /// The failures so far.
+    pub fn failures(&self) -> &[String] {
+        &self.failures
+    }
+
+    /// Fail with every deferred failure, if there were any.
+    pub fn finish(mut self) {
+        self.report();
+    }
+
+    fn report(&mut self) {
+        if self.failures.is_empty() {
+            return;
+        }
+        let failures = std::mem::take(&mut self.failures);
+        panic!(
+            "{} of {} snapshots failed:\n{}",
+            failures.len(),
+            self.checked,
+            failures.join("\n\n")
+        );
+    }
+}
+
+impl Drop for SnapshotSession {
+    fn drop(&mut self) {
+        // Panicking while already unwinding would abort the test binary.
+        if !std::thread::panicking() {
+            self.report();
+        }
+    }
+}
+
+/// Render how `subject` differs from the snapshot under `key`, if it
+/// does.
+fn diff(key: &str, subject: &str) -> Option<String> {
+    let (config, settings) = crate::overrides::resolve(key);
+    let config = &*config;
+    let (storage, _) = crate::storage(config, &settings);
+    let (key, stored) = crate::read(&*storage, key).unwrap_or_else(|err| panic!("{}", err));
+    let stored = stored?;
+    let stored = crate::meta::split(&stored).1;
+    let subject = crate::transform::apply(config, &settings, subject.to_string());
+    let mismatch = compare::compare(&settings, stored, &subject)?;
+    Some(mismatch.render(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotSession;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn failures_are_reported_together() {
+        let failure = std::panic::catch_unwind(|| {
+            // Shared snapshots are never written, even when updating.
+            crate::Settings::new()
+                .shared_root(crate::snapshot_dir!())
+                .verbosity(crate::Verbosity::Quiet)
+                .bind(|| {
+                    let mut session = SnapshotSession::new();
+                    session
+                        .snap_json("search_by_ids", &[9, 7, 4])
+                        .snap("search_by_nothing", String::from("nothing\n"))
+                        .snap_json("search_by_term", &"not a search");
+                    assert_eq!(session.failures().len(), 3);
+                    session.finish();
+                })
+        })
+        .unwrap_err();
+        let failure = failure.downcast_ref::<String>().unwrap();
+        assert!(failure.starts_with("3 of 3 snapshots failed:\nsnapshot mismatch at"));
+        assert!(failure.contains("snapshot missing for search_by_nothing"));
+        assert!(failure.contains(" search_by_term "));
+    }
+
+    #[test]
+    fn dropped_sessions_report_failures() {
+        let session = SnapshotSession {
+            checked: 1,
+            failures: vec![String::from("snapshot mismatch at search")],
+        };
+        let failure = std::panic::catch_unwind(move || drop(session)).unwrap_err();
+        assert_eq!(
+            failure.downcast_ref::<String>().unwrap(),
+            "1 of 1 snapshots failed:\nsnapshot mismatch at search"
+        );
+    }
+}
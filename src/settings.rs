@@ -0,0 +1,543 @@
+//! Settings that control how snapshots are compared and reported.
+//!
+//! Settings are scoped to the current thread. Build a `Settings` value
+//! and `bind` it around the assertions it should apply to; anything
+//! outside of a `bind` sees the defaults.
+
+use crate::code::Formatter;
+use crate::transform::SnapshotTransform;
+use std::cell::RefCell;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(feature = "clock")]
+use std::time::SystemTime;
+
+thread_local! {
+    static CURRENT: RefCell<Settings> = RefCell::new(Settings::default());
+}
+
+/// The algorithm used to diff a stored snapshot against a new one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Myers' algorithm. Fast, and a good default for most output.
+    #[default]
+    Myers,
+    /// Patience diffing. Anchors on unique lines, so blocks of JSON that
+    /// were reordered produce far more readable diffs.
+    Patience,
+    /// Longest common subsequence. Slow, but occasionally minimal where
+    /// Myers is not.
+    Lcs,
+}
+
+impl From<Algorithm> for similar::Algorithm {
+    fn from(algorithm: Algorithm) -> similar::Algorithm {
+        match algorithm {
+            Algorithm::Myers => similar::Algorithm::Myers,
+            Algorithm::Patience => similar::Algorithm::Patience,
+            Algorithm::Lcs => similar::Algorithm::Lcs,
+        }
+    }
+}
+
+/// What it means for a new snapshot to match the stored one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Comparison {
+    /// The two snapshots must be identical, or equivalent as JSON under
+    /// the ignored and unordered paths and `$matches` patterns, or the
+    /// stored one must match as a template with `{{…}}` placeholders.
+    /// This is the default.
+    #[default]
+    Lines,
+    /// The two snapshots must be identical. JSON equivalence and
+    /// templates are not considered, so a snapshot only matches if it's
+    /// exactly what's stored.
+    Bytes,
+    /// The similarity ratio of the line diff, between `0.0` and `1.0`,
+    /// must be at least the given threshold. Differences are tolerated
+    /// so long as the snapshots are "close enough".
+    Similarity(f32),
+}
+
+impl Comparison {
+    /// Whether `subject` is accepted as matching `stored`. The diff ratio
+    /// is only computed when it is needed.
+    pub(crate) fn accepts(&self, stored: &str, subject: &str, ratio: impl FnOnce() -> f32) -> bool {
+        match *self {
+            // Two texts with identical lines are identical texts, and an
+            // exact comparison avoids the rounding in an `f32` ratio that
+            // can hide a single changed line in a very large snapshot.
+            Comparison::Lines => stored == subject,
+            Comparison::Bytes => stored.as_bytes() == subject.as_bytes(),
+            Comparison::Similarity(threshold) => ratio() >= threshold,
+        }
+    }
+}
+
+/// How a mismatch is laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffStyle {
+    /// Deleted and inserted lines interleaved in one column. This is the
+    /// default.
+    #[default]
+    Interleaved,
+    /// The stored snapshot and the new one in two columns, with changed
+    /// lines aligned, which is easier to read for wide output. The
+    /// columns fit the width set with `Settings::diff_width`.
+    SideBySide,
+}
+
+/// The characters mismatches are framed with. Unicode box drawing reads
+/// best, but renders badly in some CI log viewers and Windows consoles,
+/// where `Frame::ASCII` does better.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The corner that opens the frame.
+    pub top: char,
+    /// The corner that closes the frame.
+    pub bottom: char,
+    /// The rule either side of the key, as the frame opens and closes.
+    pub rule: char,
+    /// The gutter between each line's sign and the line itself.
+    pub gutter: char,
+    /// Marks where a line too long for its column was cut short.
+    pub ellipsis: char,
+}
+
+impl Frame {
+    /// Box drawing characters. This is the default.
+    pub const UNICODE: Frame = Frame {
+        top: '┏',
+        bottom: '┗',
+        rule: '━',
+        gutter: '┃',
+        ellipsis: '…',
+    };
+
+    /// Nothing but ASCII.
+    pub const ASCII: Frame = Frame {
+        top: '+',
+        bottom: '+',
+        rule: '-',
+        gutter: '|',
+        ellipsis: '~',
+    };
+
+    /// `Frame::ASCII` if `ARCHETYPE_ASCII` is `1` or `true`, otherwise the
+    /// default.
+    fn from_env() -> Frame {
+        match std::env::var("ARCHETYPE_ASCII").as_deref() {
+            Ok("1" | "true") => Frame::ASCII,
+            _ => Frame::default(),
+        }
+    }
+
+    /// The line that opens a frame around the mismatch for `key`.
+    pub(crate) fn open(&self, key: &str) -> String {
+        self.line(self.top, key)
+    }
+
+    /// The line that closes a frame around the mismatch for `key`.
+    pub(crate) fn close(&self, key: &str) -> String {
+        self.line(self.bottom, key)
+    }
+
+    fn line(&self, corner: char, key: &str) -> String {
+        let rule = |len| self.rule.to_string().repeat(len);
+        format!(" {}{} {} {}", corner, rule(8), key, rule(5))
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Frame {
+        Frame::UNICODE
+    }
+}
+
+/// How `snap_bench` and `snap_json_bench` round the measurements in
+/// benchmark and profiling summaries, so run-to-run noise doesn't fail
+/// the snapshot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// Round to this many significant figures, so `26.251` becomes `26`
+    /// at two. This is the default, at two.
+    Significant(u32),
+    /// Round to the nearest multiple of this, so `26.251` becomes `25` at
+    /// `5.0`.
+    Multiple(f64),
+    /// Round to the nearest power of this, on a log scale, so `26.251`
+    /// becomes `32` at `2.0`. This only catches changes of an order of
+    /// magnitude, and is left to the noisiest measurements.
+    Powers(f64),
+}
+
+impl Default for Rounding {
+    fn default() -> Rounding {
+        Rounding::Significant(2)
+    }
+}
+
+/// How much is reported when a snapshot doesn't match.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only the assertion's own message, without a diff.
+    Quiet,
+    /// The diff along with the assertion's message. This is the default.
+    #[default]
+    Normal,
+    /// The diff along with where the snapshot is stored, how similar the
+    /// two snapshots are, and the normalization applied before comparing.
+    Verbose,
+}
+
+impl Verbosity {
+    /// The verbosity named by `ARCHETYPE_VERBOSITY`, one of `quiet`,
+    /// `normal`, or `verbose`, or the default if it isn't set.
+    ///
+    /// Panics if the variable names anything else.
+    fn from_env() -> Verbosity {
+        match std::env::var("ARCHETYPE_VERBOSITY").as_deref() {
+            Err(_) | Ok("normal") => Verbosity::Normal,
+            Ok("quiet") => Verbosity::Quiet,
+            Ok("verbose") => Verbosity::Verbose,
+            Ok(other) => panic!(
+                "ARCHETYPE_VERBOSITY should be quiet, normal, or verbose, not {:?}",
+                other
+            ),
+        }
+    }
+}
+
+/// A set of options applied to every snapshot assertion made while it
+/// is bound.
+///
+/// ```
+/// use archetype::{Algorithm, Settings};
+/// use std::time::Duration;
+///
+/// Settings::new()
+///     .algorithm(Algorithm::Patience)
+///     .timeout(Duration::from_secs(5))
+///     .bind(|| {
+///         archetype::snap_json("hello-world", &String::from("hello-world"));
+///     });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) comparison: Comparison,
+    pub(crate) ignore_paths: Vec<String>,
+    pub(crate) unordered_paths: Vec<String>,
+    pub(crate) json_indent: usize,
+    pub(crate) json_array_width: usize,
+    pub(crate) json_max_depth: Option<usize>,
+    pub(crate) json_max_items: Option<usize>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) verbosity: Verbosity,
+    pub(crate) diff_style: DiffStyle,
+    pub(crate) diff_width: Option<usize>,
+    pub(crate) frame: Frame,
+    pub(crate) json_summary: bool,
+    pub(crate) formatters: Vec<(String, Formatter)>,
+    pub(crate) transforms: Vec<SnapshotTransform>,
+    pub(crate) rounding: Rounding,
+    #[cfg(feature = "clock")]
+    pub(crate) frozen: Option<SystemTime>,
+    #[cfg(feature = "rand")]
+    pub(crate) seed: Option<u64>,
+    #[cfg(feature = "fs")]
+    pub(crate) shared_root: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            algorithm: Algorithm::default(),
+            comparison: Comparison::default(),
+            timeout: None,
+            deadline: None,
+            ignore_paths: Vec::new(),
+            unordered_paths: Vec::new(),
+            json_indent: 2,
+            json_array_width: 0,
+            json_max_depth: None,
+            json_max_items: None,
+            verbosity: Verbosity::from_env(),
+            diff_style: DiffStyle::default(),
+            diff_width: None,
+            frame: Frame::from_env(),
+            json_summary: false,
+            formatters: Vec::new(),
+            transforms: crate::transform::defaults(),
+            rounding: Rounding::default(),
+            #[cfg(feature = "clock")]
+            frozen: None,
+            #[cfg(feature = "rand")]
+            seed: None,
+            #[cfg(feature = "fs")]
+            shared_root: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Create settings with every option at its default.
+    pub fn new() -> Settings {
+        Settings::default()
+    }
+
+    /// Use `algorithm` when diffing snapshots.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Settings {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Decide whether snapshots match according to `comparison`.
+    pub fn comparison(mut self, comparison: Comparison) -> Settings {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Disregard differences at the JSON pointer `path` when comparing
+    /// JSON snapshots. The value is still stored for reviewers, it just
+    /// isn't enforced. Tokens in the pointer may be globs, so
+    /// `/items/*/id` covers the `id` of every item.
+    pub fn ignore_path(mut self, path: impl Into<String>) -> Settings {
+        self.ignore_paths.push(path.into());
+        self
+    }
+
+    /// Compare the JSON array at `path` as a set, so that a snapshot
+    /// matches whatever order its elements come back in. Tokens in the
+    /// pointer may be globs, as with `ignore_path`.
+    pub fn unordered_path(mut self, path: impl Into<String>) -> Settings {
+        self.unordered_paths.push(path.into());
+        self
+    }
+
+    /// Indent JSON snapshots by `indent` spaces per level. Defaults to 2.
+    pub fn json_indent(mut self, indent: usize) -> Settings {
+        self.json_indent = indent;
+        self
+    }
+
+    /// Print JSON arrays of scalars on a single line, as in `[4, 7, 9]`,
+    /// when that line fits within `width` columns. Otherwise each element
+    /// gets its own line. Defaults to 0, which never collapses arrays.
+    pub fn json_array_width(mut self, width: usize) -> Settings {
+        self.json_array_width = width;
+        self
+    }
+
+    /// Elide what lies more than `depth` levels below the root of JSON
+    /// snapshots, replacing each array or object there with a marker of
+    /// its size, such as `"... object with 3 keys"`, so snapshots of deep
+    /// structures stay reviewable while still failing if the size changes.
+    /// Keys are then printed in sorted order. Defaults to no limit.
+    pub fn json_max_depth(mut self, depth: usize) -> Settings {
+        self.json_max_depth = Some(depth);
+        self
+    }
+
+    /// Keep only the first `items` items of arrays in JSON snapshots,
+    /// replacing the rest with a marker of how many there were, such as
+    /// `"... 950 more items"`. Keys are then printed in sorted order.
+    /// Defaults to no limit.
+    pub fn json_max_items(mut self, items: usize) -> Settings {
+        self.json_max_items = Some(items);
+        self
+    }
+
+    /// Verify against the snapshots in `dir` rather than this crate's
+    /// own, typically the canonical goldens owned by another crate as
+    /// published with `snapshot_dir!`. Shared snapshots are never
+    /// written, so missing and mismatched snapshots always fail.
+    ///
+    /// ```no_run
+    /// # mod upstream { pub const GOLDENS: &str = archetype::snapshot_dir!(); }
+    /// archetype::Settings::new()
+    ///     .shared_root(upstream::GOLDENS)
+    ///     .bind(|| archetype::snap_json("search_by_term", &"..."));
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn shared_root(mut self, dir: impl Into<PathBuf>) -> Settings {
+        self.shared_root = Some(dir.into());
+        self
+    }
+
+    /// Give up refining a diff after `timeout` has elapsed. The diff is
+    /// still reported, but may be coarser than it would otherwise be.
+    /// Without a timeout pathological inputs can hang the test run.
+    pub fn timeout(mut self, timeout: Duration) -> Settings {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up diffing altogether after `deadline` has elapsed, and report
+    /// only that the contents differ, along with their sizes and hashes.
+    /// Unlike `timeout`, which still reports a coarse diff, this bounds
+    /// the time spent on pathological snapshots such as megabytes of JSON
+    /// on a single line, where even a coarse diff is no use to anyone.
+    pub fn deadline(mut self, deadline: Duration) -> Settings {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Report mismatches at `verbosity`. Defaults to `ARCHETYPE_VERBOSITY`
+    /// if it is set, otherwise `Verbosity::Normal`.
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Settings {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Lay mismatches out in `style`.
+    pub fn diff_style(mut self, style: DiffStyle) -> Settings {
+        self.diff_style = style;
+        self
+    }
+
+    /// Fit side-by-side diffs within `width` columns. Defaults to
+    /// `COLUMNS` if it is set, otherwise 120.
+    pub fn diff_width(mut self, width: usize) -> Settings {
+        self.diff_width = Some(width);
+        self
+    }
+
+    /// Summarize mismatched JSON snapshots above the diff, listing the
+    /// JSON pointer of each value that differs and how, such as
+    /// `/items/3/price: 10 -> 12`. Defaults to `false`.
+    pub fn json_summary(mut self, summary: bool) -> Settings {
+        self.json_summary = summary;
+        self
+    }
+
+    /// Round the measurements in the summaries taken by `snap_bench` and
+    /// `snap_json_bench` as `rounding` does. Defaults to two significant
+    /// figures.
+    pub fn rounding(mut self, rounding: Rounding) -> Settings {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Freeze `archetype::clock` at `at`, and redact timestamps of `at`
+    /// from snapshots as `[now]`. Timestamps before the Unix epoch aren't
+    /// redacted.
+    #[cfg(feature = "clock")]
+    pub fn freeze_time(mut self, at: SystemTime) -> Settings {
+        self.frozen = Some(at);
+        self
+    }
+
+    /// Seed the RNG that `snap_seeded` hands fixtures with `seed`, rather
+    /// than the seed recorded in the stored snapshot, and record it in the
+    /// metadata of each snapshot written.
+    #[cfg(feature = "rand")]
+    pub fn seed(mut self, seed: u64) -> Settings {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Frame mismatches with the characters in `frame`. Defaults to
+    /// `Frame::ASCII` if `ARCHETYPE_ASCII=1`, otherwise `Frame::UNICODE`.
+    pub fn frame(mut self, frame: Frame) -> Settings {
+        self.frame = frame;
+        self
+    }
+
+    /// The width side-by-side diffs are fit within.
+    pub(crate) fn width(&self) -> usize {
+        self.diff_width
+            .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+            .unwrap_or(120)
+    }
+
+    /// Format source code in the language `lang` with `formatter` before
+    /// it is snapshotted with `snap_code`, replacing any formatter already
+    /// registered for it.
+    pub fn formatter(mut self, lang: impl Into<String>, formatter: Formatter) -> Settings {
+        self.formatters.push((lang.into(), formatter));
+        self
+    }
+
+    /// Add `transform` to the end of the pipeline snapshots pass through
+    /// before they are compared or stored.
+    pub fn transform(mut self, transform: SnapshotTransform) -> Settings {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Replace the pipeline snapshots pass through before they are
+    /// compared or stored with `transforms`, in order. Defaults to
+    /// `SnapshotTransform::filters` then `SnapshotTransform::redact`.
+    pub fn transforms(
+        mut self,
+        transforms: impl IntoIterator<Item = SnapshotTransform>,
+    ) -> Settings {
+        self.transforms = transforms.into_iter().collect();
+        self
+    }
+
+    /// Run `f` with these settings applied to the current thread,
+    /// restoring the previous settings afterwards, even if `f` panics.
+    pub fn bind<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Settings>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                if let Some(previous) = self.0.take() {
+                    CURRENT.with(|current| *current.borrow_mut() = previous);
+                }
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(self.clone()));
+        let _restore = Restore(Some(previous));
+        f()
+    }
+
+    /// The settings bound to the current thread.
+    pub(crate) fn current() -> Settings {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Algorithm, Comparison, Settings};
+
+    #[test]
+    fn comparison_accepts() {
+        assert!(Comparison::Lines.accepts("a\n", "a\n", || unreachable!()));
+        assert!(!Comparison::Lines.accepts("a\n", "b\n", || unreachable!()));
+        assert!(Comparison::Bytes.accepts("a\n", "a\n", || unreachable!()));
+        assert!(!Comparison::Bytes.accepts("a\r\n", "a\n", || unreachable!()));
+        assert!(Comparison::Similarity(0.9).accepts("", "", || 0.95));
+        assert!(!Comparison::Similarity(0.9).accepts("", "", || 0.85));
+    }
+
+    #[test]
+    fn bind_restores_previous_settings() {
+        assert_eq!(Settings::current().algorithm, Algorithm::Myers);
+        Settings::new().algorithm(Algorithm::Patience).bind(|| {
+            assert_eq!(Settings::current().algorithm, Algorithm::Patience);
+            Settings::new().algorithm(Algorithm::Lcs).bind(|| {
+                assert_eq!(Settings::current().algorithm, Algorithm::Lcs);
+            });
+            assert_eq!(Settings::current().algorithm, Algorithm::Patience);
+        });
+        assert_eq!(Settings::current().algorithm, Algorithm::Myers);
+    }
+
+    #[test]
+    fn bind_restores_after_panic() {
+        let result = std::panic::catch_unwind(|| {
+            Settings::new()
+                .algorithm(Algorithm::Lcs)
+                .bind(|| panic!("boom"))
+        });
+        assert!(result.is_err());
+        assert_eq!(Settings::current().algorithm, Algorithm::Myers);
+    }
+}
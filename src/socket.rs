@@ -0,0 +1,138 @@
+//! Mismatches streamed to a local socket, for review tools.
+//!
+//! With a socket configured, each mismatch is written to it as it
+//! happens, as a line of JSON, so a GUI review tool or editor plugin
+//! listening on it can present failures live during `cargo test` rather
+//! than scraping its output:
+//!
+//! ```json
+//! {"event":"snapshot_mismatched","key":"search","path":"/repo/snapshots/search.snap","diff":"--- a/...","stored":"by-ids\n","new":"by-term\n","provisional":false}
+//! ```
+
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// The address connected to, and the connection to it, unless it
+/// couldn't be made or has since been closed.
+type Connection = (String, Option<Box<dyn Write + Send>>);
+
+static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+#[derive(Serialize)]
+struct Mismatch<'a> {
+    event: &'static str,
+    key: &'a str,
+    path: &'a str,
+    diff: String,
+    stored: &'a str,
+    new: &'a str,
+    provisional: bool,
+}
+
+/// Send the mismatch of `subject` against `stored`, the snapshot under
+/// `key` at `path`, to `address`.
+///
+/// The connection is made on the first mismatch and kept for the rest
+/// of the run. If it can't be made, or is closed, that's reported once,
+/// and nothing more is sent, as the assertion is what fails the test.
+pub(crate) fn mismatched(
+    address: &str,
+    key: &str,
+    path: &str,
+    stored: &str,
+    subject: &str,
+    provisional: bool,
+) {
+    let mismatch = Mismatch {
+        event: "snapshot_mismatched",
+        key,
+        path,
+        diff: crate::patch(path, stored, subject),
+        stored,
+        new: subject,
+        provisional,
+    };
+    let mut line = serde_json::to_string(&mismatch).expect("should serialize");
+    line.push('\n');
+
+    let mut connection = CONNECTION.lock().unwrap_or_else(|err| err.into_inner());
+    if connection.as_ref().is_none_or(|(to, _)| to != address) {
+        let stream = connect(address).map_err(|err| warn(address, err)).ok();
+        *connection = Some((address.to_string(), stream));
+    }
+    let Some((_, Some(stream))) = connection.as_mut() else {
+        return;
+    };
+    if let Err(err) = stream
+        .write_all(line.as_bytes())
+        .and_then(|_| stream.flush())
+    {
+        warn(address, err);
+        *connection = Some((address.to_string(), None));
+    }
+}
+
+/// Connect to `address`: `tcp://` followed by a host and port, or else
+/// the path of a Unix domain socket, or of a named pipe on Windows.
+fn connect(address: &str) -> io::Result<Box<dyn Write + Send>> {
+    if let Some(address) = address.strip_prefix("tcp://") {
+        return Ok(Box::new(std::net::TcpStream::connect(address)?));
+    }
+    #[cfg(unix)]
+    return Ok(Box::new(std::os::unix::net::UnixStream::connect(address)?));
+    // A named pipe, such as `\\.\pipe\archetype`, is opened as a file.
+    #[cfg(not(unix))]
+    Ok(Box::new(
+        std::fs::OpenOptions::new().write(true).open(address)?,
+    ))
+}
+
+fn warn(address: &str, err: io::Error) {
+    crate::output::print(&format!(
+        "unable to send mismatches to {}, so no more will be sent: {}",
+        address, err
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mismatched;
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn mismatches_are_sent_as_lines_of_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        mismatched(
+            &address,
+            "search",
+            "search.snap",
+            "by-ids\n",
+            "by-term\n",
+            false,
+        );
+        mismatched(&address, "users", "users.snap", "ada\n", "bob\n", true);
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let first: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(
+            first,
+            json!({
+                "event": "snapshot_mismatched",
+                "key": "search",
+                "path": "search.snap",
+                "diff": "--- a/search.snap\n+++ b/search.snap\n@@ -1 +1 @@\n-by-ids\n+by-term\n",
+                "stored": "by-ids\n",
+                "new": "by-term\n",
+                "provisional": false,
+            })
+        );
+        let second: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(second["key"], "users");
+        assert_eq!(second["provisional"], true);
+    }
+}
@@ -0,0 +1,137 @@
+//! Snapshots of the contents of spreadsheets.
+//!
+//! Spreadsheets are zipped XML, which records when and by what they were
+//! saved and compresses differently from one save to the next, so they
+//! are stored as CSV instead, a section per sheet.
+
+use crate::Sections;
+use calamine::{CellType, Data, Range, Reader};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+/// The cells of a sheet that hold anything, by row and column from `A1`.
+type Cells = BTreeMap<(u32, u32), String>;
+
+/// Take a snapshot of an XLSX, XLSB, XLS, or ODS spreadsheet under a file
+/// with the name `key`, as CSV.
+///
+/// Each sheet's values are a section named after the sheet, and, if any
+/// of its cells hold formulas, those formulas are a section named after
+/// the sheet followed by `formulas`, with each formula in the cell it's
+/// in, written as the format writes them. Both are laid out from `A1`, so
+/// moving cells shows up too, with the trailing empty cells of each row
+/// and the trailing empty rows left off. Formatting, such as fonts and
+/// widths, isn't stored.
+///
+/// Panics if `spreadsheet` isn't a spreadsheet that can be read.
+///
+/// ```no_run
+/// let export = std::fs::read("target/export.xlsx").unwrap();
+/// archetype::snap_spreadsheet("export", &export);
+/// ```
+pub fn snap_spreadsheet(key: &str, spreadsheet: &[u8]) {
+    let unreadable = |err: calamine::Error| -> ! {
+        panic!(
+            "unable to read spreadsheet to snapshot for {}: {}",
+            key, err
+        )
+    };
+    let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(spreadsheet))
+        .unwrap_or_else(|err| unreadable(err));
+    let mut sections = Sections::new(key);
+    for sheet in workbook.sheet_names() {
+        let values = workbook
+            .worksheet_range(&sheet)
+            .unwrap_or_else(|err| unreadable(err));
+        sections = sections.section(&sheet, csv(&cells(&values, Data::to_string)));
+        let formulas = workbook
+            .worksheet_formula(&sheet)
+            .unwrap_or_else(|err| unreadable(err));
+        let formulas = cells(&formulas, |formula| {
+            // ODS qualifies formulas with their syntax, as `of:=SUM(...)`.
+            let formula = formula.strip_prefix("of:").unwrap_or(formula);
+            match formula.starts_with('=') {
+                true => formula.to_string(),
+                false => format!("={}", formula),
+            }
+        });
+        if !formulas.is_empty() {
+            sections = sections.section(format!("{} formulas", sheet), csv(&formulas));
+        }
+    }
+    sections.snap();
+}
+
+/// The cells of `range` that hold anything, printed with `print`.
+fn cells<T: CellType>(range: &Range<T>, print: impl Fn(&T) -> String) -> Cells {
+    let Some((top, left)) = range.start() else {
+        return Cells::new();
+    };
+    range
+        .used_cells()
+        .map(|(row, column, cell)| ((top + row as u32, left + column as u32), print(cell)))
+        .filter(|(_, printed)| !printed.is_empty())
+        .collect()
+}
+
+/// `cells` as CSV, from `A1` to the last row and column holding anything.
+fn csv(cells: &Cells) -> String {
+    let Some(&(last, _)) = cells.keys().next_back() else {
+        return String::new();
+    };
+    let mut out = String::new();
+    for row in 0..=last {
+        let columns: Vec<(&(u32, u32), &String)> =
+            cells.range((row, 0)..=(row, u32::MAX)).collect();
+        let width = columns.last().map_or(0, |((_, column), _)| column + 1);
+        let fields: Vec<String> = (0..width)
+            .map(|column| {
+                cells
+                    .get(&(row, column))
+                    .map_or(String::new(), |cell| field(cell))
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// `cell` as a CSV field, quoted if it holds anything CSV gives meaning
+/// to, or whitespace at either end that would otherwise be lost.
+fn field(cell: &str) -> String {
+    let quoted = cell.contains([',', '"', '\n', '\r']) || cell.trim() != cell;
+    match quoted {
+        true => format!("\"{}\"", cell.replace('"', "\"\"")),
+        false => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv, field, snap_spreadsheet, Cells};
+
+    #[test]
+    fn sheets_are_stored_as_csv() {
+        snap_spreadsheet(
+            "spreadsheet_xlsx",
+            include_bytes!("../tests/fixtures/budget.xlsx"),
+        );
+        snap_spreadsheet(
+            "spreadsheet_ods",
+            include_bytes!("../tests/fixtures/budget.ods"),
+        );
+    }
+
+    #[test]
+    fn csv_is_laid_out_from_a1() {
+        let cells = Cells::from([
+            ((1, 1), String::from("Say \"hi\"")),
+            ((1, 3), String::from("true")),
+            ((3, 0), String::from(" padded")),
+        ]);
+        assert_eq!(csv(&cells), "\n,\"Say \"\"hi\"\"\",,true\n\n\" padded\"\n");
+        assert_eq!(csv(&Cells::new()), "");
+        assert_eq!(field("Rent, monthly"), "\"Rent, monthly\"");
+    }
+}
@@ -0,0 +1,866 @@
+//! Where snapshots are kept.
+//!
+//! By default snapshots are files in the snapshot directory, but any
+//! `Storage` can be configured with `GlobalConfig::storage`, e.g. to keep
+//! snapshots in memory or in a host-provided store on targets without a
+//! filesystem.
+//!
+//! When many tests expect identical output, rather than storing a copy
+//! for each, a snapshot can contain nothing but a link to another:
+//!
+//! ```text
+//! archetype-link: search/by-term
+//! ```
+//!
+//! Asserting against it asserts against the linked snapshot instead, and
+//! updating it updates the linked snapshot, so the shared output is kept
+//! in one place.
+//!
+//! On `wasm32-unknown-unknown` there is no filesystem to read from, so
+//! build without the default `fs` feature and bundle the snapshots into
+//! the test binary instead. Bundled snapshots can only be verified, never
+//! written, so record them natively first:
+//!
+//! ```
+//! use archetype::store::BundledStorage;
+//! use archetype::GlobalConfig;
+//!
+//! archetype::configure(GlobalConfig::new().storage(
+//!     BundledStorage::new()
+//!         .with("search_by_term", include_str!("../snapshots/search_by_term.snap")),
+//! ))
+//! .ok();
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+#[cfg(feature = "fs")]
+use std::fs;
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "fs")]
+mod addressed;
+mod routed;
+
+#[cfg(feature = "fs")]
+pub use addressed::ContentAddressedStorage;
+pub use routed::RoutedStorage;
+
+/// A place snapshots can be read from and written to by key.
+pub trait Storage: Debug + Send + Sync {
+    /// Read the snapshot stored under `key`, or `None` if there isn't one.
+    fn read(&self, key: &str) -> io::Result<Option<String>>;
+
+    /// Store `contents` under `key`, replacing any existing snapshot.
+    fn write(&self, key: &str, contents: &str) -> io::Result<()>;
+
+    /// Where the snapshot under `key` is stored, for messages.
+    fn location(&self, key: &str) -> String {
+        key.to_string()
+    }
+
+    /// Whether snapshots can be written at all. When they can't, missing
+    /// snapshots fail as they would in CI.
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// Remove the snapshot under `key`, if there is one.
+    fn remove(&self, _key: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this storage can't remove snapshots",
+        ))
+    }
+
+    /// The key of every stored snapshot, in order.
+    fn keys(&self) -> io::Result<Vec<String>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this storage can't list snapshots",
+        ))
+    }
+}
+
+/// Rename the snapshot under `old` to `new` in `storage`, along with any
+/// links to it, so renaming a fixture doesn't mean deleting and
+/// re-recording its snapshot.
+///
+/// Fails if there is no snapshot under `old` or there already is one
+/// under `new`.
+pub fn rename(storage: &dyn Storage, old: &str, new: &str) -> io::Result<()> {
+    let contents = existing(storage, old)?;
+    if storage.read(new)?.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a snapshot already exists under {}", new),
+        ));
+    }
+    replace(storage, old, new, &contents)
+}
+
+/// The snapshot under `key` in `storage`, which must exist.
+fn existing(storage: &dyn Storage, key: &str) -> io::Result<String> {
+    storage.read(key)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no snapshot under {}", key),
+        )
+    })
+}
+
+/// Write `contents`, the snapshot under `old`, over whatever is under
+/// `new`, then remove `old` and point its links at `new`.
+fn replace(storage: &dyn Storage, old: &str, new: &str, contents: &str) -> io::Result<()> {
+    storage.write(new, contents)?;
+    storage.remove(old)?;
+    for key in storage.keys()? {
+        let Some(contents) = storage.read(&key)? else {
+            continue;
+        };
+        let target = contents.strip_prefix(LINK).map(str::trim);
+        if target == Some(old) {
+            storage.write(&key, &format!("{} {}\n", LINK, new))?;
+        }
+    }
+    Ok(())
+}
+
+/// Lock the snapshot under `key` in `storage` so that updates never
+/// rewrite it unless forced with `GlobalConfig::force`, or unlock it. This
+/// protects blessed snapshots, such as API contracts, from being accepted
+/// wholesale along with every other change.
+pub fn lock(storage: &dyn Storage, key: &str, locked: bool) -> io::Result<()> {
+    let Some(contents) = storage.read(key)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no snapshot under {}", key),
+        ));
+    };
+    let (mut meta, body) = crate::meta::split(&contents);
+    if locked {
+        meta.set("locked", "true");
+    } else {
+        meta.remove("locked");
+    }
+    storage.write(key, &meta.join(body))
+}
+
+/// A stored snapshot, as listed by `list`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The key the snapshot is stored under.
+    pub key: String,
+    /// Where it is stored, which for files is its path.
+    pub location: String,
+    /// The size of the stored snapshot in bytes, including any metadata.
+    pub size: usize,
+    /// The fields of its metadata header, such as `updated-by`, in order.
+    pub metadata: Vec<(String, String)>,
+    /// The key of the snapshot it links to, if it is a link.
+    pub link: Option<String>,
+}
+
+/// List every snapshot in the configured storage, in key order, for
+/// tooling such as dashboards and pre-commit hooks that inspects
+/// snapshots without working out where they are kept.
+pub fn list() -> io::Result<Vec<Entry>> {
+    list_in(&*crate::config::global().snapshot_storage())
+}
+
+/// Like `list`, but for the snapshots in `storage`.
+pub fn list_in(storage: &dyn Storage) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for key in storage.keys()? {
+        let Some(contents) = storage.read(&key)? else {
+            continue;
+        };
+        let link = contents
+            .strip_prefix(LINK)
+            .map(|target| target.trim().to_string());
+        let (meta, _) = crate::meta::split(&contents);
+        entries.push(Entry {
+            location: storage.location(&key),
+            size: contents.len(),
+            metadata: meta.fields().to_vec(),
+            link,
+            key,
+        });
+    }
+    Ok(entries)
+}
+
+/// How similar a snapshot must be to an orphaned one to be suggested as
+/// its rename.
+const RENAMED: f32 = 0.8;
+
+/// A snapshot that looks like it was orphaned by renaming its test and
+/// recorded again under the new key. See `renames`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rename {
+    /// The key of the orphaned snapshot.
+    pub old: String,
+    /// The key of the snapshot that looks like it replaced it.
+    pub new: String,
+    /// How similar the two are, from 0 to 1.
+    pub similarity: f32,
+}
+
+impl Rename {
+    /// Replace the snapshot under `new` with the one under `old`, keeping
+    /// its metadata and links, as though the test had been renamed with
+    /// `rename` in the first place.
+    ///
+    /// Fails, leaving both as they are, if there is no longer a snapshot
+    /// under `old`, such as when the rename was already applied.
+    pub fn apply(&self, storage: &dyn Storage) -> io::Result<()> {
+        let contents = existing(storage, &self.old)?;
+        replace(storage, &self.old, &self.new, &contents)
+    }
+}
+
+/// Suggest renames between the snapshots in `storage` that none of the
+/// `referenced` keys asserted against, such as those in a coverage file,
+/// and the referenced snapshots whose contents are most like them, so a
+/// refactor that renames tests doesn't silently orphan their snapshots
+/// and leave near-duplicates behind. Each snapshot is suggested at most
+/// once, most similar first.
+pub fn renames<S: AsRef<str>>(
+    storage: &dyn Storage,
+    referenced: impl IntoIterator<Item = S>,
+) -> io::Result<Vec<Rename>> {
+    let referenced: BTreeSet<String> = referenced
+        .into_iter()
+        .map(|key| key.as_ref().to_string())
+        .collect();
+    let mut bodies = BTreeMap::new();
+    for key in storage.keys()? {
+        let Some(contents) = storage.read(&key)? else {
+            continue;
+        };
+        // Links are all alike and are moved along with their target.
+        if contents.starts_with(LINK) {
+            continue;
+        }
+        bodies.insert(key, crate::meta::split(&contents).1.to_string());
+    }
+    let (new, old): (Vec<_>, Vec<_>) = bodies
+        .iter()
+        .partition(|(key, _)| referenced.contains(*key));
+    let mut candidates = Vec::new();
+    for (old, old_body) in &old {
+        for (new, new_body) in &new {
+            let similarity = similar::TextDiff::from_lines(*old_body, *new_body).ratio();
+            if similarity >= RENAMED {
+                candidates.push(Rename {
+                    old: old.to_string(),
+                    new: new.to_string(),
+                    similarity,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    let mut taken = BTreeSet::new();
+    candidates.retain(|rename| {
+        let free = !taken.contains(&rename.old) && !taken.contains(&rename.new);
+        if free {
+            taken.insert(rename.old.clone());
+            taken.insert(rename.new.clone());
+        }
+        free
+    });
+    Ok(candidates)
+}
+
+/// How the snapshots in one storage differ from those in another, as
+/// found by `changes`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    /// The keys only in the second storage.
+    pub added: Vec<String>,
+    /// The keys only in the first storage.
+    pub removed: Vec<String>,
+    /// The keys in both whose snapshots differ, with how similar the two
+    /// are, from 0 to 1.
+    pub changed: Vec<(String, f32)>,
+}
+
+impl Changes {
+    /// Whether the two storages hold the same snapshots.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl std::fmt::Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for key in &self.added {
+            writeln!(f, "+ {}", key)?;
+        }
+        for key in &self.removed {
+            writeln!(f, "- {}", key)?;
+        }
+        for (key, similarity) in &self.changed {
+            writeln!(f, "~ {} ({:.0}% similar)", key, similarity * 100.0)?;
+        }
+        write!(
+            f,
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// Compare the snapshots in `before` with those in `after`, such as two
+/// snapshot directories checked out from different branches, listing
+/// the keys added, removed, and changed, each in key order. Metadata is
+/// disregarded, so only snapshots whose contents differ are changed.
+pub fn changes(before: &dyn Storage, after: &dyn Storage) -> io::Result<Changes> {
+    let bodies = |storage: &dyn Storage| -> io::Result<BTreeMap<String, String>> {
+        let mut bodies = BTreeMap::new();
+        for key in storage.keys()? {
+            if let Some(contents) = storage.read(&key)? {
+                bodies.insert(key, crate::meta::split(&contents).1.to_string());
+            }
+        }
+        Ok(bodies)
+    };
+    let (before, after) = (bodies(before)?, bodies(after)?);
+    let mut changes = Changes::default();
+    for (key, body) in &before {
+        match after.get(key) {
+            None => changes.removed.push(key.clone()),
+            Some(other) if other != body => {
+                let similarity = similar::TextDiff::from_lines(body, other).ratio();
+                changes.changed.push((key.clone(), similarity));
+            }
+            Some(_) => {}
+        }
+    }
+    changes.added = after
+        .into_keys()
+        .filter(|key| !before.contains_key(key))
+        .collect();
+    Ok(changes)
+}
+
+/// The key of every file ending in `.{extension}` under `dir`, relative
+/// to `root`, skipping the directory `skip`.
+#[cfg(feature = "fs")]
+fn walk_keys(
+    root: &Path,
+    dir: &Path,
+    extension: &str,
+    skip: Option<&Path>,
+    keys: &mut Vec<String>,
+) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            if Some(path.as_path()) != skip {
+                walk_keys(root, &path, extension, skip, keys)?;
+            }
+            continue;
+        }
+        let relative = path.strip_prefix(root).expect("walked within root");
+        let Some(relative) = relative.to_str() else {
+            continue;
+        };
+        let suffix = format!(".{}", extension);
+        if let Some(key) = relative.strip_suffix(&suffix) {
+            keys.push(key.replace(std::path::MAIN_SEPARATOR, "/"));
+        }
+    }
+    Ok(())
+}
+
+/// The prefix of a snapshot that links to another.
+const LINK: &str = "archetype-link:";
+
+/// The byte order mark some editors add to the start of UTF-8 files.
+const BOM: char = '\u{feff}';
+
+/// `contents` without a leading byte order mark, which is never part of a
+/// snapshot but would otherwise make its first line always differ.
+pub(crate) fn strip_bom(contents: String) -> String {
+    match contents.strip_prefix(BOM) {
+        Some(stripped) => stripped.to_string(),
+        None => contents,
+    }
+}
+
+/// Read the snapshot under `key`, following links, returning the key
+/// that was ultimately read along with its contents, less any byte order
+/// mark.
+pub(crate) fn read_linked(
+    storage: &dyn Storage,
+    key: &str,
+) -> io::Result<(String, Option<String>)> {
+    let mut key = key.to_string();
+    let mut seen = BTreeSet::new();
+    loop {
+        let contents = storage.read(&key)?.map(strip_bom);
+        let target = contents
+            .as_deref()
+            .and_then(|contents| contents.strip_prefix(LINK))
+            .map(|target| target.trim().to_string());
+        match target {
+            Some(target) => {
+                seen.insert(key);
+                if seen.contains(&target) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("snapshot links form a cycle through {}", target),
+                    ));
+                }
+                key = target;
+            }
+            None => return Ok((key, contents)),
+        }
+    }
+}
+
+/// A stable hash of `contents`, as hex. This is 64-bit FNV-1a, which is
+/// stable across platforms and releases, unlike `std`'s hasher, but isn't
+/// collision resistant, so whatever relies on it being unique checks.
+pub(crate) fn hash(contents: &str) -> String {
+    let hash = contents.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Snapshots bundled into the test binary, typically with `include_str!`,
+/// for verifying snapshots where there is no filesystem. Writing is not
+/// supported.
+#[derive(Clone, Debug, Default)]
+pub struct BundledStorage {
+    snapshots: BTreeMap<String, &'static str>,
+}
+
+impl BundledStorage {
+    /// Create an empty bundle.
+    pub fn new() -> BundledStorage {
+        BundledStorage::default()
+    }
+
+    /// Add `contents` to the bundle as the snapshot under `key`.
+    pub fn with(mut self, key: impl Into<String>, contents: &'static str) -> BundledStorage {
+        self.snapshots.insert(key.into(), contents);
+        self
+    }
+}
+
+impl Storage for BundledStorage {
+    fn read(&self, key: &str) -> io::Result<Option<String>> {
+        Ok(self.snapshots.get(key).map(|contents| contents.to_string()))
+    }
+
+    fn write(&self, _: &str, _: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "bundled snapshots are read-only",
+        ))
+    }
+
+    fn location(&self, key: &str) -> String {
+        format!("{} (bundled)", key)
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn keys(&self) -> io::Result<Vec<String>> {
+        Ok(self.snapshots.keys().cloned().collect())
+    }
+}
+
+/// The file at `path`, less any byte order mark, or `None` if there isn't
+/// one. A file that isn't valid UTF-8 fails to be read, giving the offset
+/// of the first invalid byte, unless it is read `lossy`, replacing what's
+/// invalid with U+FFFD and warning that it was.
+#[cfg(feature = "fs")]
+pub(crate) fn read_file(path: &Path, lossy: bool) -> io::Result<Option<String>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match String::from_utf8(bytes) {
+        Ok(contents) => Ok(Some(strip_bom(contents))),
+        Err(err) => {
+            let offset = err.utf8_error().valid_up_to();
+            if !lossy {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "invalid UTF-8 at byte {}; set lossy_utf8 to compare it lossily",
+                        offset
+                    ),
+                ));
+            }
+            crate::output::print(&format!(
+                "warning: {} is invalid UTF-8 at byte {}, so it is compared lossily",
+                path.display(),
+                offset
+            ));
+            let contents = String::from_utf8_lossy(err.as_bytes()).into_owned();
+            Ok(Some(strip_bom(contents)))
+        }
+    }
+}
+
+/// Snapshots stored as files named after their key in a directory.
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub struct FsStorage {
+    dir: PathBuf,
+    extension: String,
+    lossy: bool,
+}
+
+#[cfg(feature = "fs")]
+impl FsStorage {
+    /// Store snapshots in `dir` in files ending in `.{extension}`.
+    pub fn new(dir: impl Into<PathBuf>, extension: impl Into<String>) -> FsStorage {
+        FsStorage {
+            dir: dir.into(),
+            extension: extension.into(),
+            lossy: false,
+        }
+    }
+
+    /// Read snapshots that aren't valid UTF-8 lossily, rather than failing
+    /// to read them. See `GlobalConfig::lossy_utf8`.
+    pub fn lossy(mut self, lossy: bool) -> FsStorage {
+        self.lossy = lossy;
+        self
+    }
+
+    /// The path of the snapshot stored under `key`.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, self.extension))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Storage for FsStorage {
+    fn read(&self, key: &str) -> io::Result<Option<String>> {
+        read_file(&self.path(key), self.lossy)
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        let path = self.path(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.path(key).to_string_lossy().into_owned()
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        walk_keys(&self.dir, &self.dir, &self.extension, None, &mut keys)?;
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_linked, BundledStorage, Storage};
+
+    #[test]
+    fn byte_order_marks_are_stripped() {
+        let storage = BundledStorage::new()
+            .with("search", "\u{feff}by-term\n")
+            .with("linked", "\u{feff}archetype-link: search\n");
+        assert_eq!(
+            read_linked(&storage, "linked").unwrap(),
+            (String::from("search"), Some(String::from("by-term\n")))
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn byte_order_marks_are_stripped_from_objects() {
+        use super::ContentAddressedStorage;
+
+        let dir = std::env::temp_dir().join(format!("archetype-bom-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("objects")).unwrap();
+        std::fs::write(dir.join("objects").join("search"), "\u{feff}by-term\n").unwrap();
+        std::fs::write(dir.join("search.snap"), "archetype-object: search\n").unwrap();
+        let storage = ContentAddressedStorage::new(&dir, "snap");
+        assert_eq!(
+            storage.read("search").unwrap(),
+            Some(String::from("by-term\n"))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn invalid_utf8_is_located() {
+        use super::{ContentAddressedStorage, FsStorage};
+
+        let dir = std::env::temp_dir().join(format!("archetype-utf8-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("latin1.snap"), b"caf\xe9\n").unwrap();
+        let storage = FsStorage::new(&dir, "snap");
+        let err = storage.read("latin1").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("invalid UTF-8 at byte 3;"));
+        let storage = storage.lossy(true);
+        assert_eq!(
+            storage.read("latin1").unwrap(),
+            Some(String::from("caf\u{fffd}\n"))
+        );
+
+        // Content-addressed snapshots keep their contents in an object.
+        std::fs::create_dir_all(dir.join("objects")).unwrap();
+        std::fs::write(dir.join("objects").join("latin1"), b"caf\xe9\n").unwrap();
+        std::fs::write(dir.join("addressed.snap"), "archetype-object: latin1\n").unwrap();
+        let storage = ContentAddressedStorage::new(&dir, "snap");
+        let err = storage.read("addressed").unwrap_err();
+        assert!(err.to_string().starts_with("invalid UTF-8 at byte 3;"));
+        let storage = storage.lossy(true);
+        assert_eq!(
+            storage.read("addressed").unwrap(),
+            Some(String::from("caf\u{fffd}\n"))
+        );
+        std::fs::remove_file(dir.join("objects").join("latin1")).unwrap();
+        assert_eq!(
+            storage.read("addressed").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_linked_follows_links() {
+        let storage = BundledStorage::new()
+            .with("search", "by-term")
+            .with("search-again", "archetype-link: search\n")
+            .with("search-yet-again", "archetype-link: search-again")
+            .with("loop", "archetype-link: loop-back")
+            .with("loop-back", "archetype-link: loop");
+        assert_eq!(
+            read_linked(&storage, "search-yet-again").unwrap(),
+            (String::from("search"), Some(String::from("by-term")))
+        );
+        assert_eq!(
+            read_linked(&storage, "missing").unwrap(),
+            (String::from("missing"), None)
+        );
+        assert!(read_linked(&storage, "loop").is_err());
+    }
+
+    #[test]
+    fn bundled_storage_is_read_only() {
+        let storage = BundledStorage::new().with("search", "by-term");
+        assert_eq!(storage.read("search").unwrap().as_deref(), Some("by-term"));
+        assert_eq!(storage.read("missing").unwrap(), None);
+        assert!(storage.write("search", "by-ids").is_err());
+        assert!(!storage.writable());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn fs_storage_round_trip() {
+        use super::FsStorage;
+
+        let dir = std::env::temp_dir().join(format!("archetype-store-{}", std::process::id()));
+        let storage = FsStorage::new(dir.join("nested"), "golden");
+        assert_eq!(storage.read("search").unwrap(), None);
+        storage.write("search", "by-term").unwrap();
+        assert_eq!(storage.read("search").unwrap().as_deref(), Some("by-term"));
+        assert!(dir.join("nested/search.golden").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn rename_moves_snapshot_and_links() {
+        use super::{rename, FsStorage};
+
+        let dir = std::env::temp_dir().join(format!("archetype-rename-{}", std::process::id()));
+        let storage = FsStorage::new(&dir, "snap");
+        storage.write("search", "by-term").unwrap();
+        storage
+            .write("links/search", "archetype-link: search\n")
+            .unwrap();
+        storage.write("other", "by-ids").unwrap();
+
+        rename(&storage, "search", "search/by-term").unwrap();
+        assert_eq!(
+            storage.keys().unwrap(),
+            vec!["links/search", "other", "search/by-term"]
+        );
+        assert_eq!(
+            storage.read("links/search").unwrap().as_deref(),
+            Some("archetype-link: search/by-term\n")
+        );
+        assert!(rename(&storage, "search", "elsewhere").is_err());
+        assert!(rename(&storage, "other", "search/by-term").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn renames_are_only_applied_once() {
+        use super::{FsStorage, Rename};
+
+        let dir = std::env::temp_dir().join(format!("archetype-apply-{}", std::process::id()));
+        let storage = FsStorage::new(&dir, "snap");
+        storage
+            .write("search_by_term", "by-term\nlimit 10\n")
+            .unwrap();
+        storage
+            .write("search/by-term", "by-term\nlimit 20\n")
+            .unwrap();
+        storage
+            .write("links/search", "archetype-link: search_by_term\n")
+            .unwrap();
+        let rename = Rename {
+            old: String::from("search_by_term"),
+            new: String::from("search/by-term"),
+            similarity: 0.5,
+        };
+
+        rename.apply(&storage).unwrap();
+        assert_eq!(
+            storage.keys().unwrap(),
+            vec!["links/search", "search/by-term"]
+        );
+        assert_eq!(
+            storage.read("links/search").unwrap().as_deref(),
+            Some("archetype-link: search/by-term\n")
+        );
+        // Applying it again finds nothing to move, and leaves the snapshot
+        // it moved alone.
+        let err = rename.apply(&storage).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            storage.read("search/by-term").unwrap().as_deref(),
+            Some("by-term\nlimit 10\n")
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn list_describes_each_snapshot() {
+        use super::{list_in, Entry};
+
+        let storage = BundledStorage::new()
+            .with("search", "archetype-updated-by: ada\n---\nby-term\n")
+            .with("links/search", "archetype-link: search\n");
+        assert_eq!(
+            list_in(&storage).unwrap(),
+            vec![
+                Entry {
+                    key: String::from("links/search"),
+                    location: String::from("links/search (bundled)"),
+                    size: 23,
+                    metadata: Vec::new(),
+                    link: Some(String::from("search")),
+                },
+                Entry {
+                    key: String::from("search"),
+                    location: String::from("search (bundled)"),
+                    size: 38,
+                    metadata: vec![(String::from("updated-by"), String::from("ada"))],
+                    link: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn renames_pair_orphans_with_similar_snapshots() {
+        use super::renames;
+
+        let storage = BundledStorage::new()
+            .with(
+                "search_by_term",
+                "type: by-term\nterm: a\nlimit: 10\noffset: 0\nsort: asc\n",
+            )
+            .with(
+                "search/by-term",
+                "archetype-locked: true\n---\ntype: by-term\nterm: a\nlimit: 10\noffset: 0\nsort: desc\n",
+            )
+            .with("search/by-ids", "type: by-ids\nids: [4, 7, 9]\n")
+            .with("unrelated", "something else entirely\n")
+            .with("links/search", "archetype-link: search/by-term\n");
+        let suggested = renames(&storage, ["search/by-term", "search/by-ids"]).unwrap();
+        let [rename] = suggested.as_slice() else {
+            panic!("expected one rename, got {:?}", suggested);
+        };
+        assert_eq!(
+            (rename.old.as_str(), rename.new.as_str()),
+            ("search_by_term", "search/by-term")
+        );
+        // Four of the five lines match, a ratio of 8/10, which isn't
+        // exactly representable.
+        assert!((rename.similarity - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn changes_between_storages() {
+        use super::changes;
+
+        let before = BundledStorage::new()
+            .with("search", "by-term\nlimit 10\n")
+            .with("users", "[]\n")
+            .with("orders", "archetype-updated-by: ada\n---\n[]\n");
+        let after = BundledStorage::new()
+            .with("search", "by-term\nlimit 20\n")
+            .with("orders", "[]\n")
+            .with("products", "[]\n");
+        let changes = changes(&before, &after).unwrap();
+        assert_eq!(
+            changes.to_string(),
+            "+ products\n- users\n~ search (50% similar)\n1 added, 1 removed, 1 changed"
+        );
+        assert!(super::changes(&after, &after).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn lock_and_unlock() {
+        use super::{lock, FsStorage};
+
+        let dir = std::env::temp_dir().join(format!("archetype-lock-{}", std::process::id()));
+        let storage = FsStorage::new(&dir, "snap");
+        storage.write("contract", "{}\n").unwrap();
+        lock(&storage, "contract", true).unwrap();
+        assert_eq!(
+            storage.read("contract").unwrap().as_deref(),
+            Some("archetype-locked: true\n---\n{}\n")
+        );
+        lock(&storage, "contract", false).unwrap();
+        assert_eq!(storage.read("contract").unwrap().as_deref(), Some("{}\n"));
+        assert!(lock(&storage, "missing", true).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+//! Content-addressed snapshot storage.
+
+use super::{hash, read_file, walk_keys, Storage};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The prefix of a pointer file naming the object holding its snapshot.
+const OBJECT: &str = "archetype-object:";
+
+/// Snapshots stored by content, so identical snapshots are only stored
+/// once.
+///
+/// The body of each snapshot lives in `objects/` named by the hash of its
+/// contents, and each key has a small pointer file naming its object.
+/// When many tests share identical large outputs, this keeps the size of
+/// the repository down. Pointer files that instead hold a snapshot
+/// directly, as `FsStorage` writes them, are still read, so an existing
+/// snapshot directory can be migrated gradually. Keys under `objects/`
+/// are reserved, and can't be read or written.
+///
+/// Objects that are no longer pointed to are left behind until `gc` is
+/// run.
+#[derive(Clone, Debug)]
+pub struct ContentAddressedStorage {
+    dir: PathBuf,
+    extension: String,
+    lossy: bool,
+}
+
+impl ContentAddressedStorage {
+    /// Store pointer files in `dir`, ending in `.{extension}`, and
+    /// objects in `dir/objects`.
+    pub fn new(dir: impl Into<PathBuf>, extension: impl Into<String>) -> ContentAddressedStorage {
+        ContentAddressedStorage {
+            dir: dir.into(),
+            extension: extension.into(),
+            lossy: false,
+        }
+    }
+
+    /// Read snapshots that aren't valid UTF-8 lossily, rather than failing
+    /// to read them. See `GlobalConfig::lossy_utf8`.
+    pub fn lossy(mut self, lossy: bool) -> ContentAddressedStorage {
+        self.lossy = lossy;
+        self
+    }
+
+    /// The path of the pointer file for the snapshot under `key`.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, self.extension))
+    }
+
+    fn objects(&self) -> PathBuf {
+        self.dir.join("objects")
+    }
+
+    /// The path of the pointer file for the snapshot under `key`, unless
+    /// the key is under `objects/`, which is reserved for the objects.
+    fn pointer(&self, key: &str) -> io::Result<PathBuf> {
+        if key.starts_with("objects/") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is under objects/, which is reserved for objects", key),
+            ));
+        }
+        Ok(self.path(key))
+    }
+
+    /// Store `contents` as an object, unless it already is one, returning
+    /// its name. Objects are named by the hash of their contents, which is
+    /// short enough to collide, so an object already under the name is
+    /// checked, and a different one is told apart with a suffix.
+    fn store(&self, contents: &str) -> io::Result<String> {
+        let hash = hash(contents);
+        let mut suffix = 0;
+        loop {
+            let name = match suffix {
+                0 => hash.clone(),
+                n => format!("{}-{}", hash, n),
+            };
+            let object = self.objects().join(&name);
+            match fs::read(&object) {
+                Ok(existing) if existing == contents.as_bytes() => return Ok(name),
+                Ok(_) => suffix += 1,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    fs::create_dir_all(self.objects())?;
+                    fs::write(object, contents)?;
+                    return Ok(name);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Remove every object that no pointer file refers to, returning how
+    /// many were removed.
+    pub fn gc(&self) -> io::Result<usize> {
+        let mut live = BTreeSet::new();
+        self.pointers(&self.dir, &mut live)?;
+        let mut removed = 0;
+        let entries = match fs::read_dir(self.objects()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if !live.contains(&entry.file_name().to_string_lossy().into_owned()) {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Collect the objects named by every pointer file under `dir`.
+    fn pointers(&self, dir: &Path, live: &mut BTreeSet<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path != self.objects() {
+                    self.pointers(&path, live)?;
+                }
+            } else if path.extension().is_some_and(|ext| *ext == *self.extension) {
+                if let Some(hash) = object(&fs::read_to_string(&path)?) {
+                    live.insert(hash.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for ContentAddressedStorage {
+    fn read(&self, key: &str) -> io::Result<Option<String>> {
+        let Some(pointer) = read_file(&self.pointer(key)?, self.lossy)? else {
+            return Ok(None);
+        };
+        match object(&pointer) {
+            Some(hash) => {
+                // A pointer to a missing object is a broken snapshot, not a
+                // missing one.
+                let path = self.objects().join(hash);
+                match read_file(&path, self.lossy)? {
+                    Some(contents) => Ok(Some(contents)),
+                    None => Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("the object {} for {} is missing", hash, key),
+                    )),
+                }
+            }
+            None => Ok(Some(pointer)),
+        }
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        let path = self.pointer(key)?;
+        let object = self.store(contents)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, format!("{} {}\n", OBJECT, object))
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.path(key).to_string_lossy().into_owned()
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.pointer(key)?) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let objects = self.objects();
+        walk_keys(
+            &self.dir,
+            &self.dir,
+            &self.extension,
+            Some(&objects),
+            &mut keys,
+        )?;
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// The object named by a pointer file, if it is one.
+fn object(pointer: &str) -> Option<&str> {
+    pointer.strip_prefix(OBJECT).map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentAddressedStorage;
+    use crate::store::Storage;
+    use std::fs;
+
+    #[test]
+    fn content_addressed_round_trip() {
+        let dir = std::env::temp_dir().join(format!("archetype-addressed-{}", std::process::id()));
+        let storage = ContentAddressedStorage::new(&dir, "snap");
+        storage.write("a", "shared output").unwrap();
+        storage.write("nested/b", "shared output").unwrap();
+        storage.write("c", "other output").unwrap();
+        assert_eq!(storage.read("a").unwrap().as_deref(), Some("shared output"));
+        assert_eq!(
+            storage.read("nested/b").unwrap().as_deref(),
+            Some("shared output")
+        );
+        assert_eq!(storage.read("missing").unwrap(), None);
+        assert_eq!(fs::read_dir(dir.join("objects")).unwrap().count(), 2);
+
+        storage.write("c", "shared output").unwrap();
+        assert_eq!(storage.gc().unwrap(), 1);
+        assert_eq!(storage.read("c").unwrap().as_deref(), Some("shared output"));
+
+        fs::write(dir.join("plain.snap"), "stored directly").unwrap();
+        assert_eq!(
+            storage.read("plain").unwrap().as_deref(),
+            Some("stored directly")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn colliding_objects_are_told_apart() {
+        let dir = std::env::temp_dir().join(format!("archetype-collide-{}", std::process::id()));
+        let storage = ContentAddressedStorage::new(&dir, "snap");
+        // Another object already holds the name the output hashes to.
+        let hash = crate::store::hash("output");
+        fs::create_dir_all(dir.join("objects")).unwrap();
+        fs::write(dir.join("objects").join(&hash), "colliding").unwrap();
+
+        storage.write("a", "output").unwrap();
+        assert_eq!(storage.read("a").unwrap().as_deref(), Some("output"));
+        assert_eq!(
+            fs::read_to_string(dir.join("a.snap")).unwrap(),
+            format!("archetype-object: {}-1\n", hash)
+        );
+        storage.write("b", "output").unwrap();
+        assert_eq!(fs::read_dir(dir.join("objects")).unwrap().count(), 2);
+
+        let err = storage.write("objects/a", "output").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(storage.read("objects/a").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
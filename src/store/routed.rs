@@ -0,0 +1,145 @@
+use super::Storage;
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::Arc;
+
+/// Snapshots spread across several storages by key prefix, for
+/// repositories that keep golden data alongside each subsystem.
+///
+/// A key is stored in the storage routed from its longest matching
+/// prefix, under the rest of the key, or in the fallback storage if no
+/// prefix matches. Configure routes between directories with
+/// `GlobalConfig::route`.
+///
+/// ```
+/// use archetype::store::{BundledStorage, RoutedStorage};
+///
+/// let storage = RoutedStorage::new(BundledStorage::new())
+///     .route("api/", BundledStorage::new().with("users", "[]\n"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RoutedStorage {
+    routes: Vec<(String, Arc<dyn Storage>)>,
+    fallback: Arc<dyn Storage>,
+}
+
+impl RoutedStorage {
+    /// Store every snapshot in `fallback` until routes are added.
+    pub fn new(fallback: impl Storage + 'static) -> RoutedStorage {
+        RoutedStorage::from_arc(Arc::new(fallback))
+    }
+
+    pub(crate) fn from_arc(fallback: Arc<dyn Storage>) -> RoutedStorage {
+        RoutedStorage {
+            routes: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Store snapshots under keys starting with `prefix` in `storage`,
+    /// with the prefix removed.
+    pub fn route(
+        self,
+        prefix: impl Into<String>,
+        storage: impl Storage + 'static,
+    ) -> RoutedStorage {
+        self.route_arc(prefix, Arc::new(storage))
+    }
+
+    pub(crate) fn route_arc(
+        mut self,
+        prefix: impl Into<String>,
+        storage: Arc<dyn Storage>,
+    ) -> RoutedStorage {
+        self.routes.push((prefix.into(), storage));
+        self
+    }
+
+    /// The storage for `key` and the key within it.
+    fn resolve<'a>(&self, key: &'a str) -> (&dyn Storage, &'a str) {
+        self.routes
+            .iter()
+            .filter_map(|(prefix, storage)| Some((prefix, storage, key.strip_prefix(prefix)?)))
+            .max_by_key(|(prefix, _, _)| prefix.len())
+            .map_or((&*self.fallback, key), |(_, storage, rest)| {
+                (&**storage, rest)
+            })
+    }
+}
+
+impl Storage for RoutedStorage {
+    fn read(&self, key: &str) -> io::Result<Option<String>> {
+        let (storage, key) = self.resolve(key);
+        storage.read(key)
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        let (storage, key) = self.resolve(key);
+        storage.write(key, contents)
+    }
+
+    fn location(&self, key: &str) -> String {
+        let (storage, key) = self.resolve(key);
+        storage.location(key)
+    }
+
+    fn writable(&self) -> bool {
+        self.fallback.writable() && self.routes.iter().all(|(_, storage)| storage.writable())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        let (storage, key) = self.resolve(key);
+        storage.remove(key)
+    }
+
+    fn keys(&self) -> io::Result<Vec<String>> {
+        let mut keys = BTreeSet::new();
+        // Only keys that would be read back from the same storage count,
+        // so snapshots shadowed by a route aren't listed twice.
+        let mut add = |storage: &dyn Storage, key: String| {
+            if std::ptr::addr_eq(self.resolve(&key).0, storage) {
+                keys.insert(key);
+            }
+        };
+        for key in self.fallback.keys()? {
+            add(&*self.fallback, key);
+        }
+        for (prefix, storage) in &self.routes {
+            for key in storage.keys()? {
+                add(&**storage, format!("{}{}", prefix, key));
+            }
+        }
+        Ok(keys.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoutedStorage;
+    use crate::store::{BundledStorage, Storage};
+
+    #[test]
+    fn routes_by_longest_prefix() {
+        let storage = RoutedStorage::new(
+            BundledStorage::new()
+                .with("search", "s")
+                .with("api/users", "shadowed"),
+        )
+        .route(
+            "api/",
+            BundledStorage::new()
+                .with("users", "u")
+                .with("v2/users", "shadowed"),
+        )
+        .route("api/v2/", BundledStorage::new().with("users", "v2"));
+        assert_eq!(storage.read("search").unwrap().as_deref(), Some("s"));
+        assert_eq!(storage.read("api/users").unwrap().as_deref(), Some("u"));
+        assert_eq!(storage.read("api/v2/users").unwrap().as_deref(), Some("v2"));
+        assert_eq!(storage.location("api/users"), "users (bundled)");
+        assert_eq!(
+            storage.keys().unwrap(),
+            vec!["api/users", "api/v2/users", "search"]
+        );
+        assert!(!storage.writable());
+    }
+}
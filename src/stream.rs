@@ -0,0 +1,234 @@
+//! Snapshots of the events published to a stream, such as a Kafka topic.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// What offsets are replaced with.
+const OFFSET: &str = "[offset]";
+
+/// What timestamps are replaced with.
+const TIMESTAMP: &str = "[timestamp]";
+
+/// What the values at the paths passed to `EventStream::redact` are
+/// replaced with.
+const REDACTED: &str = "[redacted]";
+
+/// An event published to a stream: its topic, key, headers, and value,
+/// and where and when it was published.
+#[derive(Clone, Debug)]
+pub struct Event {
+    topic: String,
+    partition: Option<i32>,
+    offset: Option<i64>,
+    timestamp: Option<i64>,
+    key: Option<String>,
+    headers: Vec<(String, String)>,
+    value: Value,
+}
+
+impl Event {
+    /// An event published to `topic` holding `value`.
+    ///
+    /// Panics if `value` fails to serialize.
+    pub fn new(topic: impl Into<String>, value: impl Serialize) -> Event {
+        Event {
+            topic: topic.into(),
+            partition: None,
+            offset: None,
+            timestamp: None,
+            key: None,
+            headers: Vec::new(),
+            value: serde_json::to_value(value).expect("should serialize"),
+        }
+    }
+
+    /// Set the key the event was published under.
+    pub fn key(mut self, key: impl Into<String>) -> Event {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Add a header called `name` holding `value`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Event {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the partition the event was published to.
+    pub fn partition(mut self, partition: i32) -> Event {
+        self.partition = Some(partition);
+        self
+    }
+
+    /// Set the offset the event was published at. It's snapshotted as
+    /// `[offset]`, since it depends on what was published before.
+    pub fn offset(mut self, offset: i64) -> Event {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Set when the event was published. It's snapshotted as
+    /// `[timestamp]`, since it differs with every run.
+    pub fn timestamp(mut self, timestamp: i64) -> Event {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    fn to_value(&self) -> Value {
+        let mut event = Map::new();
+        event.insert(String::from("topic"), Value::from(self.topic.clone()));
+        if let Some(partition) = self.partition {
+            event.insert(String::from("partition"), Value::from(partition));
+        }
+        if self.offset.is_some() {
+            event.insert(String::from("offset"), Value::from(OFFSET));
+        }
+        if self.timestamp.is_some() {
+            event.insert(String::from("timestamp"), Value::from(TIMESTAMP));
+        }
+        if let Some(key) = &self.key {
+            event.insert(String::from("key"), Value::from(key.clone()));
+        }
+        if !self.headers.is_empty() {
+            event.insert(String::from("headers"), headers(&self.headers));
+        }
+        event.insert(String::from("value"), self.value.clone());
+        Value::Object(event)
+    }
+}
+
+/// `headers` as an object, with the values of a header repeated under
+/// the same name gathered into an array.
+fn headers(headers: &[(String, String)]) -> Value {
+    let mut object = Map::new();
+    for (name, value) in headers {
+        let value = Value::from(value.clone());
+        match object.get_mut(name) {
+            None => {
+                object.insert(name.clone(), value);
+            }
+            Some(Value::Array(values)) => values.push(value),
+            Some(first) => *first = Value::Array(vec![first.take(), value]),
+        }
+    }
+    Value::Object(object)
+}
+
+/// A snapshot of the events published to a stream, in the order they
+/// were published, for golden testing event-driven pipelines.
+///
+/// Each event is stored as JSON with its topic, partition, key, headers,
+/// and value. Offsets and timestamps vary from run to run, so they are
+/// stored as `[offset]` and `[timestamp]`, and anything else that varies
+/// can be redacted by path with `redact`.
+///
+/// ```
+/// use archetype::{Event, EventStream};
+///
+/// EventStream::new("stream-example")
+///     .redact("/headers/trace-id")
+///     .redact("/value/placed_at")
+///     .event(
+///         Event::new("orders", serde_json::json!({ "id": 7, "placed_at": "2024-03-01T09:30:00Z" }))
+///             .key("order-7")
+///             .header("trace-id", "4bf92f3577b34da6")
+///             .partition(0)
+///             .offset(1042)
+///             .timestamp(1_709_285_400_000),
+///     )
+///     .event(Event::new("shipments", serde_json::json!({ "order": 7 })).key("order-7"))
+///     .snap();
+/// ```
+#[must_use = "the snapshot is only asserted by `snap`"]
+#[derive(Clone, Debug)]
+pub struct EventStream {
+    key: String,
+    redacted: Vec<String>,
+    events: Vec<Event>,
+}
+
+impl EventStream {
+    /// Start a snapshot under `key` with no events.
+    pub fn new(key: impl Into<String>) -> EventStream {
+        EventStream {
+            key: key.into(),
+            redacted: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Replace the values at `path` within each event with `[redacted]`.
+    /// The path is a JSON pointer into the event as it's stored, whose
+    /// tokens may be globs, such as `/value/items/*/id` or
+    /// `/headers/trace-*`.
+    pub fn redact(mut self, path: impl Into<String>) -> EventStream {
+        self.redacted.push(path.into());
+        self
+    }
+
+    /// Append `event`, as published after those before it.
+    pub fn event(mut self, event: Event) -> EventStream {
+        self.events.push(event);
+        self
+    }
+
+    /// Append each of `events`, in order.
+    pub fn events(mut self, events: impl IntoIterator<Item = Event>) -> EventStream {
+        self.events.extend(events);
+        self
+    }
+
+    /// Assert the events against the snapshot under the key, as
+    /// `snap_json` would.
+    pub fn snap(self) {
+        crate::snap_json(&self.key, &self.render());
+    }
+
+    fn render(&self) -> Value {
+        let events = self.events.iter().map(|event| {
+            let mut event = event.to_value();
+            crate::json::replace(&mut event, &self.redacted, REDACTED);
+            event
+        });
+        Value::Array(events.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventStream};
+    use serde_json::json;
+
+    #[test]
+    fn offsets_timestamps_and_paths_are_redacted() {
+        let stream = EventStream::new("events")
+            .redact("/value/items/*/sku")
+            .redact("/headers/trace-*")
+            .events([
+                Event::new("orders", json!({ "items": [{ "sku": "A1", "count": 2 }] }))
+                    .key("order-7")
+                    .header("trace-id", "4bf92f35")
+                    .header("retry", "1")
+                    .header("retry", "2")
+                    .partition(3)
+                    .offset(1042)
+                    .timestamp(1_709_285_400_000),
+                Event::new("audit", "order placed"),
+            ]);
+        assert_eq!(
+            stream.render(),
+            json!([
+                {
+                    "topic": "orders",
+                    "partition": 3,
+                    "offset": "[offset]",
+                    "timestamp": "[timestamp]",
+                    "key": "order-7",
+                    "headers": { "trace-id": "[redacted]", "retry": ["1", "2"] },
+                    "value": { "items": [{ "sku": "[redacted]", "count": 2 }] },
+                },
+                { "topic": "audit", "value": "order placed" },
+            ])
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Stored snapshots with placeholders that match ranges of content.
+//!
+//! A golden can be edited by hand to replace content that is expected to
+//! vary with a placeholder, and the snapshot then matches whatever fills
+//! it in:
+//!
+//! * `{{any-uuid}}`, a UUID in any case;
+//! * `{{number}}`, an integer or decimal, optionally signed or with an
+//!   exponent;
+//! * `{{iso8601}}`, a date, optionally with a time and offset;
+//! * `{{regex:...}}`, whatever the regular expression matches, which
+//!   can't contain `}}`.
+//!
+//! Updating a snapshot that mismatches rewrites it with what the test
+//! produced, placeholders and all, so they need to be restored by hand.
+
+use regex::Regex;
+
+pub(crate) const UUID: &str =
+    "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+pub(crate) const NUMBER: &str = r"-?\d+(?:\.\d+)?(?:[eE][+-]?\d+)?";
+pub(crate) const ISO8601: &str =
+    r"\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)?";
+
+/// Whether `subject` matches `template`, a stored snapshot with
+/// placeholders. A template without any, or with a placeholder that isn't
+/// known or whose regular expression is invalid, never matches, leaving
+/// the snapshot to be compared as it is.
+pub(crate) fn matches(template: &str, subject: &str) -> bool {
+    if !template.contains("{{") {
+        return false;
+    }
+    match compile(template) {
+        Some(pattern) => pattern.is_match(subject),
+        None => false,
+    }
+}
+
+/// Compile `template` into a regular expression matching the whole of
+/// what it describes, if its placeholders are all valid and closed.
+fn compile(template: &str) -> Option<Regex> {
+    let mut pattern = String::from(r"\A");
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}")? + start;
+        pattern.push_str(&regex::escape(&rest[..start]));
+        let placeholder = match &rest[start + 2..end] {
+            "any-uuid" => UUID,
+            "number" => NUMBER,
+            "iso8601" => ISO8601,
+            inner => {
+                let regex = inner.strip_prefix("regex:")?;
+                // Checked alone so it can't reach outside its group.
+                Regex::new(regex).ok()?;
+                regex
+            }
+        };
+        pattern.push_str(&format!("(?:{})", placeholder));
+        rest = &rest[end + 2..];
+    }
+    pattern.push_str(&regex::escape(rest));
+    pattern.push_str(r"\z");
+    Regex::new(&pattern).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn placeholders_match_ranges() {
+        let template = "id: {{any-uuid}}\ntook: {{number}}ms\nat: {{iso8601}}\n";
+        assert!(matches(
+            template,
+            "id: 8F14E45F-ceea-467a-9575-0d7a8a8e1b2c\ntook: 12.5ms\nat: 2024-03-01T09:30:00Z\n"
+        ));
+        assert!(!matches(
+            template,
+            "id: 8f14e45f\ntook: 12.5ms\nat: 2024-03-01T09:30:00Z\n"
+        ));
+        assert!(!matches(
+            template,
+            "id: 8f14e45f-ceea-467a-9575-0d7a8a8e1b2c\ntook: 12.5ms\nat: 2024-03-01T09:30:00Z\nextra\n"
+        ));
+    }
+
+    #[test]
+    fn regex_placeholders() {
+        assert!(matches(
+            "build {{regex:v\\d+\\.\\d+}} (ok)",
+            "build v1.12 (ok)"
+        ));
+        assert!(!matches("build {{regex:v\\d+}} (ok)", "build v1.12 (ok)"));
+        assert!(!matches("build {{regex:(}}", "build {{regex:(}}"));
+        assert!(!matches("{{unknown}}", "{{unknown}}"));
+        assert!(!matches("no placeholders", "no placeholders"));
+    }
+}
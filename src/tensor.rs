@@ -0,0 +1,362 @@
+//! Snapshots of matrices and tensors, compared with tolerance.
+//!
+//! Arrays are stored with their shape, then their elements laid out a row
+//! per line at a fixed precision, so a diff lines up with the cells that
+//! changed. Like `snap_f64s`, a new array matches so long as each element
+//! is within tolerance of the stored one.
+
+use crate::Tolerance;
+
+/// How many differing cells a failure lists before summarizing the rest.
+const LISTED: usize = 10;
+
+/// An array of numbers that can be snapshotted with `Tensor`.
+///
+/// Implemented for slices and arrays of arrays, and, with the `ndarray`
+/// and `nalgebra` features, for their arrays and matrices.
+pub trait AsTensor {
+    /// The length of each axis, outermost first.
+    fn shape(&self) -> Vec<usize>;
+
+    /// Each element, in row-major order.
+    fn elements(&self) -> Vec<f64>;
+}
+
+impl<T: AsTensor + ?Sized> AsTensor for &T {
+    fn shape(&self) -> Vec<usize> {
+        (**self).shape()
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        (**self).elements()
+    }
+}
+
+impl<T: Copy + Into<f64>> AsTensor for [T] {
+    fn shape(&self) -> Vec<usize> {
+        vec![self.len()]
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        self.iter().map(|&x| x.into()).collect()
+    }
+}
+
+impl<T: Copy + Into<f64>> AsTensor for Vec<T> {
+    fn shape(&self) -> Vec<usize> {
+        self.as_slice().shape()
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        self.as_slice().elements()
+    }
+}
+
+impl<T: Copy + Into<f64>, const R: usize, const C: usize> AsTensor for [[T; C]; R] {
+    fn shape(&self) -> Vec<usize> {
+        vec![R, C]
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        self.iter().flatten().map(|&x| x.into()).collect()
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<S, D> AsTensor for ndarray::ArrayBase<S, D>
+where
+    S: ndarray::Data,
+    S::Elem: Clone + Into<f64>,
+    D: ndarray::Dimension,
+{
+    fn shape(&self) -> Vec<usize> {
+        ndarray::ArrayBase::shape(self).to_vec()
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        self.iter().map(|x| x.clone().into()).collect()
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T, R, C, S> AsTensor for nalgebra::Matrix<T, R, C, S>
+where
+    T: nalgebra::Scalar + Into<f64>,
+    R: nalgebra::Dim,
+    C: nalgebra::Dim,
+    S: nalgebra::RawStorage<T, R, C>,
+{
+    fn shape(&self) -> Vec<usize> {
+        vec![self.nrows(), self.ncols()]
+    }
+
+    fn elements(&self) -> Vec<f64> {
+        let (rows, columns) = self.shape();
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| self[(row, column)].clone().into()))
+            .collect()
+    }
+}
+
+/// A snapshot of a matrix or tensor, stored as its shape followed by its
+/// elements, a row per line, with each matrix of a tensor of more than
+/// two dimensions headed by its index:
+///
+/// ```text
+/// shape [2, 2, 3]
+/// [0, :, :]
+/// 1.000 2.000 3.000
+/// 4.000 5.000 6.000
+///
+/// [1, :, :]
+/// ...
+/// ```
+///
+/// The snapshot matches if it has the same shape and every element is
+/// within tolerance of the stored one, and otherwise fails as `snap`
+/// would, listing the cells out of tolerance.
+///
+/// ```
+/// use archetype::{Tensor, Tolerance};
+///
+/// let rotation = [[0.0, -1.0], [1.0, 0.0]];
+/// Tensor::new("tensor-example", &rotation)
+///     .precision(4)
+///     .tolerance(Tolerance::Absolute(1e-3))
+///     .snap();
+/// ```
+#[must_use = "the snapshot is only asserted by `snap`"]
+#[derive(Clone, Debug)]
+pub struct Tensor {
+    key: String,
+    shape: Vec<usize>,
+    elements: Vec<f64>,
+    precision: usize,
+    tolerance: Tolerance,
+}
+
+impl Tensor {
+    /// Start a snapshot under `key` of `tensor`.
+    pub fn new(key: impl Into<String>, tensor: &impl AsTensor) -> Tensor {
+        Tensor {
+            key: key.into(),
+            shape: tensor.shape(),
+            elements: tensor.elements(),
+            precision: 6,
+            tolerance: Tolerance::Ulps(0),
+        }
+    }
+
+    /// Store elements with `digits` after the decimal point. Defaults to
+    /// 6.
+    pub fn precision(mut self, digits: usize) -> Tensor {
+        self.precision = digits;
+        self
+    }
+
+    /// Tolerate elements within `tolerance` of the stored ones, as they're
+    /// stored. Defaults to only matching the same elements.
+    pub fn tolerance(mut self, tolerance: Tolerance) -> Tensor {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Assert the tensor against the one stored under the key, writing
+    /// it if it is missing.
+    pub fn snap(self) {
+        let subject = self.render();
+        let Some(stored) = crate::stored(&self.key) else {
+            return crate::snap(&self.key, subject);
+        };
+        match self.outside(&stored, &subject) {
+            // Asserting what's stored counts the snapshot as matched,
+            // and leaves it as it is.
+            None => crate::snap(&self.key, stored),
+            Some(cells) => {
+                if let Err(failure) = crate::check(&self.key, subject, false) {
+                    panic!("{}\n{}", failure, cells);
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let printed: Vec<String> = self
+            .elements
+            .iter()
+            .map(|x| format!("{:.*}", self.precision, x))
+            .collect();
+        let width = printed.iter().map(String::len).max().unwrap_or(0);
+        let mut out = format!("shape {:?}\n", self.shape);
+        let columns = match self.shape.len() {
+            0 => 1,
+            _ => self.shape[self.shape.len() - 1].max(1),
+        };
+        let rows = match self.shape.len() {
+            0 | 1 => 1,
+            n => self.shape[n - 2].max(1),
+        };
+        for (i, matrix) in printed.chunks(rows * columns).enumerate() {
+            if self.shape.len() > 2 {
+                if i > 0 {
+                    out.push('\n');
+                }
+                let mut index = position(&self.shape, i * rows * columns);
+                index.truncate(self.shape.len() - 2);
+                let index: Vec<String> = index.iter().map(usize::to_string).collect();
+                out.push_str(&format!("[{}, :, :]\n", index.join(", ")));
+            }
+            for row in matrix.chunks(columns) {
+                let row: Vec<String> = row.iter().map(|x| format!("{:>width$}", x)).collect();
+                out.push_str(&row.join(" "));
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// The cells of `subject` out of tolerance of `stored`, unless every
+    /// one is within tolerance.
+    fn outside(&self, stored: &str, subject: &str) -> Option<String> {
+        if stored.lines().next() != subject.lines().next() {
+            return Some(format!(
+                "the shape differs: {} -> {}",
+                stored.lines().next().unwrap_or_default(),
+                subject.lines().next().unwrap_or_default()
+            ));
+        }
+        let (Some(old), Some(new)) = (elements(stored), elements(subject)) else {
+            return Some(String::from("unable to read the stored elements"));
+        };
+        if old.len() != new.len() {
+            return Some(String::from("the number of elements differs"));
+        }
+        let cells: Vec<String> = old
+            .iter()
+            .zip(&new)
+            .enumerate()
+            .filter(|(_, (old, new))| self.tolerance.score(**old, **new).0 > 1.0)
+            .map(|(i, (old, new))| {
+                format!(
+                    "{:?} {:.*} -> {:.*}",
+                    position(&self.shape, i),
+                    self.precision,
+                    old,
+                    self.precision,
+                    new
+                )
+            })
+            .collect();
+        if cells.is_empty() {
+            return None;
+        }
+        let mut out = format!("{} cells out of tolerance:\n", cells.len());
+        for cell in cells.iter().take(LISTED) {
+            out.push_str(cell);
+            out.push('\n');
+        }
+        if cells.len() > LISTED {
+            out.push_str(&format!("and {} more\n", cells.len() - LISTED));
+        }
+        Some(out)
+    }
+}
+
+/// The elements of a stored tensor, skipping its shape and the headers of
+/// its matrices.
+fn elements(stored: &str) -> Option<Vec<f64>> {
+    stored
+        .lines()
+        .skip(1)
+        .filter(|line| !line.is_empty() && !line.starts_with('['))
+        .flat_map(str::split_whitespace)
+        .map(|x| x.parse().ok())
+        .collect()
+}
+
+/// The index along each axis of `shape` of the element at `offset`, in
+/// row-major order.
+fn position(shape: &[usize], mut offset: usize) -> Vec<usize> {
+    let mut index = vec![0; shape.len()];
+    for (axis, &length) in shape.iter().enumerate().rev() {
+        if length > 0 {
+            index[axis] = offset % length;
+            offset /= length;
+        }
+    }
+    index
+}
+
+/// Take a snapshot of `tensor` under a file with the name `key`, as
+/// `Tensor` would with its default precision and tolerance.
+///
+/// ```
+/// archetype::snap_tensor("tensor-identity-example", &[[1, 0], [0, 1]]);
+/// ```
+pub fn snap_tensor(key: &str, tensor: &impl AsTensor) {
+    Tensor::new(key, tensor).snap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{position, Tensor};
+    use crate::Tolerance;
+
+    #[test]
+    fn tensors_are_laid_out_by_row() {
+        let matrix = Tensor::new("tensor_matrix", &[[1.0, -2.5], [10.0, 0.25]]).precision(2);
+        assert_eq!(matrix.render(), "shape [2, 2]\n 1.00 -2.50\n10.00  0.25\n");
+        matrix.snap();
+
+        let cube: Vec<i32> = (0..8).collect();
+        let mut cube = Tensor::new("tensor_cube", &cube).precision(0);
+        cube.shape = vec![2, 2, 2];
+        assert_eq!(
+            cube.render(),
+            "shape [2, 2, 2]\n[0, :, :]\n0 1\n2 3\n\n[1, :, :]\n4 5\n6 7\n"
+        );
+        assert_eq!(position(&[2, 3, 4], 17), [1, 1, 1]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn ndarrays_are_read_in_row_major_order() {
+        use super::AsTensor;
+        let array =
+            ndarray::Array::from_shape_vec((2, 1, 3), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let transposed = array.unwrap().reversed_axes();
+        assert_eq!(AsTensor::shape(&transposed), [3, 1, 2]);
+        assert_eq!(transposed.elements(), [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn nalgebra_matrices_are_read_in_row_major_order() {
+        use super::AsTensor;
+        let matrix = nalgebra::Matrix2x3::new(1, 2, 3, 4, 5, 6);
+        assert_eq!(AsTensor::shape(&matrix), [2, 3]);
+        assert_eq!(matrix.elements(), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn differing_cells_are_listed() {
+        let stored = Tensor::new("tensor", &[[1.0, 2.0], [3.0, 4.0]]).precision(3);
+        let subject = Tensor::new("tensor", &[[1.0005, 2.0], [3.0, 4.5]])
+            .precision(3)
+            .tolerance(Tolerance::Absolute(0.01));
+        assert_eq!(
+            subject
+                .outside(&stored.render(), &subject.render())
+                .as_deref(),
+            Some("1 cells out of tolerance:\n[1, 1] 4.000 -> 4.500\n")
+        );
+        let subject = subject.tolerance(Tolerance::Absolute(1.0));
+        assert_eq!(subject.outside(&stored.render(), &subject.render()), None);
+
+        let wider = Tensor::new("tensor", &[[1.0, 2.0, 3.0]]).precision(3);
+        assert_eq!(
+            wider.outside(&stored.render(), &wider.render()).as_deref(),
+            Some("the shape differs: shape [2, 2] -> shape [1, 3]")
+        );
+    }
+}
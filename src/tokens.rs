@@ -0,0 +1,74 @@
+//! Snapshots of Rust syntax, from `syn` and `proc_macro2`.
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+
+/// The function that statements are wrapped in to be formatted.
+const WRAPPER: &str = "__archetype_snapshot";
+
+/// Take a snapshot of Rust syntax, such as a `proc_macro2::TokenStream`
+/// or any `syn` node, under a file with the name `key`.
+///
+/// Token streams print on a single line with spaces between every token,
+/// which is unreadable in a diff. The syntax is formatted with
+/// `prettyplease` instead, so the output of a proc-macro or code generator
+/// can be golden tested as the code it is. Items are formatted as a file
+/// and expressions or statements as the body of a function. Anything
+/// else, such as a lone type, is stored as its tokens.
+///
+/// ```
+/// let expansion: proc_macro2::TokenStream = "impl Search { fn term(&self) -> &str { &self.term } }"
+///     .parse()
+///     .unwrap();
+/// archetype::snap_tokens("tokens-example", &expansion);
+/// ```
+pub fn snap_tokens<T: ToTokens>(key: &str, syntax: &T) {
+    crate::snap(key, pretty(syntax.to_token_stream()));
+}
+
+/// Format `tokens` as described by `snap_tokens`.
+fn pretty(tokens: TokenStream) -> String {
+    if let Ok(file) = syn::parse2::<syn::File>(tokens.clone()) {
+        return prettyplease::unparse(&file);
+    }
+    let wrapped = format!("fn {}() {{ {} }}", WRAPPER, tokens);
+    let Ok(file) = syn::parse_str::<syn::File>(&wrapped) else {
+        return format!("{}\n", tokens);
+    };
+    let formatted = prettyplease::unparse(&file);
+    let lines: Vec<&str> = formatted.lines().collect();
+    let mut out = String::new();
+    for line in &lines[1..lines.len() - 1] {
+        out.push_str(line.strip_prefix("    ").unwrap_or(line));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pretty, snap_tokens};
+    use proc_macro2::TokenStream;
+
+    fn tokens(source: &str) -> TokenStream {
+        source.parse().unwrap()
+    }
+
+    #[test]
+    fn formats_expressions_and_statements() {
+        assert_eq!(
+            pretty(tokens("let ids = vec ! [4 , 7 , 9] ; ids . len ()")),
+            "let ids = vec![4, 7, 9];\nids.len()\n"
+        );
+        assert_eq!(pretty(tokens("Vec < u8 >")), "Vec < u8 >\n");
+    }
+
+    #[test]
+    fn snapshot_derived_impl() {
+        let item: syn::ItemImpl = syn::parse2(tokens(
+            "impl Search { pub fn by_term(term: String) -> Search { Search::ByTerm { term } } }",
+        ))
+        .unwrap();
+        snap_tokens("tokens_impl", &item);
+    }
+}
@@ -0,0 +1,122 @@
+//! External diff tools, such as delta, difftastic, or meld, which show
+//! mismatches in place of the built-in rendering in interactive runs.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many diffs have been shown, so each gets its own copies.
+static SHOWN: AtomicUsize = AtomicUsize::new(0);
+
+/// Show how `subject` differs from `stored`, the snapshot under `key`,
+/// with the diff tool `command`. See `GlobalConfig::diff_tool`.
+///
+/// The tool writes straight to the terminal rather than through the test
+/// harness, and what it exits with is ignored, as many diff tools exit
+/// nonzero whenever there are differences. The copies of each side it's
+/// given are removed once it exits.
+pub(crate) fn show(command: &str, key: &str, stored: &str, subject: &str) -> io::Result<()> {
+    let shown = SHOWN.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join("archetype-diff").join(format!(
+        "{}-{}",
+        std::process::id(),
+        shown
+    ));
+    let result = run(command, &dir, key, stored, subject);
+    match std::fs::remove_dir_all(&dir) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => result.and(Err(err)),
+        _ => result,
+    }
+}
+
+/// Copy each side under `dir`, and run the tool on the copies.
+fn run(command: &str, dir: &Path, key: &str, stored: &str, subject: &str) -> io::Result<()> {
+    let old = write(&dir.join("old"), key, stored)?;
+    let new = write(&dir.join("new"), key, subject)?;
+    let args = args(command, &old, &new);
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the diff tool is empty"))?;
+    Command::new(program).args(args).status()?;
+    Ok(())
+}
+
+/// Write `contents` under `dir` at the path `key`, keeping the key's
+/// name so tools that highlight by extension can.
+fn write(dir: &Path, key: &str, contents: &str) -> io::Result<PathBuf> {
+    let path = dir.join(key);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Split `command` on whitespace into a program and its arguments,
+/// replacing `{old}` and `{new}` with the paths to each side. Without
+/// either placeholder, the paths are passed last, old then new.
+fn args(command: &str, old: &Path, new: &Path) -> Vec<String> {
+    let old = old.to_string_lossy();
+    let new = new.to_string_lossy();
+    let mut args: Vec<String> = command
+        .split_whitespace()
+        .map(|arg| arg.replace("{old}", &old).replace("{new}", &new))
+        .collect();
+    if !command.contains("{old}") && !command.contains("{new}") {
+        args.push(old.into_owned());
+        args.push(new.into_owned());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::args;
+    use std::path::Path;
+
+    #[test]
+    fn placeholders_are_replaced_with_paths() {
+        let (old, new) = (Path::new("/tmp/old/search"), Path::new("/tmp/new/search"));
+        assert_eq!(
+            args("difft --color always {old} {new}", old, new),
+            [
+                "difft",
+                "--color",
+                "always",
+                "/tmp/old/search",
+                "/tmp/new/search"
+            ]
+        );
+        assert_eq!(
+            args("meld", old, new),
+            ["meld", "/tmp/old/search", "/tmp/new/search"]
+        );
+    }
+
+    #[test]
+    fn tools_run_on_copies_of_each_side() {
+        let dir = std::env::temp_dir().join(format!("archetype-tool-{}", std::process::id()));
+        let report = dir.join("report");
+        std::fs::create_dir_all(&dir).unwrap();
+        super::show(
+            &format!("cp {{new}} {}", report.display()),
+            "api/users.json",
+            "[]\n",
+            "[4]\n",
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&report).unwrap(), "[4]\n");
+
+        let link = dir.join("link");
+        super::show(
+            &format!("ln -s {{old}} {}", link.display()),
+            "users",
+            "[]\n",
+            "[4]\n",
+        )
+        .unwrap();
+        assert!(!std::fs::read_link(&link).unwrap().exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+//! The stages every snapshot passes through before it is compared or
+//! stored.
+
+use crate::{GlobalConfig, Settings};
+use std::fmt;
+use std::sync::Arc;
+
+type Transform = dyn Fn(&str) -> String + Send + Sync;
+
+/// A stage of the pipeline that snapshots pass through before they are
+/// compared or stored, registered with `Settings::transform`.
+///
+/// By default the pipeline applies the configured filters, and then
+/// redacts the frozen time. Normalizing line endings and sorting the keys
+/// of JSON are left to be added, along with any custom stages:
+///
+/// ```
+/// use archetype::{Settings, SnapshotTransform};
+///
+/// Settings::new()
+///     .transforms([
+///         SnapshotTransform::normalize_line_endings(),
+///         SnapshotTransform::filters(),
+///         SnapshotTransform::redact(),
+///         SnapshotTransform::sort_keys(),
+///     ])
+///     .transform(SnapshotTransform::new("trim", |subject| subject.trim_end().to_string() + "\n"))
+///     .bind(|| archetype::snap("transform-example", String::from("done\r\n\r\n")));
+/// ```
+#[derive(Clone)]
+pub struct SnapshotTransform {
+    name: String,
+    stage: Stage,
+}
+
+#[derive(Clone)]
+enum Stage {
+    LineEndings,
+    Filters,
+    Redact,
+    SortKeys,
+    Custom(Arc<Transform>),
+}
+
+impl SnapshotTransform {
+    /// Transform with `transform`, named `name` when debugging.
+    pub fn new(
+        name: impl Into<String>,
+        transform: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> SnapshotTransform {
+        SnapshotTransform {
+            name: name.into(),
+            stage: Stage::Custom(Arc::new(transform)),
+        }
+    }
+
+    /// Replace `\r\n` line endings with `\n`.
+    pub fn normalize_line_endings() -> SnapshotTransform {
+        SnapshotTransform::builtin("normalize-line-endings", Stage::LineEndings)
+    }
+
+    /// Apply the filters added with `GlobalConfig::filter` and
+    /// `GlobalConfig::scrub`, in the order they were added.
+    pub fn filters() -> SnapshotTransform {
+        SnapshotTransform::builtin("filters", Stage::Filters)
+    }
+
+    /// Redact the time frozen with `Settings::freeze_time`, when the
+    /// `clock` feature is enabled.
+    pub fn redact() -> SnapshotTransform {
+        SnapshotTransform::builtin("redact", Stage::Redact)
+    }
+
+    /// Sort the keys of every object in a snapshot that is JSON, pretty
+    /// printing it as `snap_json` would. Anything else is left alone.
+    pub fn sort_keys() -> SnapshotTransform {
+        SnapshotTransform::builtin("sort-keys", Stage::SortKeys)
+    }
+
+    fn builtin(name: &str, stage: Stage) -> SnapshotTransform {
+        SnapshotTransform {
+            name: name.to_string(),
+            stage,
+        }
+    }
+
+    fn apply(&self, config: &GlobalConfig, settings: &Settings, subject: String) -> String {
+        match &self.stage {
+            Stage::LineEndings => subject.replace("\r\n", "\n"),
+            Stage::Filters => config.apply_filters(subject),
+            #[cfg(feature = "clock")]
+            Stage::Redact => crate::clock::redact(settings, subject),
+            #[cfg(not(feature = "clock"))]
+            Stage::Redact => subject,
+            Stage::SortKeys => sort_keys(settings, subject),
+            Stage::Custom(transform) => transform(&subject),
+        }
+    }
+}
+
+impl fmt::Debug for SnapshotTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SnapshotTransform")
+            .field(&self.name)
+            .finish()
+    }
+}
+
+/// The pipeline snapshots pass through when the settings don't replace it.
+pub(crate) fn defaults() -> Vec<SnapshotTransform> {
+    vec![SnapshotTransform::filters(), SnapshotTransform::redact()]
+}
+
+/// Pass `subject` through each stage of the pipeline in `settings`, in
+/// order, after dropping any byte order mark, as is done when reading
+/// snapshots.
+pub(crate) fn apply(config: &GlobalConfig, settings: &Settings, subject: String) -> String {
+    let subject = crate::store::strip_bom(subject);
+    settings.transforms.iter().fold(subject, |subject, stage| {
+        stage.apply(config, settings, subject)
+    })
+}
+
+/// `subject` pretty printed with its keys sorted, if it is JSON, keeping
+/// a trailing newline.
+fn sort_keys(settings: &Settings, subject: String) -> String {
+    // Without `preserve_order`, objects are kept in sorted maps.
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(&subject) else {
+        return subject;
+    };
+    let sorted = crate::json::pretty(&value, settings);
+    match subject.ends_with('\n') {
+        true => sorted + "\n",
+        false => sorted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, SnapshotTransform};
+    use crate::{GlobalConfig, Settings};
+
+    #[test]
+    fn stages_run_in_order() {
+        let config = GlobalConfig::new().filter(r"\d{4}-\d{2}-\d{2}", "[date]");
+        let settings = Settings::new().transforms([
+            SnapshotTransform::normalize_line_endings(),
+            SnapshotTransform::filters(),
+            SnapshotTransform::sort_keys(),
+            SnapshotTransform::new("shout", |subject| subject.to_uppercase()),
+        ]);
+        assert_eq!(
+            apply(
+                &config,
+                &settings,
+                String::from("{\"type\": \"by-term\",\r\n\"at\": \"2023-10-21\"}\r\n")
+            ),
+            "{\n  \"AT\": \"[DATE]\",\n  \"TYPE\": \"BY-TERM\"\n}\n"
+        );
+        assert_eq!(
+            apply(&config, &settings, String::from("not json\r\n")),
+            "NOT JSON\n"
+        );
+    }
+
+    #[test]
+    fn filters_are_applied_by_default() {
+        let config = GlobalConfig::new().filter(r"\d+ms", "[duration]");
+        let settings = Settings::new().transform(SnapshotTransform::new("trim", |subject| {
+            subject.trim().to_string()
+        }));
+        assert_eq!(
+            apply(&config, &settings, String::from(" took 12ms \n")),
+            "took [duration]"
+        );
+    }
+}
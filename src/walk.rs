@@ -0,0 +1,347 @@
+//! Snapshots of graph-shaped data.
+//!
+//! Serde and `Debug` both follow every reference they are given, so data
+//! built from `Rc` and `RefCell` either loops forever, when it is cyclic,
+//! or repeats shared nodes wherever they are referenced. Walking it with
+//! `Walk` instead prints each node once: a node that is reached more than
+//! once is labelled, as `&1`, where it is first printed, and printed as
+//! `*1` wherever else it is reached.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+/// Data that can be walked into a snapshot by `snap_walk`.
+///
+/// Implement it for each kind of node, describing its fields and the
+/// nodes it refers to with the `Walker`. It is already implemented for
+/// `Rc`, `Weak`, `Arc`, `Box`, and `RefCell` around nodes, so that links
+/// between nodes can be walked as they are.
+pub trait Walk {
+    /// The name printed for the node. Defaults to the name of its type.
+    fn name(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        let path = name.split('<').next().unwrap_or(name);
+        let short = path.rsplit("::").next().unwrap_or(path);
+        format!("{}{}", short, &name[path.len()..])
+    }
+
+    /// Describe the fields of the node and the nodes it refers to.
+    fn walk(&self, walker: &mut Walker<'_>);
+
+    /// What makes the node the same node wherever it is reached, by
+    /// default its address.
+    fn address(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+}
+
+/// A node reached while walking: its address and name, as a node and the
+/// first of its fields can share an address.
+type Id = (usize, String);
+
+#[derive(Default)]
+struct State {
+    /// Whether nodes are only being counted, before they are printed.
+    counting: bool,
+    visits: HashMap<Id, usize>,
+    labels: HashMap<Id, usize>,
+    out: String,
+}
+
+/// Describes the node being walked. See `Walk`.
+pub struct Walker<'a> {
+    state: &'a mut State,
+    depth: usize,
+}
+
+impl Walker<'_> {
+    fn line(&mut self, depth: usize) {
+        self.state.out.push('\n');
+        self.state.out.push_str(&"    ".repeat(depth));
+    }
+
+    /// Print the field `name` with its `Debug` representation.
+    pub fn field(&mut self, name: &str, value: impl Debug) -> &mut Self {
+        if !self.state.counting {
+            self.line(self.depth);
+            self.state.out.push_str(&format!("{}: {:?},", name, value));
+        }
+        self
+    }
+
+    /// Walk into the node `child`, referred to by the field `name`.
+    pub fn child<T: Walk + ?Sized>(&mut self, name: &str, child: &T) -> &mut Self {
+        if !self.state.counting {
+            self.line(self.depth);
+            self.state.out.push_str(&format!("{}: ", name));
+        }
+        visit(self.state, child, self.depth);
+        if !self.state.counting {
+            self.state.out.push(',');
+        }
+        self
+    }
+
+    /// Walk into each of the nodes `children`, referred to as a list by
+    /// the field `name`.
+    pub fn children<'c, T: Walk + 'c>(
+        &mut self,
+        name: &str,
+        children: impl IntoIterator<Item = &'c T>,
+    ) -> &mut Self {
+        if self.state.counting {
+            for child in children {
+                visit(self.state, child, self.depth);
+            }
+            return self;
+        }
+        self.line(self.depth);
+        self.state.out.push_str(&format!("{}: [", name));
+        let start = self.state.out.len();
+        for child in children {
+            self.line(self.depth + 1);
+            visit(self.state, child, self.depth + 1);
+            self.state.out.push(',');
+        }
+        if self.state.out.len() > start {
+            self.line(self.depth);
+        }
+        self.state.out.push_str("],");
+        self
+    }
+}
+
+/// Count or print `node`, at `depth`, from where the walker left off.
+fn visit<T: Walk + ?Sized>(state: &mut State, node: &T, depth: usize) {
+    let id = (node.address() as usize, node.name());
+    if state.counting {
+        let visits = state.visits.entry(id).or_default();
+        *visits += 1;
+        if *visits == 1 {
+            node.walk(&mut Walker {
+                state,
+                depth: depth + 1,
+            });
+        }
+        return;
+    }
+    if let Some(label) = state.labels.get(&id) {
+        state.out.push_str(&format!("*{}", label));
+        return;
+    }
+    if state.visits.get(&id).is_some_and(|visits| *visits > 1) {
+        let label = state.labels.len() + 1;
+        state.out.push_str(&format!("&{} ", label));
+        state.labels.insert(id.clone(), label);
+    }
+    state.out.push_str(&id.1);
+    let start = state.out.len();
+    node.walk(&mut Walker {
+        state,
+        depth: depth + 1,
+    });
+    // Nodes without fields print like unit structs do.
+    if state.out.len() > start {
+        state.out.insert_str(start, " {");
+        state.out.push('\n');
+        state.out.push_str(&"    ".repeat(depth));
+        state.out.push('}');
+    }
+}
+
+/// Print `root` and everything reachable from it, labelling the nodes
+/// that are reached more than once.
+pub(crate) fn render<T: Walk + ?Sized>(root: &T) -> String {
+    let mut state = State {
+        counting: true,
+        ..State::default()
+    };
+    visit(&mut state, root, 0);
+    state.counting = false;
+    visit(&mut state, root, 0);
+    state.out.push('\n');
+    state.out
+}
+
+/// Take a snapshot of `root` and everything reachable from it, printed
+/// by walking it with `Walk`, so cyclic and shared structures print once
+/// and safely.
+///
+/// ```
+/// use archetype::{Walk, Walker};
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// struct Task {
+///     name: &'static str,
+///     after: Vec<Rc<RefCell<Task>>>,
+/// }
+///
+/// impl Walk for Task {
+///     fn walk(&self, walker: &mut Walker<'_>) {
+///         walker.field("name", self.name).children("after", &self.after);
+///     }
+/// }
+///
+/// let fetch = Rc::new(RefCell::new(Task { name: "fetch", after: vec![] }));
+/// let build = Rc::new(RefCell::new(Task { name: "build", after: vec![fetch.clone()] }));
+/// let test = Task { name: "test", after: vec![fetch, build] };
+/// archetype::snap_walk("walk-example", &test);
+/// ```
+pub fn snap_walk<T: Walk + ?Sized>(key: &str, root: &T) {
+    crate::snap(key, render(root));
+}
+
+impl<T: Walk + ?Sized> Walk for Rc<T> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn walk(&self, walker: &mut Walker<'_>) {
+        (**self).walk(walker);
+    }
+
+    fn address(&self) -> *const () {
+        (**self).address()
+    }
+}
+
+impl<T: Walk + ?Sized> Walk for Arc<T> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn walk(&self, walker: &mut Walker<'_>) {
+        (**self).walk(walker);
+    }
+
+    fn address(&self) -> *const () {
+        (**self).address()
+    }
+}
+
+impl<T: Walk + ?Sized> Walk for Box<T> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn walk(&self, walker: &mut Walker<'_>) {
+        (**self).walk(walker);
+    }
+
+    fn address(&self) -> *const () {
+        (**self).address()
+    }
+}
+
+impl<T: Walk> Walk for Weak<T> {
+    fn name(&self) -> String {
+        match self.upgrade() {
+            Some(node) => node.name(),
+            None => String::from("(dropped)"),
+        }
+    }
+
+    fn walk(&self, walker: &mut Walker<'_>) {
+        if let Some(node) = self.upgrade() {
+            node.walk(walker);
+        }
+    }
+
+    fn address(&self) -> *const () {
+        self.as_ptr() as *const ()
+    }
+}
+
+impl<T: Walk + ?Sized> Walk for RefCell<T> {
+    fn name(&self) -> String {
+        match self.try_borrow() {
+            Ok(node) => node.name(),
+            Err(_) => String::from("(mutably borrowed)"),
+        }
+    }
+
+    fn walk(&self, walker: &mut Walker<'_>) {
+        if let Ok(node) = self.try_borrow() {
+            node.walk(walker);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, snap_walk, Walk, Walker};
+    use std::cell::RefCell;
+    use std::rc::{Rc, Weak};
+
+    #[derive(Default)]
+    struct Node {
+        label: &'static str,
+        parent: Option<Weak<RefCell<Node>>>,
+        children: Vec<Rc<RefCell<Node>>>,
+    }
+
+    impl Walk for Node {
+        fn walk(&self, walker: &mut Walker<'_>) {
+            walker.field("label", self.label);
+            if let Some(parent) = &self.parent {
+                walker.child("parent", parent);
+            }
+            walker.children("children", &self.children);
+        }
+    }
+
+    fn node(label: &'static str) -> Rc<RefCell<Node>> {
+        Rc::new(RefCell::new(Node {
+            label,
+            ..Node::default()
+        }))
+    }
+
+    #[test]
+    fn shared_nodes_are_printed_once() {
+        let shared = node("shared");
+        let root = node("root");
+        root.borrow_mut().children = vec![shared.clone(), shared];
+        assert_eq!(
+            render(&root),
+            "Node {
+    label: \"root\",
+    children: [
+        &1 Node {
+            label: \"shared\",
+            children: [],
+        },
+        *1,
+    ],
+}
+"
+        );
+    }
+
+    #[test]
+    fn nodes_without_fields_print_bare() {
+        struct Leaf;
+
+        impl Walk for Leaf {
+            fn walk(&self, _: &mut Walker<'_>) {}
+        }
+
+        assert_eq!(render(&Leaf), "Leaf\n");
+        assert_eq!(render(&Weak::<Leaf>::new()), "(dropped)\n");
+    }
+
+    #[test]
+    fn snapshot_cyclic_tree() {
+        let root = node("root");
+        for label in ["left", "right"] {
+            let child = node(label);
+            child.borrow_mut().parent = Some(Rc::downgrade(&root));
+            root.borrow_mut().children.push(child);
+        }
+        snap_walk("walk_tree", &root);
+    }
+}
@@ -0,0 +1,173 @@
+//! Snapshots written incrementally by code that streams its output.
+
+use std::fmt;
+use std::io;
+
+/// A sink that compares output against the snapshot under a key as it is
+/// written, for report generators, encoders, and anything else that
+/// streams its output rather than returning a string.
+///
+/// While the output matches the stored snapshot nothing is buffered; only
+/// output past the point where it first differs is kept, to report and
+/// store once the writer is finished. Output that matches throughout is
+/// never held in memory twice.
+///
+/// The output must be UTF-8. Call `finish` to assert once everything has
+/// been written.
+///
+/// ```
+/// use std::io::Write;
+///
+/// let mut writer = archetype::SnapshotWriter::new("writer-example");
+/// for id in [4, 7, 9] {
+///     writeln!(writer, "id: {}", id).unwrap();
+/// }
+/// writer.finish();
+/// ```
+#[must_use = "the snapshot is only asserted by `finish`"]
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    key: String,
+    /// The stored snapshot, when the output can be compared as it is
+    /// written.
+    stored: Option<String>,
+    /// How many bytes of the stored snapshot the output has matched.
+    matched: usize,
+    /// Everything written after the output first differed.
+    rest: Option<Vec<u8>>,
+}
+
+impl SnapshotWriter {
+    /// Create a writer for the snapshot under `key`.
+    pub fn new(key: impl Into<String>) -> SnapshotWriter {
+        let key = key.into();
+        let (config, settings) = crate::overrides::resolve(&key);
+        let config = &*config;
+        // Filters rewrite the whole output, so whether it matches can't be
+        // known until it is complete.
+        let stored = if config.filters.is_empty() {
+            let (storage, _) = crate::storage(config, &settings);
+            crate::read(&*storage, &key)
+                .unwrap_or_else(|err| panic!("{}", err))
+                .1
+                .map(|stored| crate::meta::split(&stored).1.to_string())
+        } else {
+            None
+        };
+        let rest = match stored {
+            Some(_) => None,
+            None => Some(Vec::new()),
+        };
+        SnapshotWriter {
+            key,
+            stored,
+            matched: 0,
+            rest,
+        }
+    }
+
+    fn push(&mut self, buf: &[u8]) {
+        if let Some(rest) = &mut self.rest {
+            rest.extend_from_slice(buf);
+            return;
+        }
+        let stored = self.stored.as_deref().unwrap_or_default().as_bytes();
+        let remaining = &stored[self.matched..];
+        let common = remaining
+            .iter()
+            .zip(buf)
+            .take_while(|(stored, written)| stored == written)
+            .count();
+        self.matched += common;
+        if common < buf.len() {
+            self.rest = Some(buf[common..].to_vec());
+        }
+    }
+
+    /// Assert that everything written matches the snapshot, exactly as
+    /// `snap` would, writing it if it is missing.
+    ///
+    /// Panics if the output isn't UTF-8.
+    pub fn finish(self) {
+        let stored = self.stored.unwrap_or_default();
+        let subject = match self.rest {
+            None if self.matched == stored.len() => stored,
+            rest => {
+                let mut subject = stored.into_bytes();
+                subject.truncate(self.matched);
+                subject.extend(rest.unwrap_or_default());
+                String::from_utf8(subject).unwrap_or_else(|err| {
+                    panic!("snapshot output for {} should be UTF-8: {}", self.key, err)
+                })
+            }
+        };
+        crate::snap(&self.key, subject);
+    }
+}
+
+impl io::Write for SnapshotWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.push(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Write for SnapshotWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotWriter;
+
+    #[test]
+    fn snapshot_streamed_json() {
+        #[derive(serde::Serialize)]
+        struct Search {
+            r#type: &'static str,
+            ids: Vec<i64>,
+        }
+
+        let mut writer = SnapshotWriter::new("search_by_ids");
+        let search = Search {
+            r#type: "by-ids",
+            ids: vec![4, 7, 9],
+        };
+        serde_json::to_writer_pretty(&mut writer, &search).unwrap();
+        assert!(writer.rest.is_none());
+        writer.finish();
+    }
+
+    #[test]
+    fn snapshot_streamed_text() {
+        use std::fmt::Write;
+
+        let mut writer = SnapshotWriter::new("writer_report");
+        for section in ["headers", "body", "trailers"] {
+            writeln!(writer, "== {} ==", section).unwrap();
+        }
+        writer.finish();
+    }
+
+    #[test]
+    fn keeps_only_output_past_a_difference() {
+        let mut writer = SnapshotWriter {
+            key: String::from("unused"),
+            stored: Some(String::from("abcdef")),
+            matched: 0,
+            rest: None,
+        };
+        writer.push(b"ab");
+        writer.push(b"cx");
+        writer.push(b"yz");
+        assert_eq!(writer.matched, 3);
+        assert_eq!(writer.rest.as_deref(), Some(&b"xyz"[..]));
+    }
+}
@@ -0,0 +1,17 @@
+//! Snapshots kept in a configured storage are asserted without resolving
+//! a snapshot directory, so a binary run outside of cargo, where
+//! `CARGO_MANIFEST_DIR` isn't set, can still assert them.
+//!
+//! This runs without the test harness, so the variable is removed before
+//! any other thread could be reading the environment.
+
+use archetype::store::BundledStorage;
+use archetype::{GlobalConfig, Update};
+
+fn main() {
+    std::env::remove_var("CARGO_MANIFEST_DIR");
+    let storage = BundledStorage::new().with("greeting", "hello\n");
+    archetype::configure(GlobalConfig::new().storage(storage).update(Update::Never))
+        .expect("nothing else should configure archetype");
+    archetype::snap("greeting", String::from("hello\n"));
+}
@@ -0,0 +1,28 @@
+//! What code prints is captured from the process's stdout and stderr.
+//!
+//! The test harness captures what tests print before it reaches stdout,
+//! so this runs without it.
+
+#[cfg(unix)]
+fn main() {
+    use std::io::Write;
+
+    let code = archetype::snap_output("output_streams", || {
+        println!("searching for by-term");
+        eprintln!("warning: no limit given");
+        std::io::stdout().write_all(b"3 results\n").unwrap();
+        0
+    });
+    assert_eq!(code, 0);
+
+    // The streams are put back even if the code panics.
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = std::panic::catch_unwind(|| {
+        archetype::snap_output("output_panicked", || panic!("search failed"))
+    });
+    let _ = std::panic::take_hook();
+    assert!(panicked.is_err());
+}
+
+#[cfg(not(unix))]
+fn main() {}
@@ -0,0 +1,11 @@
+//! Archetype is configured once per process, so this runs in a test
+//! binary of its own, where configuring it can't change the config the
+//! library's unit tests run with.
+
+use archetype::GlobalConfig;
+
+#[test]
+fn configure_only_once() {
+    assert!(archetype::configure(GlobalConfig::new()).is_ok());
+    assert!(archetype::configure(GlobalConfig::new()).is_err());
+}
@@ -0,0 +1,46 @@
+//! The `cargo archetype merge-driver` git runs on conflicting snapshots.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn file(dir: &std::path::Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn snapshots_with_headers_are_merged() {
+    // Keys are left in the order they were written, not sorted, so the
+    // merged snapshot still matches the output it was taken from.
+    let dir = std::env::temp_dir().join(format!("archetype-merge-driver-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let base = file(
+        &dir,
+        "base",
+        "archetype-updated-by: ada\n---\n{\n  \"type\": \"by-ids\",\n  \"ids\": [\n    4,\n    7\n  ]\n}",
+    );
+    let current = file(
+        &dir,
+        "current",
+        "archetype-updated-by: ada\narchetype-locked: true\n---\n{\n  \"type\": \"by-ids\",\n  \"ids\": [\n    4,\n    9\n  ]\n}",
+    );
+    let other = file(
+        &dir,
+        "other",
+        "archetype-updated-by: bob\n---\n{\n  \"type\": \"by-term\",\n  \"ids\": [\n    4,\n    7\n  ]\n}",
+    );
+
+    let status = Command::new(env!("CARGO_BIN_EXE_cargo-archetype"))
+        .arg("archetype")
+        .arg("merge-driver")
+        .args([&base, &current, &other])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(&current).unwrap(),
+        "archetype-updated-by: bob\narchetype-locked: true\n---\n{\n  \"type\": \"by-term\",\n  \"ids\": [\n    4,\n    9\n  ]\n}"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
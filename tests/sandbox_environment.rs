@@ -0,0 +1,27 @@
+//! A sandbox sets the variables it's given while the fixture runs, and
+//! puts the environment back afterwards, even if the fixture panics.
+//!
+//! Changing the environment while other threads may be reading it is
+//! unsound, so this runs without the test harness, in a process of its
+//! own, rather than alongside the library's unit tests.
+
+use archetype::Sandbox;
+
+fn main() {
+    let seen = Sandbox::new()
+        .var("ARCHETYPE_SANDBOX_TEST", "inside")
+        .run(|| (std::env::var("TZ"), std::env::var("ARCHETYPE_SANDBOX_TEST")));
+    assert_eq!(seen, (Ok(String::from("UTC")), Ok(String::from("inside"))));
+    assert_eq!(std::env::var_os("ARCHETYPE_SANDBOX_TEST"), None);
+
+    // The panic is expected, so it isn't reported.
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = std::panic::catch_unwind(|| {
+        Sandbox::new()
+            .var("ARCHETYPE_SANDBOX_TEST", "inside")
+            .run(|| panic!("fixture failed"))
+    });
+    let _ = std::panic::take_hook();
+    assert!(panicked.is_err());
+    assert_eq!(std::env::var_os("ARCHETYPE_SANDBOX_TEST"), None);
+}